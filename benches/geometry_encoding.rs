@@ -0,0 +1,93 @@
+// Compares encoding geometries as WKT and as WKB for the transfer to PostGIS
+// Run with: cargo bench --bench geometry_encoding [-- <features>]
+use duckdb::Connection;
+use std::time::{Duration, Instant};
+
+const DEFAULT_FEATURES: usize = 200_000;
+
+struct Encoding {
+    name: &'static str,
+    encode: &'static str,
+    decode: &'static str,
+}
+
+const ENCODINGS: [Encoding; 2] = [
+    Encoding {
+        name: "WKT",
+        encode: "ST_AsText(geom)",
+        decode: "ST_GeomFromText(encoded)",
+    },
+    Encoding {
+        name: "WKB",
+        encode: "ST_AsWKB(geom)::BLOB",
+        decode: "ST_GeomFromWKB(encoded)",
+    },
+];
+
+fn time(conn: &Connection, sql: &str) -> Result<Duration, duckdb::Error> {
+    let started = Instant::now();
+    conn.execute_batch(sql)?;
+    Ok(started.elapsed())
+}
+
+fn main() -> Result<(), duckdb::Error> {
+    let features = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_FEATURES);
+
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch("INSTALL spatial; LOAD spatial;")?;
+
+    // Buffered points give polygons of 33 vertices with full-precision coordinates
+    conn.execute_batch(&format!(
+        "CREATE TABLE features AS
+        SELECT ST_Buffer(ST_Point(random() * 360 - 180, random() * 180 - 90), 0.01) AS geom
+        FROM range({});",
+        features
+    ))?;
+
+    println!("{} polygon features", features);
+    for encoding in &ENCODINGS {
+        let encode = time(
+            &conn,
+            &format!(
+                "CREATE OR REPLACE TABLE encoded AS SELECT {} AS encoded FROM features;",
+                encoding.encode
+            ),
+        )?;
+        let bytes: i64 = conn.query_row(
+            "SELECT sum(octet_length(encoded::BLOB)) FROM encoded;",
+            [],
+            |row| row.get(0),
+        )?;
+        let decode = time(
+            &conn,
+            &format!(
+                "CREATE OR REPLACE TABLE decoded AS SELECT {} AS geom FROM encoded;",
+                encoding.decode
+            ),
+        )?;
+
+        // Geometries that do not come back exactly show the precision lost by the encoding
+        let changed: i64 = conn.query_row(
+            "SELECT count(*) FROM (SELECT geom, row_number() OVER () AS i FROM features) f
+            JOIN (SELECT geom, row_number() OVER () AS i FROM decoded) d USING (i)
+            WHERE NOT ST_Equals(f.geom, d.geom) OR ST_AsWKB(f.geom) <> ST_AsWKB(d.geom);",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let seconds = (encode + decode).as_secs_f64();
+        println!(
+            "{}: encode {:?}, decode {:?}, {:.0} features/s, {:.1} MB, {} geometries changed",
+            encoding.name,
+            encode,
+            decode,
+            features as f64 / seconds,
+            bytes as f64 / 1_000_000.0,
+            changed
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+// Compares copying a wide table into Postgres through the attached database and through COPY
+// Run with: GRIDWALK_BENCH_PG=postgres://... cargo bench --bench transfer_engine [-- <rows> <columns>]
+use duckdb::Connection;
+use duckdb_postgis::duckdb_load::{launch_process_file_with_options, LoadOptions, TransferEngine};
+use std::error::Error;
+use std::time::Instant;
+
+const DEFAULT_ROWS: usize = 100_000;
+const DEFAULT_COLUMNS: usize = 80;
+
+const ENGINES: [TransferEngine; 2] = [TransferEngine::Attached, TransferEngine::Copy];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let Ok(postgres_connection) = std::env::var("GRIDWALK_BENCH_PG") else {
+        println!("Set GRIDWALK_BENCH_PG to the Postgres database to load into");
+        return Ok(());
+    };
+    let mut args = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<usize>().ok());
+    let rows = args.next().unwrap_or(DEFAULT_ROWS);
+    let columns = args.next().unwrap_or(DEFAULT_COLUMNS);
+
+    // Alternating text, integer and double columns written to a Parquet file the loads read
+    let path = std::env::temp_dir().join("gridwalk_transfer_bench.parquet");
+    let expressions = (0..columns)
+        .map(|i| match i % 3 {
+            0 => format!("'value ' || (range * {}) AS text_{}", i + 1, i),
+            1 => format!("range * {} AS integer_{}", i + 1, i),
+            _ => format!("random() * {} AS double_{}", i + 1, i),
+        })
+        .collect::<Vec<_>>();
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch(&format!(
+        "COPY (SELECT {} FROM range({})) TO '{}' (FORMAT parquet);",
+        expressions.join(", "),
+        rows,
+        path.display()
+    ))?;
+
+    println!("{} rows of {} columns", rows, columns);
+    for engine in ENGINES {
+        let options = LoadOptions {
+            postgres_connection: postgres_connection.clone(),
+            transfer_engine: engine,
+            transfer_verification: None,
+            create_spatial_index: false,
+            drift_thresholds: None,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        launch_process_file_with_options(
+            &path.display().to_string(),
+            "gridwalk_transfer_bench",
+            &options,
+        )?;
+        let seconds = started.elapsed().as_secs_f64();
+        println!(
+            "{:?}: {:.2}s, {:.0} rows/s",
+            engine,
+            seconds,
+            rows as f64 / seconds
+        );
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
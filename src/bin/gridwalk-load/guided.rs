@@ -0,0 +1,226 @@
+// Guided load - asks for each choice in turn, showing what was detected in the file, for users who would
+// rather not assemble a long list of ingest flags
+use clap::ArgMatches;
+use duckdb_postgis::duckdb_load::{
+    inspect_file, launch_process_files, list_layers, list_sheets, ColumnMapping, ExcelSheet,
+    FileInfo, LoadMode, LoadOptions, PointColumns,
+};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+pub fn run(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_path = matches.get_one::<String>("file").expect("required");
+    let mut options = LoadOptions::default();
+    options.postgres_connection = matches.get_one::<String>("pg").expect("required").clone();
+    let mut input = io::stdin().lock();
+
+    // The schema depends on the layer or sheet, so files with several are inspected again once one is picked
+    let mut info = inspect_file(file_path, &options)?;
+    match info.file_type.as_str() {
+        "Geopackage" => {
+            let layers = list_layers(file_path)?;
+            if layers.len() > 1 {
+                let names = layers
+                    .iter()
+                    .map(|layer| format!("{} ({} features)", layer.name, layer.feature_count))
+                    .collect::<Vec<_>>();
+                let choice = choose(&mut input, "Layer to load", &names)?;
+                options.layer = Some(layers[choice].name.clone());
+                info = inspect_file(file_path, &options)?;
+            }
+        }
+        "Excel" => {
+            let sheets = list_sheets(file_path)?;
+            if sheets.len() > 1 {
+                let choice = choose(&mut input, "Sheet to load", &sheets)?;
+                options.excel.sheet = Some(ExcelSheet::Index(choice));
+                info = inspect_file(file_path, &options)?;
+            }
+        }
+        _ => {}
+    }
+    print_info(&info);
+
+    if info.geometry_columns.is_empty() {
+        choose_point_columns(&mut input, &info, &mut options)?;
+    }
+    options.column_mapping = choose_column_mapping(&mut input, &info)?;
+
+    let default_table = Path::new(file_path)
+        .file_stem()
+        .map(|stem| {
+            stem.to_string_lossy()
+                .to_lowercase()
+                .replace([' ', '-', '.'], "_")
+        })
+        .unwrap_or_default();
+    let table_name = prompt(&mut input, "Table name", Some(&default_table))?;
+    if !info.geometry_columns.is_empty() || options.point_columns.is_some() {
+        options.target_crs = prompt(&mut input, "Target EPSG code", Some(&options.target_crs))?;
+    }
+    let modes = [
+        "Replace the table".to_string(),
+        "Append to the table".to_string(),
+    ];
+    options.load_mode = match choose(&mut input, "Load mode", &modes)? {
+        0 => LoadMode::Replace,
+        _ => LoadMode::Append,
+    };
+    options.postgres_connection = prompt(
+        &mut input,
+        "PostGIS connection",
+        Some(&options.postgres_connection),
+    )?;
+
+    println!();
+    println!(
+        "Ready to load {} into {} ({:?}, EPSG:{})",
+        file_path, table_name, options.load_mode, options.target_crs
+    );
+    if !confirm(&mut input, "Run the load?")? {
+        println!("Load cancelled");
+        return Ok(());
+    }
+    super::cancel_on_interrupt(&options.cancellation);
+    let result = launch_process_files(&[file_path], &table_name, &options)?;
+    super::print_load_result(&result);
+    Ok(())
+}
+
+fn print_info(info: &FileInfo) {
+    println!();
+    println!("File type: {}", info.file_type);
+    println!("Rows: {}", info.row_count);
+    if let Some(crs) = &info.crs {
+        println!("CRS: EPSG:{}", crs);
+    }
+    if !info.geometry_types.is_empty() {
+        println!("Geometry types: {}", info.geometry_types.join(", "));
+    }
+    println!("Columns:");
+    for (name, data_type) in &info.columns {
+        println!("  {} {}", name, data_type);
+    }
+    println!();
+}
+
+fn choose_point_columns(
+    input: &mut impl BufRead,
+    info: &FileInfo,
+    options: &mut LoadOptions,
+) -> Result<(), io::Error> {
+    println!("The file has no geometry column");
+    if !confirm(input, "Build points from coordinate columns?")? {
+        return Ok(());
+    }
+    let x_column = prompt_column(input, info, "Longitude or easting column")?;
+    let y_column = prompt_column(input, info, "Latitude or northing column")?;
+    let srid = prompt(input, "EPSG code of the coordinates", Some("4326"))?;
+    options.point_columns = Some(PointColumns::new(&x_column, &y_column, &srid));
+    Ok(())
+}
+
+// Leaving every column as it is keeps the default mapping
+fn choose_column_mapping(
+    input: &mut impl BufRead,
+    info: &FileInfo,
+) -> Result<ColumnMapping, io::Error> {
+    let mut mapping = ColumnMapping::default();
+    if !confirm(input, "Rename or drop columns?")? {
+        return Ok(mapping);
+    }
+    println!(
+        "For each column, press Enter to keep it, type a new name to rename it or - to drop it"
+    );
+
+    let mut selected = Vec::new();
+    for (name, _) in &info.columns {
+        if info.geometry_columns.contains(name) {
+            selected.push(name.clone());
+            continue;
+        }
+        match prompt(input, name, Some(name))?.as_str() {
+            "-" => {}
+            new_name => {
+                if new_name != name {
+                    mapping.rename.push((name.clone(), new_name.to_string()));
+                }
+                selected.push(name.clone());
+            }
+        }
+    }
+    if selected.len() < info.columns.len() {
+        mapping.select = Some(selected);
+    }
+    Ok(mapping)
+}
+
+fn prompt_column(
+    input: &mut impl BufRead,
+    info: &FileInfo,
+    question: &str,
+) -> Result<String, io::Error> {
+    loop {
+        let column = prompt(input, question, None)?;
+        if info.columns.iter().any(|(name, _)| *name == column) {
+            return Ok(column);
+        }
+        println!("No column named '{}'", column);
+    }
+}
+
+// Reads a line, falling back to the default when it is empty
+fn prompt(
+    input: &mut impl BufRead,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String, io::Error> {
+    loop {
+        match default {
+            Some(default) => print!("{} [{}]: ", question, default),
+            None => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::Error::other("Input ended before the load was set up"));
+        }
+        match (line.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+// Numbered choice, starting at 1 on screen - the first option is the default
+fn choose(
+    input: &mut impl BufRead,
+    question: &str,
+    options: &[String],
+) -> Result<usize, io::Error> {
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    loop {
+        let answer = prompt(input, question, Some("1"))?;
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=options.len()).contains(&choice) => return Ok(choice - 1),
+            _ => println!("Enter a number from 1 to {}", options.len()),
+        }
+    }
+}
+
+fn confirm(input: &mut impl BufRead, question: &str) -> Result<bool, io::Error> {
+    loop {
+        match prompt(input, &format!("{} (y/n)", question), Some("n"))?
+            .to_lowercase()
+            .as_str()
+        {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Answer y or n"),
+        }
+    }
+}
@@ -0,0 +1,1075 @@
+// Command line interface to the loader
+// e.g. gridwalk-load ingest boundaries.gpkg --table boundaries --pg postgres://... --srid 27700 --mode append
+mod guided;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use duckdb_postgis::duckdb_load::{
+    export_table, inspect_file, launch_process_files, list_layers, list_loads, load_manifest,
+    round_trip, search_catalog, validate, BatchOptions, CancellationToken, CatalogQuery, ClipArea,
+    CompanionTables, DataDictionaryOptions, DictionaryFormat, DuckDbSink, ExcelSheet, ExportFormat,
+    ExportSink, Extent, GeometrySimplification, IncompatibleChangePolicy, LoadMode, LoadOptions,
+    LoadResult, NameNormalization, ParallelTransfer, ParquetSink, PrimaryKey, ProfileOptions,
+    RoundTripOptions, SchemaEvolution, Sink, SpatialFilter, TransferEngine,
+};
+use std::io;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+fn cli() -> Command {
+    let pg = Arg::new("pg")
+        .long("pg")
+        .env("DATABASE_URL")
+        .required(true)
+        .help("libpq connection string or postgres:// URI of the PostGIS database");
+    // DuckDB resource limits, shared by the commands that load files
+    let limits = [
+        Arg::new("memory-limit")
+            .long("memory-limit")
+            .help("Memory DuckDB may use before spilling to disk, e.g. 4GB"),
+        Arg::new("threads")
+            .long("threads")
+            .value_parser(clap::value_parser!(usize))
+            .help("Threads DuckDB may use - defaults to one per core"),
+        Arg::new("temp-directory").long("temp-directory").help(
+            "Directory DuckDB spills to - defaults to gridwalk_spill in the system temp directory",
+        ),
+    ];
+    let tag = Arg::new("tag").long("tag").action(ArgAction::Append);
+    let label = Arg::new("label")
+        .long("label")
+        .action(ArgAction::Append)
+        .value_parser(parse_label);
+    let layer = Arg::new("layer")
+        .long("layer")
+        .help("Layer to read from a multi-layer source such as a GeoPackage");
+
+    Command::new("gridwalk-load")
+        .about("Transform geospatial files with DuckDB and load them into PostGIS")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("ingest")
+                .about("Load one or more files into a PostGIS table - several files are merged")
+                .arg(
+                    Arg::new("files")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Files to load"),
+                )
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .required(true)
+                        .help("PostGIS table to load into"),
+                )
+                .arg(pg.clone())
+                .arg(
+                    Arg::new("srid")
+                        .long("srid")
+                        .default_value("4326")
+                        .help("EPSG code geometries are transformed to"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_parser(["replace", "append"])
+                        .default_value("replace")
+                        .help("Replace the table or append to it"),
+                )
+                .arg(
+                    Arg::new("copy")
+                        .long("copy")
+                        .action(ArgAction::SetTrue)
+                        .help("Copy the rows into Postgres with COPY FROM STDIN, which is faster for wide tables"),
+                )
+                .arg(
+                    Arg::new("partitions")
+                        .long("partitions")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Transfer the table to Postgres in this many partitions, several at once"),
+                )
+                .arg(
+                    Arg::new("connections")
+                        .long("connections")
+                        .value_parser(clap::value_parser!(usize))
+                        .requires("partitions")
+                        .help("Partitions transferred at once, each over its own connection - defaults to 4"),
+                )
+                .arg(
+                    Arg::new("partition-column")
+                        .long("partition-column")
+                        .requires("partitions")
+                        .help("Column whose values decide the partition of each row - defaults to ranges of rows"),
+                )
+                .arg(
+                    Arg::new("skip-unchanged")
+                        .long("skip-unchanged")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip the load when the file and options match the table's last load"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .requires("skip-unchanged")
+                        .help("Load even when --skip-unchanged finds nothing has changed"),
+                )
+                .arg(
+                    Arg::new("evolve-schema")
+                        .long("evolve-schema")
+                        .action(ArgAction::SetTrue)
+                        .help("When appending, add new columns and widen column types to fit the source"),
+                )
+                .arg(
+                    Arg::new("skip-incompatible")
+                        .long("skip-incompatible")
+                        .action(ArgAction::SetTrue)
+                        .requires("evolve-schema")
+                        .help("Leave out columns whose type can't be appended instead of failing"),
+                )
+                .args(limits.clone())
+                .arg(
+                    Arg::new("deduplicate")
+                        .long("deduplicate")
+                        .action(ArgAction::SetTrue)
+                        .help("Drop rows that are exact duplicates of an earlier row"),
+                )
+                .arg(
+                    Arg::new("serial-key")
+                        .long("serial-key")
+                        .conflicts_with("hash-key")
+                        .help("Add a BIGSERIAL primary key column of this name"),
+                )
+                .arg(
+                    Arg::new("hash-key")
+                        .long("hash-key")
+                        .help("Add a primary key column of this name holding an md5 hash of --key-columns"),
+                )
+                .arg(
+                    Arg::new("key-columns")
+                        .long("key-columns")
+                        .requires("hash-key")
+                        .help("Comma separated columns hashed by --hash-key - defaults to every column"),
+                )
+                .arg(
+                    Arg::new("normalize-names")
+                        .long("normalize-names")
+                        .action(ArgAction::SetTrue)
+                        .help("Rewrite the table and column names as unaccented snake_case of at most 63 bytes"),
+                )
+                .arg(
+                    Arg::new("catalog")
+                        .long("catalog")
+                        .action(ArgAction::SetTrue)
+                        .help("Record the load in the gridwalk_catalog table - implied by --tag and --label"),
+                )
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .action(ArgAction::SetTrue)
+                        .help("Record the load, whether or not it succeeds, in the gridwalk_loads table"),
+                )
+                .arg(tag.clone().help("Tag stored with the load in the catalog - repeat for several"))
+                .arg(
+                    label
+                        .clone()
+                        .help("key=value label stored with the load in the catalog - repeat for several"),
+                )
+                .arg(
+                    Arg::new("replica")
+                        .long("replica")
+                        .action(ArgAction::Append)
+                        .help("Another PostGIS database to apply the same load to - repeat for several"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .help("Postgres schema to load into, created if missing - defaults to the search path"),
+                )
+                .arg(layer.clone())
+                .arg(
+                    Arg::new("promote-to-multi")
+                        .long("promote-to-multi")
+                        .action(ArgAction::SetTrue)
+                        .help("Promote single geometries to their multi type"),
+                )
+                .arg(
+                    Arg::new("simplify")
+                        .long("simplify")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Simplify geometries with this tolerance, in the units of the target CRS"),
+                )
+                .arg(
+                    Arg::new("coordinate-precision")
+                        .long("coordinate-precision")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Round coordinates to this many decimal places"),
+                )
+                .arg(
+                    Arg::new("centroid-table")
+                        .long("centroid-table")
+                        .action(ArgAction::SetTrue)
+                        .help("Also create a {table}_centroid table of geometry centroids"),
+                )
+                .arg(
+                    Arg::new("display-tolerance")
+                        .long("display-tolerance")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Also create a {table}_display table simplified at this tolerance, in the units of the target CRS"),
+                )
+                .arg(
+                    Arg::new("no-spatial-index")
+                        .long("no-spatial-index")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip creating GIST indexes on geometry columns"),
+                )
+                .arg(
+                    Arg::new("layer-extents")
+                        .long("layer-extents")
+                        .action(ArgAction::SetTrue)
+                        .help("Record the extent and feature count in the layer_extents table"),
+                )
+                .arg(
+                    Arg::new("layer-styles")
+                        .long("layer-styles")
+                        .action(ArgAction::SetTrue)
+                        .help("Store QML/SLD styles found with the data in the layer_styles table"),
+                )
+                .arg(
+                    Arg::new("source-srid").long("source-srid").help(
+                        "EPSG code of files that do not record their CRS, e.g. CSV coordinates",
+                    ),
+                )
+                .arg(
+                    Arg::new("detect-points")
+                        .long("detect-points")
+                        .action(ArgAction::SetTrue)
+                        .help(
+                            "Look for coordinate columns such as lon/lat in data without geometry",
+                        ),
+                )
+                .arg(
+                    Arg::new("confirm-points")
+                        .long("confirm-points")
+                        .action(ArgAction::SetTrue)
+                        .requires("detect-points")
+                        .help("Build point geometries from the detected coordinate columns"),
+                )
+                .arg(
+                    Arg::new("sheet")
+                        .long("sheet")
+                        .help("Excel sheet to read, by name or by position from 0"),
+                )
+                .arg(
+                    Arg::new("skip-rows")
+                        .long("skip-rows")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Excel rows to skip above the header"),
+                )
+                .arg(
+                    Arg::new("range")
+                        .long("range")
+                        .help("Excel cell range to read, e.g. B3:F200"),
+                )
+                .arg(Arg::new("transform").long("transform").help(
+                    "SELECT over a table named data to run before loading, e.g. to filter or derive columns",
+                ))
+                .arg(Arg::new("clip").long("clip").help(
+                    "Only load features intersecting this area - min_x,min_y,max_x,max_y or a WKT polygon",
+                ))
+                .arg(
+                    Arg::new("clip-srid")
+                        .long("clip-srid")
+                        .default_value("4326")
+                        .help("EPSG code of the --clip area"),
+                )
+                .arg(
+                    Arg::new("null-string")
+                        .long("null-string")
+                        .action(ArgAction::Append)
+                        .help("CSV value to read as NULL, may be repeated"),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .help("CSV character encoding, e.g. latin-1"),
+                )
+                .arg(
+                    Arg::new("hive-partitioning")
+                        .long("hive-partitioning")
+                        .action(ArgAction::SetTrue)
+                        .help("Read key=value directories of Parquet paths as columns"),
+                )
+                .arg(
+                    Arg::new("open-option")
+                        .long("open-option")
+                        .action(ArgAction::Append)
+                        .help("GDAL open option for spatial files, e.g. LIST_ALL_TABLES=YES"),
+                )
+                .arg(Arg::new("dictionary").long("dictionary").help(
+                    "Write a data dictionary to this path - CSV for a .csv path, JSON otherwise",
+                )),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Load every file listed in a JSON, YAML or CSV manifest into its own table")
+                .arg(Arg::new("manifest").required(true).help(
+                    "Manifest of entries with file, table and optional schema, srid, source_srid, mode and layer",
+                ))
+                .arg(pg.clone())
+                .args(limits)
+                .arg(
+                    Arg::new("continue-on-error")
+                        .long("continue-on-error")
+                        .action(ArgAction::SetTrue)
+                        .help("Load the remaining entries after one fails"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Find loaded tables in the catalog by tag, label, extent and load date")
+                .arg(pg.clone())
+                .arg(tag.help("Tag the table must have - repeat to require several"))
+                .arg(label.help("key=value label the table must have - repeat to require several"))
+                .arg(
+                    Arg::new("bbox")
+                        .long("bbox")
+                        .help("min_x,min_y,max_x,max_y in EPSG:4326 the table's extent must intersect"),
+                )
+                .arg(
+                    Arg::new("after")
+                        .long("after")
+                        .help("Only tables loaded at or after this date or timestamp"),
+                )
+                .arg(
+                    Arg::new("before")
+                        .long("before")
+                        .help("Only tables loaded before this date or timestamp"),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List past loads of a table recorded with ingest --history, newest first")
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .required(true)
+                        .help("Table the loads wrote to"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .help("Schema of the table - defaults to the current schema"),
+                )
+                .arg(pg.clone()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write a PostGIS table to a file")
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .required(true)
+                        .help("PostGIS table to export"),
+                )
+                .arg(Arg::new("schema").long("schema").help("Postgres schema of the table"))
+                .arg(pg.clone())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["parquet", "geojson", "geojsonseq", "csv", "duckdb"])
+                        .required(true)
+                        .help("Output format - duckdb creates the table in a DuckDB database file"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .required(true)
+                        .help("Path of the file to write"),
+                ),
+        )
+        .subcommand(
+            Command::new("round-trip")
+                .about("Load a file, export it back out and report any differences from the source")
+                .arg(Arg::new("file").required(true).help("File to check"))
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .required(true)
+                        .help("PostGIS table to load into - it is replaced"),
+                )
+                .arg(pg.clone())
+                .arg(
+                    Arg::new("srid")
+                        .long("srid")
+                        .default_value("4326")
+                        .help("EPSG code geometries are transformed to"),
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .long("tolerance")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.000000001")
+                        .help("Grid size geometries are snapped to and allowed numeric difference"),
+                )
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .help("Column identifying rows - rows are paired by order otherwise"),
+                )
+                .arg(layer.clone()),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Show the schema, CRS and geometry types of a file without loading it")
+                .arg(Arg::new("file").required(true).help("File to inspect"))
+                .arg(layer.clone())
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .action(ArgAction::SetTrue)
+                        .help("Report null counts, distinct counts, ranges and frequent values per column"),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check what loading a file would do and what would stop it, without writing anything")
+                .arg(Arg::new("file").required(true).help("File to check"))
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .required(true)
+                        .help("PostGIS table the file would be loaded into"),
+                )
+                .arg(pg.clone())
+                .arg(
+                    Arg::new("srid")
+                        .long("srid")
+                        .default_value("4326")
+                        .help("EPSG code geometries would be transformed to"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_parser(["replace", "append"])
+                        .default_value("replace")
+                        .help("Whether the table would be replaced or appended to"),
+                )
+                .arg(Arg::new("schema").long("schema").help("Postgres schema of the table"))
+                .arg(
+                    Arg::new("normalize-names")
+                        .long("normalize-names")
+                        .action(ArgAction::SetTrue)
+                        .help("Check the names as --normalize-names would rewrite them"),
+                )
+                .arg(layer),
+        )
+        .subcommand(
+            Command::new("guided")
+                .about("Walk through the detected schema, layer and column choices, then load the file")
+                .arg(Arg::new("file").required(true).help("File to load"))
+                .arg(pg),
+        )
+        .subcommand(
+            Command::new("list-layers")
+                .about("List the layers of a GeoPackage, GPX trace, OSM extract or other multi-layer file")
+                .arg(Arg::new("file").required(true).help("File to list")),
+        )
+}
+
+fn ingest(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_paths: Vec<&str> = matches
+        .get_many::<String>("files")
+        .unwrap_or_default()
+        .map(String::as_str)
+        .collect();
+    let table_name = matches.get_one::<String>("table").expect("required");
+
+    let mut options = LoadOptions::default();
+    options.postgres_connection = matches.get_one::<String>("pg").expect("required").clone();
+    options.target_crs = matches
+        .get_one::<String>("srid")
+        .expect("defaulted")
+        .clone();
+    options.load_mode = match matches.get_one::<String>("mode").map(String::as_str) {
+        Some("append") => LoadMode::Append,
+        _ => LoadMode::Replace,
+    };
+    if matches.get_flag("copy") {
+        options.transfer_engine = TransferEngine::Copy;
+    }
+    if let Some(partitions) = matches.get_one::<usize>("partitions") {
+        let mut parallel = ParallelTransfer {
+            partitions: *partitions,
+            partition_column: matches.get_one::<String>("partition-column").cloned(),
+            ..Default::default()
+        };
+        if let Some(connections) = matches.get_one::<usize>("connections") {
+            parallel.max_connections = *connections;
+        }
+        options.parallel_transfer = Some(parallel);
+    }
+    options.skip_unchanged = matches.get_flag("skip-unchanged");
+    options.force = matches.get_flag("force");
+    if matches.get_flag("evolve-schema") {
+        options.schema_evolution = Some(SchemaEvolution {
+            on_incompatible: if matches.get_flag("skip-incompatible") {
+                IncompatibleChangePolicy::SkipColumn
+            } else {
+                IncompatibleChangePolicy::Fail
+            },
+            ..Default::default()
+        });
+    }
+    set_limits(matches, &mut options);
+    options.tags = matches
+        .get_many::<String>("tag")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    options.labels = matches
+        .get_many::<(String, String)>("label")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    options.deduplicate = matches.get_flag("deduplicate");
+    if let Some(column) = matches.get_one::<String>("serial-key") {
+        options.primary_key = Some(PrimaryKey::Serial(column.clone()));
+    }
+    if let Some(column) = matches.get_one::<String>("hash-key") {
+        options.primary_key = Some(PrimaryKey::Hash {
+            column: column.clone(),
+            columns: matches
+                .get_one::<String>("key-columns")
+                .map(|columns| columns.split(',').map(|c| c.trim().to_string()).collect())
+                .unwrap_or_default(),
+        });
+    }
+    if matches.get_flag("normalize-names") {
+        options.name_normalization = Some(NameNormalization::default());
+    }
+    options.write_load_history = matches.get_flag("history");
+    options.write_catalog =
+        matches.get_flag("catalog") || !options.tags.is_empty() || !options.labels.is_empty();
+    options.replica_connections = matches
+        .get_many::<String>("replica")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    options.schema = matches.get_one::<String>("schema").cloned();
+    options.layer = matches.get_one::<String>("layer").cloned();
+    options.promote_to_multi = matches.get_flag("promote-to-multi");
+    let tolerance = matches.get_one::<f64>("simplify").copied();
+    let decimal_places = matches.get_one::<u32>("coordinate-precision").copied();
+    if tolerance.is_some() || decimal_places.is_some() {
+        options.simplification = Some(GeometrySimplification {
+            tolerance,
+            decimal_places,
+        });
+    }
+    options.companion_tables = CompanionTables {
+        centroid: matches.get_flag("centroid-table"),
+        display_tolerance: matches.get_one::<f64>("display-tolerance").copied(),
+    };
+    options.create_spatial_index = !matches.get_flag("no-spatial-index");
+    options.write_layer_extents = matches.get_flag("layer-extents");
+    options.write_layer_styles = matches.get_flag("layer-styles");
+    options.source_crs = matches.get_one::<String>("source-srid").cloned();
+    options.detect_point_columns = matches.get_flag("detect-points");
+    options.confirm_point_columns = matches.get_flag("confirm-points");
+    options.excel.sheet = matches
+        .get_one::<String>("sheet")
+        .map(|sheet| match sheet.parse() {
+            Ok(index) => ExcelSheet::Index(index),
+            Err(_) => ExcelSheet::Name(sheet.clone()),
+        });
+    if let Some(skip_rows) = matches.get_one::<usize>("skip-rows") {
+        options.excel.skip_rows = *skip_rows;
+    }
+    options.excel.range = matches.get_one::<String>("range").cloned();
+    options.sql_transform = matches.get_one::<String>("transform").cloned();
+    if let Some(clip) = matches.get_one::<String>("clip") {
+        let bounds = clip
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>();
+        let area = match bounds.as_deref() {
+            Ok([min_x, min_y, max_x, max_y]) => ClipArea::BoundingBox(Extent {
+                min_x: *min_x,
+                min_y: *min_y,
+                max_x: *max_x,
+                max_y: *max_y,
+            }),
+            _ => ClipArea::Wkt(clip.clone()),
+        };
+        let srid = matches.get_one::<String>("clip-srid").expect("has default");
+        options.spatial_filter = Some(SpatialFilter::new(area, srid));
+    }
+    let readers = &mut options.reader_options;
+    readers.csv.null_strings = matches
+        .get_many::<String>("null-string")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    readers.csv.encoding = matches.get_one::<String>("encoding").cloned();
+    readers.parquet.hive_partitioning = matches.get_flag("hive-partitioning");
+    readers.spatial.open_options = matches
+        .get_many::<String>("open-option")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    options.data_dictionary = matches.get_one::<String>("dictionary").map(|path| {
+        let format = if path.ends_with(".csv") {
+            DictionaryFormat::Csv
+        } else {
+            DictionaryFormat::Json
+        };
+        DataDictionaryOptions::new(path, format)
+    });
+
+    cancel_on_interrupt(&options.cancellation);
+    let result = launch_process_files(&file_paths, table_name, &options)?;
+    print_load_result(&result);
+    Ok(())
+}
+
+// Set by the SIGINT handler, which can't cancel the token itself as cancelling takes a lock
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// The first Ctrl-C cancels the load, interrupting its running query and dropping anything staged in Postgres
+// A second one exits straight away
+pub(crate) fn cancel_on_interrupt(cancellation: &CancellationToken) {
+    // Only async-signal-safe calls are made in the handler - an atomic swap and _exit
+    let registered = unsafe {
+        signal_hook_registry::register(libc::SIGINT, || {
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                libc::_exit(130);
+            }
+        })
+    };
+    if let Err(e) = registered {
+        eprintln!("Ctrl-C will stop the load without cleaning up: {}", e);
+        return;
+    }
+    let cancellation = cancellation.clone();
+    thread::spawn(move || {
+        while !INTERRUPTED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        cancellation.cancel();
+    });
+}
+
+fn set_limits(matches: &ArgMatches, options: &mut LoadOptions) {
+    let limits = &mut options.duckdb_limits;
+    limits.memory_limit = matches.get_one::<String>("memory-limit").cloned();
+    limits.threads = matches.get_one::<usize>("threads").copied();
+    if let Some(temp_directory) = matches.get_one::<String>("temp-directory") {
+        limits.temp_directory = Some(temp_directory.clone());
+    }
+}
+
+fn print_load_result(result: &LoadResult) {
+    if result.skipped_unchanged {
+        println!(
+            "Skipped table {}, unchanged since its last load",
+            result.table_name
+        );
+        return;
+    }
+    println!("Loaded table {}", result.table_name);
+    for mapping in &result.normalized_names {
+        println!("  Renamed {} to {}", mapping.original, mapping.normalized);
+    }
+    for column_type in &result.geometry_column_types {
+        println!("  {} {}", column_type.column, column_type.postgis_type());
+    }
+    for statistics in &result.geometry_statistics {
+        if let Some(extent) = &statistics.extent {
+            println!(
+                "  {} extent: {}, {}, {}, {}",
+                statistics.column, extent.min_x, extent.min_y, extent.max_x, extent.max_y
+            );
+        }
+        let type_counts = statistics
+            .type_counts
+            .iter()
+            .map(|(geometry_type, count)| format!("{} {}", count, geometry_type))
+            .collect::<Vec<_>>();
+        println!(
+            "  {} geometries: {} ({} empty), {} vertices",
+            statistics.column,
+            type_counts.join(", "),
+            statistics.empty_count,
+            statistics.total_vertices
+        );
+    }
+    for change in result
+        .schema_evolution
+        .iter()
+        .flat_map(|report| &report.changes)
+    {
+        println!(
+            "  {:?} column {} ({} -> {})",
+            change.kind,
+            change.column,
+            change.source_type.as_deref().unwrap_or("-"),
+            change.target_type.as_deref().unwrap_or("-")
+        );
+    }
+    if let Some(points) = &result.detected_point_columns {
+        println!(
+            "  Coordinate columns: {}, {} (EPSG:{})",
+            points.x_column, points.y_column, points.srid
+        );
+    }
+    if let Some(drift) = result.drift.as_ref().filter(|drift| drift.suspicious) {
+        println!("  Drift warnings: {}", drift.reasons.join("; "));
+    }
+    for replica in &result.replicas {
+        match &replica.error {
+            Some(e) => println!("  Replica {} failed: {}", replica.connection, e),
+            None => println!("  Replica {} loaded", replica.connection),
+        }
+    }
+}
+
+fn batch(matches: &ArgMatches) -> Result<(), io::Error> {
+    let manifest = matches.get_one::<String>("manifest").expect("required");
+    let mut options = BatchOptions {
+        continue_on_error: matches.get_flag("continue-on-error"),
+        ..Default::default()
+    };
+    options.load_options.postgres_connection =
+        matches.get_one::<String>("pg").expect("required").clone();
+    set_limits(matches, &mut options.load_options);
+
+    cancel_on_interrupt(&options.load_options.cancellation);
+    let report = load_manifest(manifest, &options)?;
+    println!();
+    for outcome in &report.outcomes {
+        match &outcome.result {
+            Ok(_) => println!(
+                "Loaded {} into {}",
+                outcome.file_path.display(),
+                outcome.table_name
+            ),
+            Err(e) => println!(
+                "Failed {} into {}: {}",
+                outcome.file_path.display(),
+                outcome.table_name,
+                e
+            ),
+        }
+    }
+    for entry in &report.skipped {
+        println!("Skipped {} into {}", entry.file, entry.table);
+    }
+    println!(
+        "{} loaded, {} failed, {} skipped",
+        report.succeeded(),
+        report.failed(),
+        report.skipped.len()
+    );
+    if report.failed() > 0 {
+        Err(io::Error::other(format!(
+            "{} of {} manifest entries failed",
+            report.failed(),
+            report.outcomes.len() + report.skipped.len()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_label(label: &str) -> Result<(String, String), String> {
+    match label.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected key=value, found '{}'", label)),
+    }
+}
+
+fn search(matches: &ArgMatches) -> Result<(), io::Error> {
+    let postgres_connection = matches.get_one::<String>("pg").expect("required");
+    let mut query = CatalogQuery {
+        tags: matches
+            .get_many::<String>("tag")
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default(),
+        labels: matches
+            .get_many::<(String, String)>("label")
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default(),
+        loaded_after: matches.get_one::<String>("after").cloned(),
+        loaded_before: matches.get_one::<String>("before").cloned(),
+        ..Default::default()
+    };
+    if let Some(bbox) = matches.get_one::<String>("bbox") {
+        let bounds = bbox
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>();
+        let Ok([min_x, min_y, max_x, max_y]) = bounds.as_deref() else {
+            return Err(io::Error::other(format!(
+                "Expected --bbox min_x,min_y,max_x,max_y, found '{}'",
+                bbox
+            )));
+        };
+        query.intersects = Some(Extent {
+            min_x: *min_x,
+            min_y: *min_y,
+            max_x: *max_x,
+            max_y: *max_y,
+        });
+    }
+
+    let entries = search_catalog(postgres_connection, &query)?;
+    for entry in &entries {
+        let labels = entry
+            .labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        println!(
+            "{}.{}\t{} rows\t{}\t{}\t{}\t{}",
+            entry.table_schema,
+            entry.table_name,
+            entry.row_count,
+            entry.loaded_at,
+            entry.tags.join(","),
+            labels.join(","),
+            entry.source_files.join(", ")
+        );
+    }
+    println!("{} tables found", entries.len());
+    Ok(())
+}
+
+fn history(matches: &ArgMatches) -> Result<(), io::Error> {
+    let table_name = matches.get_one::<String>("table").expect("required");
+    let postgres_connection = matches.get_one::<String>("pg").expect("required");
+    let schema = matches.get_one::<String>("schema").map(String::as_str);
+
+    let entries = list_loads(postgres_connection, schema, table_name)?;
+    for entry in &entries {
+        let rows = entry
+            .row_count
+            .map_or("-".to_string(), |count| format!("{} rows", count));
+        println!(
+            "{}\t{:?}\t{}\t{:.1}s\t{}\t{}{}",
+            entry.loaded_at,
+            entry.status,
+            rows,
+            entry.duration.as_secs_f64(),
+            entry.file_type,
+            entry.source_files.join(", "),
+            entry
+                .error
+                .as_ref()
+                .map(|e| format!("\t{}", e))
+                .unwrap_or_default()
+        );
+    }
+    println!("{} loads found", entries.len());
+    Ok(())
+}
+
+fn export(matches: &ArgMatches) -> Result<(), io::Error> {
+    let table_name = matches.get_one::<String>("table").expect("required");
+    let output = matches.get_one::<String>("output").expect("required");
+    let postgres_connection = matches.get_one::<String>("pg").expect("required");
+    let sink = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("parquet") => Sink::Parquet(ParquetSink::new(output)),
+        Some("geojson") => Sink::Export(ExportSink::new(output, ExportFormat::GeoJson)),
+        Some("geojsonseq") => Sink::Export(ExportSink::new(output, ExportFormat::GeoJsonSeq)),
+        Some("csv") => Sink::Export(ExportSink::new(output, ExportFormat::Csv)),
+        _ => Sink::DuckDb(DuckDbSink::new(output)),
+    };
+    let schema = matches.get_one::<String>("schema").map(String::as_str);
+
+    let result = export_table(table_name, postgres_connection, schema, &sink)?;
+    println!("Exported table {} to {}", result.table_name, output);
+    Ok(())
+}
+
+fn check_round_trip(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_path = matches.get_one::<String>("file").expect("required");
+    let table_name = matches.get_one::<String>("table").expect("required");
+
+    let mut options = RoundTripOptions {
+        tolerance: *matches.get_one::<f64>("tolerance").expect("defaulted"),
+        key_column: matches.get_one::<String>("key").cloned(),
+        ..Default::default()
+    };
+    options.load_options.postgres_connection =
+        matches.get_one::<String>("pg").expect("required").clone();
+    options.load_options.target_crs = matches
+        .get_one::<String>("srid")
+        .expect("defaulted")
+        .clone();
+    options.load_options.layer = matches.get_one::<String>("layer").cloned();
+
+    let report = round_trip(file_path, table_name, &options)?;
+    println!(
+        "Rows: {} in source, {} after round trip",
+        report.source_rows, report.round_trip_rows
+    );
+    for column in &report.missing_columns {
+        println!("Missing column: {}", column);
+    }
+    for mismatch in report
+        .geometry_mismatches
+        .iter()
+        .chain(&report.attribute_mismatches)
+    {
+        println!(
+            "Column {}: {} rows differ",
+            mismatch.column, mismatch.mismatched_rows
+        );
+    }
+    if report.is_lossless() {
+        println!(
+            "Round trip is lossless within a tolerance of {}",
+            options.tolerance
+        );
+        Ok(())
+    } else {
+        Err(io::Error::other("Round trip lost data"))
+    }
+}
+
+fn inspect(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_path = matches.get_one::<String>("file").expect("required");
+    let options = LoadOptions {
+        layer: matches.get_one::<String>("layer").cloned(),
+        profile: matches.get_flag("profile").then(ProfileOptions::default),
+        ..Default::default()
+    };
+
+    let info = inspect_file(file_path, &options)?;
+    println!("File type: {}", info.file_type);
+    println!("Rows: {}", info.row_count);
+    if let Some(crs) = &info.crs {
+        println!("CRS: EPSG:{}", crs);
+    }
+    if !info.geometry_types.is_empty() {
+        println!("Geometry types: {}", info.geometry_types.join(", "));
+    }
+    println!("Columns:");
+    for (name, data_type) in &info.columns {
+        println!("  {} {}", name, data_type);
+    }
+    if let Some(profile) = &info.profile {
+        println!("Profile:");
+        for column in &profile.columns {
+            println!(
+                "  {}: {} nulls ({:.1}%), {} distinct",
+                column.name,
+                column.null_count,
+                column.null_fraction * 100.0,
+                column.distinct_count
+            );
+            if let (Some(min), Some(max)) = (&column.min, &column.max) {
+                println!("    range {} to {}", min, max);
+            }
+            if !column.top_values.is_empty() {
+                let top_values = column
+                    .top_values
+                    .iter()
+                    .map(|(value, count)| format!("'{}' ({})", value, count))
+                    .collect::<Vec<_>>();
+                println!("    top values {}", top_values.join(", "));
+            }
+            for warning in &column.warnings {
+                println!("    Warning: {}", warning);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_file(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_path = matches.get_one::<String>("file").expect("required");
+    let table_name = matches.get_one::<String>("table").expect("required");
+    let mut options = LoadOptions {
+        target_crs: matches
+            .get_one::<String>("srid")
+            .expect("defaulted")
+            .clone(),
+        schema: matches.get_one::<String>("schema").cloned(),
+        layer: matches.get_one::<String>("layer").cloned(),
+        name_normalization: matches
+            .get_flag("normalize-names")
+            .then(NameNormalization::default),
+        ..Default::default()
+    };
+    options.postgres_connection = matches.get_one::<String>("pg").expect("required").clone();
+    if matches.get_one::<String>("mode").map(String::as_str) == Some("append") {
+        options.load_mode = LoadMode::Append;
+    }
+
+    let report = validate(file_path, table_name, &options);
+    if let Some(file_type) = &report.file_type {
+        println!("File type: {}", file_type);
+    }
+    if let Some(row_count) = report.row_count {
+        println!("Rows: {}", row_count);
+    }
+    if let Some(crs) = &report.crs {
+        println!("CRS: EPSG:{}", crs);
+    }
+    println!("Table: {}", report.table_name);
+    for issue in &report.issues {
+        println!(
+            "{:?} ({:?}): {}",
+            issue.severity, issue.check, issue.message
+        );
+    }
+    if report.is_valid() {
+        println!("No errors found");
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} errors would stop the load",
+            report.errors().count()
+        )))
+    }
+}
+
+fn print_layers(matches: &ArgMatches) -> Result<(), io::Error> {
+    let file_path = matches.get_one::<String>("file").expect("required");
+    for layer in list_layers(file_path)? {
+        println!(
+            "{}\t{} features\t{}\t{}",
+            layer.name,
+            layer.feature_count,
+            layer.geometry_type.as_deref().unwrap_or("No geometry"),
+            layer
+                .crs
+                .map(|crs| format!("EPSG:{}", crs))
+                .unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let result = match matches.subcommand() {
+        Some(("ingest", matches)) => ingest(matches),
+        Some(("batch", matches)) => batch(matches),
+        Some(("search", matches)) => search(matches),
+        Some(("history", matches)) => history(matches),
+        Some(("export", matches)) => export(matches),
+        Some(("round-trip", matches)) => check_round_trip(matches),
+        Some(("inspect", matches)) => inspect(matches),
+        Some(("validate", matches)) => check_file(matches),
+        Some(("guided", matches)) => guided::run(matches),
+        Some(("list-layers", matches)) => print_layers(matches),
+        _ => unreachable!("a subcommand is required"),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
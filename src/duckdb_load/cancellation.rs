@@ -0,0 +1,94 @@
+use duckdb::{Connection, InterruptHandle};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Stops a load from another thread, e.g. when the web request that started it is aborted
+// Clones share the flag, so keep a clone of LoadOptions::cancellation and cancel it while the load runs
+// Cancelling interrupts the queries the load is running, so it stops without waiting for its next stage
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    // Interrupt handles of the connections the load is running queries on
+    interrupts: Arc<Mutex<Vec<Arc<InterruptHandle>>>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Takes a lock, so is not safe to call from a signal handler - set a flag there and cancel from a thread
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for interrupt in self.interrupts.lock().unwrap().iter() {
+            interrupt.interrupt();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            Err(Box::new(LoadCancelledError))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Interrupts the connection's queries on cancellation until the registration is dropped
+    pub(crate) fn register(&self, conn: &Connection) -> InterruptRegistration {
+        let handle = conn.interrupt_handle();
+        self.interrupts.lock().unwrap().push(handle.clone());
+        InterruptRegistration {
+            interrupts: self.interrupts.clone(),
+            handle,
+        }
+    }
+
+    // A query that failed because cancelling interrupted it as a LoadCancelledError, or else the error back
+    pub(crate) fn interrupted(&self, e: Box<dyn Error>) -> Box<dyn Error> {
+        if self.is_cancelled() {
+            Box::new(LoadCancelledError)
+        } else {
+            e
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+pub(crate) struct InterruptRegistration {
+    interrupts: Arc<Mutex<Vec<Arc<InterruptHandle>>>>,
+    handle: Arc<InterruptHandle>,
+}
+
+impl Drop for InterruptRegistration {
+    fn drop(&mut self) {
+        self.interrupts
+            .lock()
+            .unwrap()
+            .retain(|handle| !Arc::ptr_eq(handle, &self.handle));
+    }
+}
+
+// Returned when a load stops because its CancellationToken was cancelled
+#[derive(Debug)]
+pub struct LoadCancelledError;
+
+impl fmt::Display for LoadCancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Load cancelled")
+    }
+}
+
+impl Error for LoadCancelledError {}
@@ -0,0 +1,236 @@
+use super::query_log::LoggedConnection;
+use super::report::{Extent, GeometryColumnType};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+
+// A loaded table as recorded in the gridwalk_catalog table
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub table_schema: String,
+    pub table_name: String,
+    pub source_files: Vec<String>,
+    pub file_type: String,
+    pub row_count: u64,
+    // SRID of the first geometry column - None for tables without geometry
+    pub srid: Option<String>,
+    // Extent of the first geometry column in EPSG:4326, so tables in different CRSs can be searched together
+    pub extent: Option<Extent>,
+    pub tags: Vec<String>,
+    pub labels: Vec<(String, String)>,
+    // Timestamp of the latest load, as Postgres formats timestamptz
+    pub loaded_at: String,
+}
+
+// Filters for search_catalog - entries must match all of them, and an empty query returns every entry
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogQuery {
+    // Every tag must be present
+    pub tags: Vec<String>,
+    // Every label must be present with the same value
+    pub labels: Vec<(String, String)>,
+    // EPSG:4326 bounds the entry's extent must intersect
+    pub intersects: Option<Extent>,
+    // Dates or timestamps Postgres can cast to timestamptz, e.g. '2024-05-01'
+    pub loaded_after: Option<String>,
+    pub loaded_before: Option<String>,
+}
+
+const CATALOG_TABLE: &str = "CREATE TABLE IF NOT EXISTS gridwalk_catalog (
+    table_schema text NOT NULL,
+    table_name text NOT NULL,
+    source_files text[] NOT NULL,
+    file_type text NOT NULL,
+    row_count bigint NOT NULL,
+    srid integer,
+    min_x double precision,
+    min_y double precision,
+    max_x double precision,
+    max_y double precision,
+    tags text[] NOT NULL DEFAULT '{}',
+    labels jsonb NOT NULL DEFAULT '{}',
+    loaded_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (table_schema, table_name)
+);";
+
+// What a load records about itself - a later load of the same table replaces the entry
+pub(crate) struct CatalogRecord<'a> {
+    pub(crate) schema: Option<&'a str>,
+    pub(crate) table_name: &'a str,
+    pub(crate) qualified_table: &'a str,
+    pub(crate) source_files: &'a [String],
+    pub(crate) file_type: String,
+    pub(crate) column_types: &'a [GeometryColumnType],
+    pub(crate) tags: &'a [String],
+    pub(crate) labels: &'a [(String, String)],
+}
+
+// Postgres statements upserting the catalog entry, with the row count and extent read from the loaded table
+pub(crate) fn catalog_statements(record: &CatalogRecord) -> String {
+    let schema = record
+        .schema
+        .map_or("current_schema()".to_string(), literal);
+    let (srid, extent) = match record.column_types.first() {
+        Some(column_type) => (
+            column_type.srid.clone(),
+            format!(
+                "ST_Extent(ST_Transform({}, 4326))",
+                identifier(&column_type.column)
+            ),
+        ),
+        None => ("NULL".to_string(), "NULL::box3d".to_string()),
+    };
+    format!(
+        "{}
+        INSERT INTO gridwalk_catalog (table_schema, table_name, source_files, file_type, row_count, srid,
+            min_x, min_y, max_x, max_y, tags, labels, loaded_at)
+        SELECT {}, {}, {}, {}, row_count, {},
+            ST_XMin(extent), ST_YMin(extent), ST_XMax(extent), ST_YMax(extent), {}, {}, now()
+        FROM (SELECT count(*) AS row_count, {} AS extent FROM {}) loaded
+        ON CONFLICT (table_schema, table_name) DO UPDATE SET
+            source_files = EXCLUDED.source_files, file_type = EXCLUDED.file_type,
+            row_count = EXCLUDED.row_count, srid = EXCLUDED.srid,
+            min_x = EXCLUDED.min_x, min_y = EXCLUDED.min_y, max_x = EXCLUDED.max_x, max_y = EXCLUDED.max_y,
+            tags = EXCLUDED.tags, labels = EXCLUDED.labels, loaded_at = EXCLUDED.loaded_at;",
+        CATALOG_TABLE,
+        schema,
+        literal(record.table_name),
+        text_array(record.source_files),
+        literal(&record.file_type),
+        srid,
+        text_array(record.tags),
+        labels_json(record.labels),
+        extent,
+        record.qualified_table
+    )
+}
+
+pub(crate) fn search(
+    conn: &LoggedConnection,
+    database: &str,
+    query: &CatalogQuery,
+) -> Result<Vec<CatalogEntry>, Box<dyn Error>> {
+    let exists: bool = conn.query_row(
+        &format!(
+            "SELECT * FROM postgres_query('{}', 'SELECT to_regclass(''gridwalk_catalog'') IS NOT NULL');",
+            database
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let mut conditions = Vec::new();
+    if !query.tags.is_empty() {
+        conditions.push(format!("tags @> {}", text_array(&query.tags)));
+    }
+    if !query.labels.is_empty() {
+        conditions.push(format!("labels @> {}", labels_json(&query.labels)));
+    }
+    if let Some(area) = &query.intersects {
+        conditions.push(format!(
+            "min_x <= {} AND max_x >= {} AND min_y <= {} AND max_y >= {}",
+            area.max_x, area.min_x, area.max_y, area.min_y
+        ));
+    }
+    if let Some(after) = &query.loaded_after {
+        conditions.push(format!("loaded_at >= {}::timestamptz", literal(after)));
+    }
+    if let Some(before) = &query.loaded_before {
+        conditions.push(format!("loaded_at < {}::timestamptz", literal(before)));
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    };
+
+    // Arrays and labels come back as JSON text, which postgres_query passes through unchanged
+    let sql = format!(
+        "SELECT table_schema, table_name, array_to_json(source_files)::text, file_type, row_count,
+            srid::text, min_x, min_y, max_x, max_y, array_to_json(tags)::text, labels::text, loaded_at::text
+        FROM gridwalk_catalog{}
+        ORDER BY loaded_at DESC",
+        filter
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM postgres_query('{}', '{}');",
+        database,
+        sql.replace('\'', "''")
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                CatalogEntry {
+                    table_schema: row.get(0)?,
+                    table_name: row.get(1)?,
+                    file_type: row.get(3)?,
+                    row_count: row.get::<_, i64>(4)? as u64,
+                    srid: row.get(5)?,
+                    loaded_at: row.get(12)?,
+                    ..Default::default()
+                },
+                [row.get(6)?, row.get(7)?, row.get(8)?, row.get(9)?],
+                [
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(10)?,
+                    row.get::<_, String>(11)?,
+                ],
+            ))
+        })?
+        .collect::<Result<Vec<(CatalogEntry, [Option<f64>; 4], [String; 3])>, _>>()?;
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for (mut entry, bounds, [source_files, tags, labels]) in rows {
+        if let [Some(min_x), Some(min_y), Some(max_x), Some(max_y)] = bounds {
+            entry.extent = Some(Extent {
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+            });
+        }
+        entry.source_files = serde_json::from_str(&source_files)?;
+        entry.tags = serde_json::from_str(&tags)?;
+        entry.labels = serde_json::from_str::<Map<String, Value>>(&labels)?
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect();
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+pub(crate) fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Quoted identifier, keeping its case and any spaces or quotes in DuckDB and Postgres statements alike
+pub(crate) fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub(crate) fn text_array(values: &[String]) -> String {
+    format!(
+        "ARRAY[{}]::text[]",
+        values
+            .iter()
+            .map(|value| literal(value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn labels_json(labels: &[(String, String)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect::<Map<_, _>>();
+    format!("{}::jsonb", literal(&Value::Object(labels).to_string()))
+}
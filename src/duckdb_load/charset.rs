@@ -0,0 +1,410 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// Bytes read from the start of a file to detect its encoding, and read at a time when transcoding it
+const DETECTION_BYTES: usize = 1 << 20;
+
+// Characters Windows-1252 puts where Latin-1 has C1 control codes - None for the five bytes it leaves undefined
+const WINDOWS_1252: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+
+// Canonical name of an encoding as it is given in options and .cpg files, e.g. 'CP1252' or 'ANSI 1252'
+pub(crate) fn normalize(encoding: &str) -> String {
+    let encoding = encoding.trim().to_ascii_lowercase().replace('_', "-");
+    match encoding.as_str() {
+        "utf8" | "utf-8" | "65001" => "utf-8".to_string(),
+        "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" | "88591" | "28591" => {
+            "latin-1".to_string()
+        }
+        "cp1252" | "1252" | "ansi 1252" | "windows-1252" | "win1252" => "windows-1252".to_string(),
+        _ => encoding,
+    }
+}
+
+// Name GDAL's ENCODING open option takes for a canonical encoding
+fn gdal_name(encoding: &str) -> String {
+    match encoding {
+        "utf-8" => "UTF-8".to_string(),
+        "latin-1" => "ISO-8859-1".to_string(),
+        "windows-1252" => "CP1252".to_string(),
+        other => other.to_ascii_uppercase(),
+    }
+}
+
+// Encoding of a text file from its byte order mark, or else from the first DETECTION_BYTES of it
+// Latin-1 and Windows-1252 differ only in bytes 0x80-0x9F, so a file using none of them is reported as Latin-1
+// A prefix mixing UTF-8 with bytes that aren't is an error rather than a guess, as reading it
+// as either would garble part of the text
+pub(crate) fn detect_file(file_path: &str) -> Result<String, Box<dyn Error>> {
+    let mut prefix = Vec::new();
+    File::open(file_path)?
+        .take(DETECTION_BYTES as u64)
+        .read_to_end(&mut prefix)?;
+    if let Some(encoding) = detect_utf16(&prefix) {
+        return Ok(encoding);
+    }
+    let prefix = prefix.strip_prefix(UTF8_BOM).unwrap_or(&prefix);
+    let scan = scan_utf8(prefix);
+    match scan.first_invalid {
+        None => Ok("utf-8".to_string()),
+        Some(offset) if scan.multibyte > 0 => Err(format!(
+            "'{}' mixes UTF-8 with a byte that isn't, at offset {} - give its encoding to read it",
+            file_path, offset
+        )
+        .into()),
+        Some(_) => Ok(single_byte(prefix)),
+    }
+}
+
+// Encoding of bytes already in memory, e.g. the records of a DBF file
+pub(crate) fn detect_bytes(bytes: &[u8]) -> String {
+    if let Some(encoding) = detect_utf16(bytes) {
+        return encoding;
+    }
+    match std::str::from_utf8(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)) {
+        Ok(_) => "utf-8".to_string(),
+        Err(_) => single_byte(bytes),
+    }
+}
+
+// UTF-16 from a byte order mark, or from text where every other byte is NUL as it is for mostly ASCII text
+fn detect_utf16(start: &[u8]) -> Option<String> {
+    match start {
+        [0xFE, 0xFF, ..] => return Some("utf-16be".to_string()),
+        [0xFF, 0xFE, ..] => return Some("utf-16le".to_string()),
+        _ => {}
+    }
+    let sample = &start[..start.len().min(4096) & !1];
+    if sample.len() < 8 {
+        return None;
+    }
+    let nuls = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let pairs = sample.len() / 2;
+    if nuls(1) * 10 >= pairs * 9 && nuls(0) == 0 {
+        Some("utf-16le".to_string())
+    } else if nuls(0) * 10 >= pairs * 9 && nuls(1) == 0 {
+        Some("utf-16be".to_string())
+    } else {
+        None
+    }
+}
+
+// Multi-byte characters in text that is meant to be UTF-8, and the offset of the first byte that isn't
+// A character cut off at the end of the text is neither
+struct Utf8Scan {
+    multibyte: usize,
+    first_invalid: Option<usize>,
+}
+
+fn scan_utf8(bytes: &[u8]) -> Utf8Scan {
+    let multibyte = |text: &[u8]| {
+        std::str::from_utf8(text).map_or(0, |text| text.chars().filter(|c| !c.is_ascii()).count())
+    };
+    let mut scan = Utf8Scan {
+        multibyte: 0,
+        first_invalid: None,
+    };
+    let mut offset = 0;
+    while offset < bytes.len() {
+        match std::str::from_utf8(&bytes[offset..]) {
+            Ok(text) => {
+                scan.multibyte += multibyte(text.as_bytes());
+                break;
+            }
+            Err(e) => {
+                scan.multibyte += multibyte(&bytes[offset..offset + e.valid_up_to()]);
+                let Some(invalid) = e.error_len() else {
+                    break;
+                };
+                scan.first_invalid.get_or_insert(offset + e.valid_up_to());
+                offset += e.valid_up_to() + invalid;
+            }
+        }
+    }
+    scan
+}
+
+fn single_byte(bytes: &[u8]) -> String {
+    if bytes.iter().any(|byte| (0x80..=0x9F).contains(byte)) {
+        "windows-1252".to_string()
+    } else {
+        "latin-1".to_string()
+    }
+}
+
+// Text of a file in a canonical encoding
+pub(crate) fn decode(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    let (encoding, mark) = byte_order(bytes, encoding);
+    decode_block(&bytes[mark..], encoding)
+}
+
+// Copies text in a canonical encoding from reader to writer as UTF-8, a block at a time
+pub(crate) fn transcode(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    encoding: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut block = vec![0; DETECTION_BYTES];
+    let mut pending = Vec::new();
+    let mut resolved = None;
+    loop {
+        let read = reader.read(&mut block)?;
+        pending.extend_from_slice(&block[..read]);
+        // The byte order mark is only looked for once there are enough bytes to hold one
+        let encoding = match resolved {
+            Some(encoding) => encoding,
+            None if read > 0 && pending.len() < UTF8_BOM.len() => continue,
+            None => {
+                let (encoding, mark) = byte_order(&pending, encoding);
+                pending.drain(..mark);
+                *resolved.insert(encoding)
+            }
+        };
+        let complete = if read == 0 {
+            pending.len()
+        } else {
+            complete_length(&pending, encoding)
+        };
+        writer.write_all(decode_block(&pending[..complete], encoding)?.as_bytes())?;
+        pending.drain(..complete);
+        if read == 0 {
+            writer.flush()?;
+            return Ok(());
+        }
+    }
+}
+
+// Encoding settled by any byte order mark at the start of the text, and the length of the mark
+// A byte order mark wins over the stated order, and plain utf-16 without one is little-endian
+fn byte_order<'a>(bytes: &[u8], encoding: &'a str) -> (&'a str, usize) {
+    match (encoding, bytes) {
+        ("utf-8", _) if bytes.starts_with(UTF8_BOM) => ("utf-8", UTF8_BOM.len()),
+        ("utf-16" | "utf-16le" | "utf-16be", [0xFE, 0xFF, ..]) => ("utf-16be", 2),
+        ("utf-16" | "utf-16le" | "utf-16be", [0xFF, 0xFE, ..]) => ("utf-16le", 2),
+        ("utf-16", _) => ("utf-16le", 0),
+        _ => (encoding, 0),
+    }
+}
+
+// Length of the bytes that decode on their own, leaving a character split across blocks for the next one
+fn complete_length(bytes: &[u8], encoding: &str) -> usize {
+    match encoding {
+        "utf-8" => match std::str::from_utf8(bytes) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => bytes.len(),
+        },
+        "utf-16le" | "utf-16be" => {
+            let even = bytes.len() & !1;
+            let last = match &bytes[..even] {
+                [.., a, b] if encoding == "utf-16le" => Some(u16::from_le_bytes([*a, *b])),
+                [.., a, b] => Some(u16::from_be_bytes([*a, *b])),
+                _ => None,
+            };
+            // A high surrogate waits for the low surrogate that completes it
+            match last {
+                Some(0xD800..=0xDBFF) => even - 2,
+                _ => even,
+            }
+        }
+        _ => bytes.len(),
+    }
+}
+
+// Text of bytes after any byte order mark, in an encoding with a settled byte order
+fn decode_block(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    let invalid = || format!("not valid {}", encoding);
+    match encoding {
+        "utf-8" => String::from_utf8(bytes.to_vec()).map_err(|_| invalid()),
+        "latin-1" => Ok(bytes.iter().map(|byte| char::from(*byte)).collect()),
+        "windows-1252" => bytes
+            .iter()
+            .map(|byte| match byte {
+                0x80..=0x9F => WINDOWS_1252[(byte - 0x80) as usize].ok_or_else(invalid),
+                _ => Ok(char::from(*byte)),
+            })
+            .collect(),
+        "utf-16le" | "utf-16be" => {
+            if bytes.len() % 2 != 0 {
+                return Err(invalid());
+            }
+            let units = bytes
+                .chunks_exact(2)
+                .map(|pair| {
+                    let pair = [pair[0], pair[1]];
+                    if encoding == "utf-16be" {
+                        u16::from_be_bytes(pair)
+                    } else {
+                        u16::from_le_bytes(pair)
+                    }
+                })
+                .collect::<Vec<_>>();
+            String::from_utf16(&units).map_err(|_| invalid())
+        }
+        _ => Err(format!("unsupported encoding '{}'", encoding)),
+    }
+}
+
+// Encoding of a Shapefile's DBF attributes, and the GDAL open option that makes ST_Read use it
+// GDAL honours a .cpg sidecar and the DBF's language driver byte itself, and without either reads the
+// attributes as Latin-1, so only then is the encoding detected from the records and passed on
+pub(crate) fn shapefile_encoding(
+    shp_path: &str,
+) -> Result<(Option<String>, Option<String>), Box<dyn Error>> {
+    let path = Path::new(shp_path);
+    let sidecar = |extension: &str| {
+        [extension.to_string(), extension.to_ascii_uppercase()]
+            .into_iter()
+            .map(|extension| path.with_extension(extension))
+            .find(|sidecar| sidecar.exists())
+    };
+    if let Some(cpg) = sidecar("cpg") {
+        return Ok((Some(normalize(&fs::read_to_string(cpg)?)), None));
+    }
+    let Some(dbf) = sidecar("dbf") else {
+        return Ok((None, None));
+    };
+
+    let bytes = fs::read(dbf)?;
+    if bytes.len() < 32 {
+        return Ok((None, None));
+    }
+    let language_driver = match bytes[29] {
+        0x00 => None,
+        0x01 => Some("cp437"),
+        0x02 => Some("cp850"),
+        0x03 | 0x57 => Some("windows-1252"),
+        0x64 => Some("cp852"),
+        0x65 => Some("cp866"),
+        0xC8 => Some("windows-1250"),
+        0xC9 => Some("windows-1251"),
+        other => return Ok((Some(format!("ldid/{}", other)), None)),
+    };
+    if let Some(encoding) = language_driver {
+        return Ok((Some(encoding.to_string()), None));
+    }
+
+    let header_length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let records = bytes.get(header_length..).unwrap_or_default();
+    let encoding = detect_bytes(records);
+    let open_option = (encoding != "latin-1").then(|| format!("ENCODING={}", gdal_name(&encoding)));
+    Ok((Some(encoding), open_option))
+}
+
+// Encoding a source file was found to be in, before it is read
+pub(crate) struct SourceEncoding {
+    pub(crate) file_path: String,
+    pub(crate) encoding: String,
+    // GDAL open option passing the encoding to ST_Read, when GDAL wouldn't find it itself
+    pub(crate) open_option: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_utf8_with_and_without_a_byte_order_mark() {
+        assert_eq!(detect_bytes("name,café\n".as_bytes()), "utf-8");
+        assert_eq!(detect_bytes(b"\xef\xbb\xbfname,caf\xc3\xa9\n"), "utf-8");
+    }
+
+    #[test]
+    fn detects_single_byte_encodings() {
+        assert_eq!(detect_bytes(b"name,caf\xe9\n"), "latin-1");
+        assert_eq!(detect_bytes(b"name,\x93quoted\x94\n"), "windows-1252");
+    }
+
+    #[test]
+    fn detects_utf16_by_byte_order_mark_and_by_nul_bytes() {
+        assert_eq!(detect_bytes(b"\xfe\xff\x00a"), "utf-16be");
+        assert_eq!(detect_bytes(b"\xff\xfea\x00"), "utf-16le");
+        let le = "name,value\n".encode_utf16().flat_map(u16::to_le_bytes);
+        assert_eq!(detect_bytes(&le.collect::<Vec<_>>()), "utf-16le");
+        let be = "name,value\n".encode_utf16().flat_map(u16::to_be_bytes);
+        assert_eq!(detect_bytes(&be.collect::<Vec<_>>()), "utf-16be");
+    }
+
+    #[test]
+    fn decodes_each_encoding() {
+        assert_eq!(decode(b"\xef\xbb\xbfcaf\xc3\xa9", "utf-8").unwrap(), "café");
+        assert_eq!(decode(b"caf\xe9", "latin-1").unwrap(), "café");
+        assert_eq!(decode(b"\x80 \x93x\x94", "windows-1252").unwrap(), "€ “x”");
+        assert_eq!(decode(b"\xff\xfea\x00", "utf-16be").unwrap(), "a");
+        assert_eq!(decode(b"a\x00", "utf-16").unwrap(), "a");
+        assert_eq!(decode(b"\x00a", "utf-16be").unwrap(), "a");
+    }
+
+    #[test]
+    fn rejects_invalid_text() {
+        assert!(decode(b"caf\xe9", "utf-8").is_err());
+        assert!(decode(b"\x81", "windows-1252").is_err());
+        assert!(decode(b"a\x00b", "utf-16le").is_err());
+        assert!(decode(b"\x00\xd8", "utf-16le").is_err());
+        assert!(decode(b"abc", "koi8-r").is_err());
+    }
+
+    #[test]
+    fn transcodes_characters_split_across_blocks() {
+        // After the byte order mark, the emoji's surrogate pair straddles the end of the first block
+        let text = "é".repeat(DETECTION_BYTES / 2 - 2) + "😀,";
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        let mut written = Vec::new();
+        transcode(&utf16[..], &mut written, "utf-16").unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), text);
+    }
+
+    #[test]
+    fn counts_utf8_and_invalid_bytes() {
+        let scan = scan_utf8("café, caf".as_bytes());
+        assert_eq!((scan.multibyte, scan.first_invalid), (1, None));
+        let scan = scan_utf8(b"caf\xc3\xa9, caf\xe9,");
+        assert_eq!((scan.multibyte, scan.first_invalid), (1, Some(10)));
+        // A character cut off by the end of a prefix is not an invalid byte
+        let scan = scan_utf8(b"caf\xc3");
+        assert_eq!((scan.multibyte, scan.first_invalid), (0, None));
+    }
+}
@@ -0,0 +1,134 @@
+use super::priority::PriorityGate;
+use super::{
+    open_connection, ContractViolationError, DuckDBFileProcessor, IncompatibleSchemaError,
+    LoadCancelledError, LoadOptions, LoadResult, Sink,
+};
+use duckdb::Connection;
+use std::error::Error;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// The error as one that can be sent between threads if it is one callers match on, or else the error back
+fn typed_error(e: Box<dyn Error>) -> Result<Box<dyn Error + Send + Sync>, Box<dyn Error>> {
+    let e = match e.downcast::<ContractViolationError>() {
+        Ok(e) => return Ok(e),
+        Err(e) => e,
+    };
+    match e.downcast::<IncompatibleSchemaError>() {
+        Ok(e) => Ok(e),
+        Err(e) => Err(e),
+    }
+}
+
+// A DuckDB database with the required extensions loaded, shared by any number of loads
+// Each load gets its own connection and uniquely named intermediate tables, so loads can run concurrently
+// Batch priority loads give way to interactive ones, see LoadOptions::priority
+pub struct LoaderContext {
+    conn: Mutex<Connection>,
+    next_load_id: AtomicU64,
+    gate: PriorityGate,
+}
+
+impl LoaderContext {
+    pub fn new() -> Result<Self, io::Error> {
+        let conn = open_connection()
+            .map_err(|e| io::Error::other(format!("Error creating DuckDB connection: {}", e)))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            next_load_id: AtomicU64::new(1),
+            gate: PriorityGate::default(),
+        })
+    }
+
+    fn connection(&self) -> Result<Connection, Box<dyn Error>> {
+        Ok(self.conn.lock().unwrap().try_clone()?)
+    }
+
+    pub fn load_file(
+        &self,
+        file_path: &str,
+        table_name: &str,
+        options: &LoadOptions,
+    ) -> Result<LoadResult, io::Error> {
+        self.load_files(&[file_path], table_name, options)
+    }
+
+    // Merges several files of the same type into a single table
+    pub fn load_files(
+        &self,
+        file_paths: &[&str],
+        table_name: &str,
+        options: &LoadOptions,
+    ) -> Result<LoadResult, io::Error> {
+        let file_path = file_paths.join(", ");
+        let load_id = self.next_load_id.fetch_add(1, Ordering::SeqCst);
+        let _interactive = self.gate.enter(options.priority);
+
+        // Create new processor object
+        let processor = self.connection().and_then(|conn| {
+            DuckDBFileProcessor::new_file(file_paths, table_name, options, conn, load_id)
+        });
+        let mut processor = processor.map_err(|e| {
+            io::Error::other(format!(
+                "Error creating FileProcessor for '{}': {}",
+                file_path, e
+            ))
+        })?;
+
+        println!(
+            "Detected file type: {} for file: '{}'",
+            processor.file_type_name(),
+            file_path
+        );
+
+        // Process the file, reporting the statement that failed
+        let started = Instant::now();
+        // A query interrupted by cancelling fails with DuckDB's own error
+        let outcome = processor
+            .process_new_file(&self.gate)
+            .map_err(|e| options.cancellation.interrupted(e));
+        if options.write_load_history && options.sink == Sink::PostGis {
+            processor.record_load(&outcome, started.elapsed());
+        }
+        let result = outcome.map_err(|e| {
+            if e.is::<LoadCancelledError>() {
+                println!(
+                    "Cancelled load of {} file: '{}'",
+                    processor.file_type_name(),
+                    file_path
+                );
+                return io::Error::new(io::ErrorKind::Interrupted, LoadCancelledError);
+            }
+            let last_statement = processor
+                .conn
+                .log()
+                .last()
+                .map(|entry| format!(" (last {:?} statement: {})", entry.engine, entry.sql))
+                .unwrap_or_default();
+            let message = format!(
+                "Error processing {} file '{}': {}{}",
+                processor.file_type_name(),
+                file_path,
+                e,
+                last_statement
+            );
+            // Errors callers act on keep their type, so they can be downcast from the io::Error
+            match typed_error(e) {
+                Ok(e) => {
+                    println!("{}", message);
+                    io::Error::other(e)
+                }
+                Err(_) => io::Error::other(message),
+            }
+        })?;
+
+        println!(
+            "Successfully loaded {} file: '{}'",
+            processor.file_type_name(),
+            file_path
+        );
+        Ok(result)
+    }
+}
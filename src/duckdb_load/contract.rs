@@ -0,0 +1,209 @@
+use super::catalog::{identifier, literal};
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+// Expected name and DuckDB type of a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractColumn {
+    pub name: String,
+    pub data_type: String,
+}
+
+// Schema a supplier's file is expected to match, pinned per dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaContract {
+    pub columns: Vec<ContractColumn>,
+    // Geometry type every feature must have, e.g. MULTIPOLYGON
+    #[serde(default)]
+    pub geometry_type: Option<String>,
+    // EPSG code of the source data
+    #[serde(default)]
+    pub srid: Option<String>,
+    // Columns not listed in the contract are tolerated when true
+    #[serde(default)]
+    pub allow_extra_columns: bool,
+}
+
+impl SchemaContract {
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read schema contract '{}': {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)
+            .map_err(|e| format!("Could not write schema contract '{}': {}", path, e))?;
+        Ok(())
+    }
+
+    // Lists every way the actual schema deviates from the contract
+    pub(crate) fn violations(
+        &self,
+        columns: &[(String, String)],
+        geometry_types: &[String],
+        srid: Option<&str>,
+    ) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        for expected in &self.columns {
+            match columns.iter().find(|(name, _)| *name == expected.name) {
+                None => violations.push(format!("Missing column {}", expected.name)),
+                Some((_, data_type)) if !data_type.eq_ignore_ascii_case(&expected.data_type) => {
+                    violations.push(format!(
+                        "Column {} has type {}, expected {}",
+                        expected.name, data_type, expected.data_type
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !self.allow_extra_columns {
+            for (name, data_type) in columns {
+                if !self.columns.iter().any(|expected| expected.name == *name) {
+                    violations.push(format!("Unexpected column {} ({})", name, data_type));
+                }
+            }
+        }
+
+        if let Some(expected) = &self.geometry_type {
+            for geometry_type in geometry_types {
+                if !geometry_type.eq_ignore_ascii_case(expected) {
+                    violations.push(format!(
+                        "Found {} geometries, expected {}",
+                        geometry_type, expected
+                    ));
+                }
+            }
+        }
+
+        if let (Some(expected), Some(actual)) = (&self.srid, srid) {
+            if expected != actual {
+                violations.push(format!(
+                    "Data is in EPSG:{}, expected EPSG:{}",
+                    actual, expected
+                ));
+            }
+        }
+
+        violations
+    }
+}
+
+// Returned when a load does not match its schema contract
+#[derive(Debug)]
+pub struct ContractViolationError {
+    pub violations: Vec<String>,
+}
+
+impl fmt::Display for ContractViolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Schema contract violated ({} issues): {}",
+            self.violations.len(),
+            self.violations.join("; ")
+        )
+    }
+}
+
+impl Error for ContractViolationError {}
+
+// Reads columns, geometry columns and geometry types of a table in the attached PostGIS database
+// schema None looks in the current schema
+pub(crate) fn contract_from_table(
+    conn: &Connection,
+    database: &str,
+    schema: Option<&str>,
+    table_name: &str,
+) -> Result<SchemaContract, Box<dyn Error>> {
+    let postgres_query = |sql: &str| {
+        format!(
+            "SELECT * FROM postgres_query({}, {});",
+            literal(database),
+            literal(sql)
+        )
+    };
+    let schema = match schema {
+        Some(schema) => schema.to_string(),
+        None => conn.query_row(
+            &postgres_query("SELECT current_schema()::text"),
+            [],
+            |row| row.get(0),
+        )?,
+    };
+
+    // Geometry columns registered by PostGIS
+    let mut stmt = conn.prepare(&postgres_query(&format!(
+        "SELECT f_geometry_column::text FROM geometry_columns WHERE f_table_schema = {} AND f_table_name = {}",
+        literal(&schema),
+        literal(table_name)
+    )))?;
+    let mut rows = stmt.query([])?;
+    let mut geom_columns = Vec::new();
+    while let Some(row) = rows.next()? {
+        let column: String = row.get(0)?;
+        geom_columns.push(column);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT column_name, data_type FROM information_schema.columns WHERE table_catalog = ? AND table_schema = ? AND table_name = ? ORDER BY ordinal_position",
+    )?;
+    let mut rows = stmt.query([database, schema.as_str(), table_name])?;
+    let mut columns = Vec::new();
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let data_type: String = row.get(1)?;
+        let data_type = if geom_columns.contains(&name) {
+            "GEOMETRY".to_string()
+        } else {
+            data_type
+        };
+        columns.push(ContractColumn { name, data_type });
+    }
+    if columns.is_empty() {
+        return Err(format!("Table {}.{} not found", schema, table_name).into());
+    }
+
+    // Only pin a geometry type when the table has exactly one
+    let mut geometry_types = Vec::new();
+    for column in &geom_columns {
+        let mut stmt = conn.prepare(&postgres_query(&format!(
+            "SELECT DISTINCT upper(GeometryType({})) FROM {}.{} WHERE {} IS NOT NULL",
+            identifier(column),
+            identifier(&schema),
+            identifier(table_name),
+            identifier(column)
+        )))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let geometry_type: String = row.get(0)?;
+            if !geometry_types.contains(&geometry_type) {
+                geometry_types.push(geometry_type);
+            }
+        }
+    }
+    let geometry_type = match geometry_types.as_slice() {
+        [geometry_type] => Some(geometry_type.clone()),
+        _ => None,
+    };
+
+    Ok(SchemaContract {
+        columns,
+        geometry_type,
+        srid: None,
+        allow_extra_columns: false,
+    })
+}
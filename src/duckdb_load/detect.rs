@@ -0,0 +1,242 @@
+use super::charset;
+use super::source::Confidence;
+use super::FileType;
+
+// Bytes read from the start of a file to detect its type
+pub(crate) const DETECTION_PREFIX_BYTES: usize = 64 * 1024;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// Confidence that a file is of a built-in type, given at most DETECTION_PREFIX_BYTES of it
+pub(crate) fn detect(file_type: FileType, buffer: &[u8]) -> Option<Confidence> {
+    // Magic bytes are all within the first 16 bytes, and shorter files are matched on what there is
+    let header = buffer.get(..16).unwrap_or(buffer);
+    let magic = match file_type {
+        FileType::Excel => header.starts_with(b"PK\x03\x04"),
+        FileType::Geopackage => header.starts_with(b"SQLite format 3\0"),
+        FileType::Shapefile => header.starts_with(&[0, 0, 39, 10]),
+        FileType::Parquet => header.starts_with(b"PAR1"),
+        // DuckDB databases start with a checksum, followed by the magic bytes
+        FileType::DuckDb => header.get(8..12) == Some(b"DUCK"),
+        FileType::Arrow => header.starts_with(b"ARROW1"),
+        // PBF files start with the length of a BlobHeader whose type is the 9 byte string OSMHeader
+        FileType::OsmPbf => header.get(4..15) == Some(b"\x0a\x09OSMHeader"),
+        FileType::Gpx => return is_gpx(buffer).then_some(Confidence::Medium),
+        FileType::Geojson => {
+            return (starts_like_json(buffer) && is_geojson(buffer)).then_some(Confidence::Medium)
+        }
+        FileType::Csv => return looks_like_csv(buffer).then_some(Confidence::Low),
+        FileType::Registered => false,
+    };
+    magic.then_some(Confidence::High)
+}
+
+// Built-in file type from the magic bytes or text at the start of a file, given at most
+// DETECTION_PREFIX_BYTES of it
+pub(crate) fn sniff_prefix(buffer: &[u8]) -> Option<FileType> {
+    FileType::BUILT_IN
+        .iter()
+        .filter_map(|file_type| Some((detect(*file_type, buffer)?, *file_type)))
+        .max_by_key(|(confidence, _)| *confidence)
+        .map(|(_, file_type)| file_type)
+}
+
+// Comma separated lines with the same number of fields - JSON that isn't GeoJSON is not taken for CSV
+fn looks_like_csv(buffer: &[u8]) -> bool {
+    if starts_like_json(buffer) {
+        return false;
+    }
+    let Some(file_text) = prefix_text(buffer) else {
+        return false;
+    };
+    let lines: Vec<&str> = file_text.lines().collect();
+    lines.len() >= 2
+        && lines[0].split(',').count() > 1
+        && lines[1].split(',').count() == lines[0].split(',').count()
+        && !file_text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\r' | '\n'))
+}
+
+// Complete lines of text at the start of a file, in any encoding CSVs are read in, e.g. Windows-1252 or
+// UTF-16, without a byte order mark
+// A prefix that fills the buffer may end part way through a line or a multi-byte character, so that tail is dropped
+fn prefix_text(prefix: &[u8]) -> Option<String> {
+    let complete = if prefix.len() < DETECTION_PREFIX_BYTES {
+        prefix
+    } else {
+        &prefix[..prefix.iter().rposition(|byte| *byte == b'\n')?]
+    };
+    let encoding = charset::detect_bytes(complete);
+    let complete = if encoding.starts_with("utf-16") {
+        &complete[..complete.len() & !1]
+    } else {
+        complete
+    };
+    charset::decode(complete, &encoding).ok()
+}
+
+// XML whose root element is gpx, after any declaration and comments
+fn is_gpx(buffer: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(buffer.strip_prefix(UTF8_BOM).unwrap_or(buffer));
+    let mut rest = text.trim_start();
+    loop {
+        let (after, end) = if let Some(after) = rest.strip_prefix("<?") {
+            (after, "?>")
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            (after, "-->")
+        } else {
+            break;
+        };
+        let Some(i) = after.find(end) else {
+            return false;
+        };
+        rest = after[i + end.len()..].trim_start();
+    }
+    rest.strip_prefix("<gpx")
+        .and_then(|after| after.chars().next())
+        .is_some_and(|next| next == '>' || next.is_whitespace())
+}
+
+// Whether the file starts like a JSON document, ignoring a byte order mark and leading whitespace
+pub(crate) fn starts_like_json(prefix: &[u8]) -> bool {
+    let mut scanner = Scanner::new(prefix);
+    scanner.skip_whitespace();
+    matches!(scanner.peek(), Some(b'{' | b'['))
+}
+
+// Scans the start of a JSON document for the members that mark it as GeoJSON
+// Only the top-level object is inspected, so large feature arrays before a 'type' member are skipped over
+// without being interpreted, and a document cut off by the prefix is judged on what was read
+pub(crate) fn is_geojson(prefix: &[u8]) -> bool {
+    let mut scanner = Scanner::new(prefix);
+    scanner.skip_whitespace();
+    if !scanner.consume(b'{') {
+        return false;
+    }
+
+    loop {
+        scanner.skip_whitespace();
+        let Some(key) = scanner.string() else {
+            return false;
+        };
+        scanner.skip_whitespace();
+        if !scanner.consume(b':') {
+            return false;
+        }
+        scanner.skip_whitespace();
+
+        match key {
+            b"type" => {
+                return matches!(
+                    scanner.string(),
+                    Some(b"FeatureCollection") | Some(b"Feature")
+                );
+            }
+            // A features array is only found in a FeatureCollection, whatever order the members are in
+            b"features" if scanner.peek() == Some(b'[') => return true,
+            _ => {
+                if !scanner.skip_value() {
+                    return false;
+                }
+            }
+        }
+
+        scanner.skip_whitespace();
+        if !scanner.consume(b',') {
+            return false;
+        }
+    }
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+        Self { bytes, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn consume(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+    }
+
+    // Raw contents of a string, with escapes left in place
+    fn string(&mut self) -> Option<&'a [u8]> {
+        if !self.consume(b'"') {
+            return None;
+        }
+        let start = self.pos;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(&self.bytes[start..self.pos - 1]);
+                }
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        None
+    }
+
+    // Skips any value, returning false if the input ends first
+    fn skip_value(&mut self) -> bool {
+        match self.peek() {
+            Some(b'"') => self.string().is_some(),
+            Some(b'{' | b'[') => {
+                let mut depth = 0usize;
+                while let Some(byte) = self.peek() {
+                    match byte {
+                        b'"' => {
+                            if self.string().is_none() {
+                                return false;
+                            }
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.pos += 1;
+                                return true;
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                false
+            }
+            Some(_) => {
+                // Numbers, true, false and null run until the next delimiter
+                while let Some(byte) = self.peek() {
+                    if matches!(byte, b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n') {
+                        return true;
+                    }
+                    self.pos += 1;
+                }
+                false
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::geometry::GeometryEncoding;
+use super::query_log::LoggedConnection;
+use super::report::GeometryColumnType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DictionaryFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+// Writes a data dictionary describing the loaded columns next to each load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataDictionaryOptions {
+    // '{table}' is replaced with the table name so directory loads write one file each
+    pub path: String,
+    pub format: DictionaryFormat,
+    // Column descriptions keyed by loaded column name, e.g. from the dataset's publication notes
+    pub descriptions: HashMap<String, String>,
+    // Distinct non-null values listed per column
+    pub example_values: usize,
+}
+
+impl DataDictionaryOptions {
+    pub fn new(path: &str, format: DictionaryFormat) -> Self {
+        Self {
+            path: path.to_string(),
+            format,
+            descriptions: HashMap::new(),
+            example_values: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DictionaryEntry {
+    pub column: String,
+    // DuckDB type, or the PostGIS type for geometry columns, e.g. geometry(MultiPolygon, 4326)
+    pub data_type: String,
+    pub description: Option<String>,
+    pub null_percent: f64,
+    // Empty for geometry columns
+    pub examples: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataDictionary {
+    pub table_name: String,
+    pub row_count: u64,
+    pub columns: Vec<DictionaryEntry>,
+}
+
+impl DataDictionary {
+    // Describes the transformed table, where geometry columns are held as the encoding says
+    pub(crate) fn build(
+        conn: &LoggedConnection,
+        source_table: &str,
+        table_name: &str,
+        columns: &[(String, String)],
+        column_types: &[GeometryColumnType],
+        encoding: GeometryEncoding,
+        options: &DataDictionaryOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Row count followed by the non-null count of each column
+        let counts = std::iter::once("count(*)".to_string())
+            .chain(
+                columns
+                    .iter()
+                    .map(|(name, _)| format!("count(\"{}\")", name)),
+            )
+            .collect::<Vec<_>>();
+        let counts = conn.query_row(
+            &format!("SELECT {} FROM {};", counts.join(", "), source_table),
+            [],
+            |row| {
+                (0..=columns.len())
+                    .map(|i| row.get::<_, i64>(i))
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        )?;
+        let row_count = counts[0];
+
+        let mut entries = Vec::new();
+        for (i, (name, data_type)) in columns.iter().enumerate() {
+            let geometry = column_types
+                .iter()
+                .find(|column_type| encoding.stored_column(&column_type.column) == *name);
+            let null_percent = if row_count > 0 {
+                let nulls = row_count - counts[i + 1];
+                (nulls as f64 * 10000.0 / row_count as f64).round() / 100.0
+            } else {
+                0.0
+            };
+
+            let (column, data_type, examples) = match geometry {
+                Some(column_type) => (
+                    column_type.column.clone(),
+                    column_type.postgis_type(),
+                    Vec::new(),
+                ),
+                None => (
+                    name.clone(),
+                    data_type.clone(),
+                    Self::examples(conn, source_table, name, options.example_values)?,
+                ),
+            };
+            entries.push(DictionaryEntry {
+                description: options.descriptions.get(&column).cloned(),
+                column,
+                data_type,
+                null_percent,
+                examples,
+            });
+        }
+
+        Ok(Self {
+            table_name: table_name.to_string(),
+            row_count: row_count as u64,
+            columns: entries,
+        })
+    }
+
+    fn examples(
+        conn: &LoggedConnection,
+        source_table: &str,
+        column: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT \"{}\"::VARCHAR AS value FROM {} WHERE \"{}\" IS NOT NULL ORDER BY value LIMIT {};",
+            column, source_table, column, limit
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut examples = Vec::new();
+        while let Some(row) = rows.next()? {
+            examples.push(row.get(0)?);
+        }
+        Ok(examples)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // One row per column, with example values separated by '; '
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("column,data_type,description,null_percent,examples\n");
+        for entry in &self.columns {
+            let fields = [
+                csv_field(&entry.column),
+                csv_field(&entry.data_type),
+                csv_field(entry.description.as_deref().unwrap_or("")),
+                entry.null_percent.to_string(),
+                csv_field(&entry.examples.join("; ")),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub(crate) fn write(&self, options: &DataDictionaryOptions) -> Result<(), Box<dyn Error>> {
+        let path = options.path.replace("{table}", &self.table_name);
+        let contents = match options.format {
+            DictionaryFormat::Json => self.to_json()?,
+            DictionaryFormat::Csv => self.to_csv(),
+        };
+        fs::write(&path, contents)
+            .map_err(|e| format!("Could not write data dictionary '{}': {}", path, e))?;
+        Ok(())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,282 @@
+use super::{FileType, LoadOptions, LoadResult, LoaderContext};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Options for loading every supported file in a directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectoryOptions {
+    // Descend into subdirectories
+    pub recursive: bool,
+    // Glob patterns (relative to the directory) a file must match - empty matches everything
+    pub include: Vec<String>,
+    // Glob patterns (relative to the directory) that exclude a file
+    pub exclude: Vec<String>,
+    // Number of files processed at the same time
+    pub workers: usize,
+    // Options applied to every file
+    pub load_options: LoadOptions,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            load_options: LoadOptions::default(),
+        }
+    }
+}
+
+// Outcome of loading a single file found in the directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileLoadOutcome {
+    pub file_path: PathBuf,
+    pub table_name: String,
+    pub result: Result<LoadResult, String>,
+}
+
+pub fn load_directory(
+    path: &str,
+    options: &DirectoryOptions,
+) -> Result<Vec<FileLoadOutcome>, io::Error> {
+    let root = Path::new(path);
+    let filters = Filters {
+        include: patterns(&options.include)?,
+        exclude: patterns(&options.exclude)?,
+    };
+    let mut files = Vec::new();
+    discover_files(root, root, options, &filters, &mut files)?;
+    files.sort();
+    check_table_names(&files)?;
+    println!("Discovered {} files in '{}'", files.len(), path);
+
+    // Workers share one DuckDB database and take the next unprocessed file until none are left
+    let context = LoaderContext::new()?;
+    let next_file = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::with_capacity(files.len()));
+    thread::scope(|scope| {
+        for _ in 0..options.workers.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next_file.fetch_add(1, Ordering::SeqCst);
+                let Some((file_path, table_name)) = files.get(index) else {
+                    break;
+                };
+                let result = context
+                    .load_file(
+                        &file_path.to_string_lossy(),
+                        table_name,
+                        &options.load_options,
+                    )
+                    .map_err(|e| e.to_string());
+                outcomes.lock().unwrap().push((
+                    index,
+                    FileLoadOutcome {
+                        file_path: file_path.clone(),
+                        table_name: table_name.clone(),
+                        result,
+                    },
+                ));
+            });
+        }
+    });
+
+    // Return outcomes in discovery order
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|(index, _)| *index);
+    Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+}
+
+// Compiled include and exclude patterns
+struct Filters {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+fn patterns(globs: &[String]) -> Result<Vec<Pattern>, io::Error> {
+    globs
+        .iter()
+        .map(|glob| {
+            Pattern::new(glob).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid glob pattern '{}': {}", glob, e),
+                )
+            })
+        })
+        .collect()
+}
+
+fn discover_files(
+    root: &Path,
+    dir: &Path,
+    options: &DirectoryOptions,
+    filters: &Filters,
+    files: &mut Vec<(PathBuf, String)>,
+) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Symlinked directories are not followed, so a link back up the tree can't loop
+        if fs::symlink_metadata(&path)?.is_dir() {
+            if options.recursive {
+                discover_files(root, &path, options, filters, files)?;
+            }
+            continue;
+        }
+
+        // Only files with a supported extension are picked up
+        let path_str = path.to_string_lossy();
+        if FileType::from_extension(&path_str).is_none()
+            && options
+                .load_options
+                .sources
+                .handler_for_extension(&path_str)
+                .is_none()
+        {
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let relative_str = relative.to_string_lossy().replace('\\', "/");
+        let included = filters.include.is_empty()
+            || filters
+                .include
+                .iter()
+                .any(|pattern| glob_match(pattern, &relative_str));
+        let excluded = filters
+            .exclude
+            .iter()
+            .any(|pattern| glob_match(pattern, &relative_str));
+        if included && !excluded {
+            files.push((path.clone(), table_name_for(relative)));
+        }
+    }
+    Ok(())
+}
+
+// Fails when two files would load into the same table, e.g. 'a-b.csv' and 'a_b.csv', or 'x.gpkg' and 'x.geojson',
+// before any of them are loaded
+fn check_table_names(files: &[(PathBuf, String)]) -> Result<(), io::Error> {
+    let mut first_file = HashMap::new();
+    let mut collisions = Vec::new();
+    for (file_path, table_name) in files {
+        if let Some(first) = first_file.insert(table_name, file_path) {
+            collisions.push(format!(
+                "'{}' and '{}' both load into table {}",
+                first.display(),
+                file_path.display(),
+                table_name
+            ));
+            first_file.insert(table_name, first);
+        }
+    }
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "Files would overwrite each other's tables - rename or exclude them: {}",
+            collisions.join("; ")
+        ),
+    ))
+}
+
+// Builds a table name from the file's relative path, e.g. 'boundaries/Wards 2022.shp' -> 'boundaries_wards_2022'
+fn table_name_for(relative: &Path) -> String {
+    let without_extension = relative.with_extension("");
+    let mut name = String::new();
+    for c in without_extension.to_string_lossy().chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_matches('_').to_string();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("t_{}", name)
+    } else {
+        name
+    }
+}
+
+// Matches '/'-separated paths against a glob pattern supporting '*', '?', '[...]' and '**' as a whole
+// path component, ignoring case as Windows and macOS paths do
+fn glob_match(pattern: &Pattern, path: &str) -> bool {
+    pattern.matches_with(
+        path,
+        MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        glob_match(&Pattern::new(pattern).unwrap(), path)
+    }
+
+    #[test]
+    fn star_stays_within_a_directory() {
+        assert!(matches("*.csv", "wards.csv"));
+        assert!(!matches("*.csv", "boundaries/wards.csv"));
+        assert!(matches("boundaries/*.SHP", "Boundaries/wards.shp"));
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        assert!(matches("**/*.csv", "wards.csv"));
+        assert!(matches("**/*.csv", "a/b/c/wards.csv"));
+        assert!(matches("archive/**", "archive/2021/wards.csv"));
+        assert!(!matches("archive/**", "current/wards.csv"));
+    }
+
+    #[test]
+    fn long_paths_match_quickly() {
+        let path = format!("{}b", "a/".repeat(200));
+        assert!(!matches("**/*a*a*a*a*a*a*c", &path));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(patterns(&["a**b".to_string()]).is_err());
+        assert!(patterns(&["[abc".to_string()]).is_err());
+    }
+
+    #[test]
+    fn colliding_table_names_are_rejected() {
+        let files = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| (PathBuf::from(name), table_name_for(Path::new(name))))
+                .collect::<Vec<_>>()
+        };
+        assert!(check_table_names(&files(&["a-b.csv", "c.csv"])).is_ok());
+        assert!(check_table_names(&files(&["a-b.csv", "a_b.csv"])).is_err());
+        assert!(check_table_names(&files(&["x.geojson", "x.gpkg"])).is_err());
+    }
+
+    #[test]
+    fn table_names_come_from_the_relative_path() {
+        assert_eq!(
+            table_name_for(Path::new("boundaries/Wards 2022.shp")),
+            "boundaries_wards_2022"
+        );
+        assert_eq!(table_name_for(Path::new("2022.csv")), "t_2022");
+    }
+}
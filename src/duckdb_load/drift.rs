@@ -0,0 +1,127 @@
+use super::options::DriftThresholds;
+use super::report::{DriftReport, Extent};
+
+// Compares a new load against the table it replaces and flags changes beyond the thresholds
+pub(crate) fn assess_drift(
+    previous_row_count: u64,
+    row_count: u64,
+    previous_extent: Option<Extent>,
+    extent: Option<Extent>,
+    thresholds: &DriftThresholds,
+) -> DriftReport {
+    let mut reasons = Vec::new();
+
+    if previous_row_count > 0 {
+        let change = (row_count as f64 - previous_row_count as f64) / previous_row_count as f64;
+        if change < -thresholds.max_row_decrease {
+            reasons.push(format!(
+                "Row count fell by {:.1}% ({} -> {}), more than the allowed {:.1}%",
+                -change * 100.0,
+                previous_row_count,
+                row_count,
+                thresholds.max_row_decrease * 100.0
+            ));
+        } else if change > thresholds.max_row_increase {
+            reasons.push(format!(
+                "Row count rose by {:.1}% ({} -> {}), more than the allowed {:.1}%",
+                change * 100.0,
+                previous_row_count,
+                row_count,
+                thresholds.max_row_increase * 100.0
+            ));
+        }
+    }
+
+    if let (Some(previous), Some(current)) = (previous_extent, extent) {
+        if let Some(change) = extent_change(&previous, &current) {
+            if change > thresholds.max_extent_change {
+                reasons.push(format!(
+                    "Extent changed by {:.1}% of the area the two extents cover, more than the allowed {:.1}%",
+                    change * 100.0,
+                    thresholds.max_extent_change * 100.0
+                ));
+            }
+        }
+    }
+
+    DriftReport {
+        previous_row_count,
+        row_count,
+        previous_extent,
+        extent,
+        suspicious: !reasons.is_empty(),
+        reasons,
+    }
+}
+
+// One minus the intersection over union of two extents, so an extent that moves without changing size
+// counts as much as one that grows or shrinks - None when both are a point or a line with no area
+fn extent_change(previous: &Extent, current: &Extent) -> Option<f64> {
+    let overlap = Extent {
+        min_x: previous.min_x.max(current.min_x),
+        min_y: previous.min_y.max(current.min_y),
+        max_x: previous.max_x.min(current.max_x),
+        max_y: previous.max_y.min(current.max_y),
+    };
+    let intersection = if overlap.min_x < overlap.max_x && overlap.min_y < overlap.max_y {
+        overlap.area()
+    } else {
+        0.0
+    };
+    let union = previous.area() + current.area() - intersection;
+    (union > 0.0).then(|| 1.0 - intersection / union)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Extent {
+        Extent {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    #[test]
+    fn unchanged_extent_has_no_change() {
+        let bounds = extent(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(extent_change(&bounds, &bounds), Some(0.0));
+    }
+
+    #[test]
+    fn moved_extent_of_the_same_area_is_a_change() {
+        let previous = extent(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            extent_change(&previous, &extent(100.0, 100.0, 110.0, 110.0)),
+            Some(1.0)
+        );
+        // Half overlapping: 50 shared out of 150 covered
+        let change = extent_change(&previous, &extent(5.0, 0.0, 15.0, 10.0)).unwrap();
+        assert!((change - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn extents_without_area_are_not_compared() {
+        let point = extent(1.0, 1.0, 1.0, 1.0);
+        assert_eq!(extent_change(&point, &extent(2.0, 2.0, 2.0, 2.0)), None);
+        assert_eq!(
+            extent_change(&point, &extent(0.0, 0.0, 2.0, 2.0)),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn moved_extent_is_reported_as_drift() {
+        let report = assess_drift(
+            100,
+            100,
+            Some(extent(0.0, 0.0, 10.0, 10.0)),
+            Some(extent(20.0, 0.0, 30.0, 10.0)),
+            &DriftThresholds::default(),
+        );
+        assert!(report.suspicious);
+    }
+}
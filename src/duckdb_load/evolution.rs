@@ -0,0 +1,296 @@
+use super::catalog::identifier;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+// How an append reconciles the columns of the source with the table it is added to
+// Types are compared as Postgres names them, e.g. 'integer' or 'character varying(20)'
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchemaEvolution {
+    // Add source columns the table doesn't have, as nullable columns
+    pub add_columns: bool,
+    // Change a column to a wider type when the source values don't fit it, e.g. integer to bigint
+    pub widen_types: bool,
+    pub on_incompatible: IncompatibleChangePolicy,
+}
+
+impl Default for SchemaEvolution {
+    fn default() -> Self {
+        Self {
+            add_columns: true,
+            widen_types: true,
+            on_incompatible: IncompatibleChangePolicy::default(),
+        }
+    }
+}
+
+// What to do with a source column whose type can't be stored in the table's column, e.g. text into a date
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum IncompatibleChangePolicy {
+    // Fail the load before anything is inserted
+    #[default]
+    Fail,
+    // Load the other columns, leaving the column NULL for the new rows
+    SkipColumn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SchemaChangeKind {
+    // New column added to the table
+    Added,
+    // Table column changed to a wider type
+    Widened,
+    // Source values converted to the table column's type
+    Cast,
+    // Column left out of the insert, or failing the load under IncompatibleChangePolicy::Fail
+    Incompatible,
+    // Table column the source doesn't have, left NULL for the new rows
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub column: String,
+    pub kind: SchemaChangeKind,
+    pub source_type: Option<String>,
+    // Type of the table column before the append - None for added columns
+    pub target_type: Option<String>,
+}
+
+// Differences between the source and the table found by an append, and how each was resolved
+// Columns with the same type on both sides are not listed
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SchemaEvolutionReport {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaEvolutionReport {
+    pub fn incompatible(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == SchemaChangeKind::Incompatible)
+    }
+
+    fn change(&self, column: &str) -> Option<&SchemaChange> {
+        self.changes.iter().find(|change| change.column == column)
+    }
+
+    // Postgres statements adding and widening the table's columns, run in the batch with the insert
+    pub(crate) fn alter_statements(&self, table: &str) -> Vec<String> {
+        let mut statements = Vec::new();
+        for change in &self.changes {
+            let Some(source_type) = &change.source_type else {
+                continue;
+            };
+            let column = identifier(&change.column);
+            match change.kind {
+                SchemaChangeKind::Added => statements.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {} {};",
+                    table, column, source_type
+                )),
+                SchemaChangeKind::Widened => {
+                    let widened = widened_type(source_type, change.target_type.as_deref());
+                    statements.push(format!(
+                        "ALTER TABLE {} ALTER COLUMN {} TYPE {} USING {}::{};",
+                        table, column, widened, column, widened
+                    ))
+                }
+                _ => {}
+            }
+        }
+        statements
+    }
+
+    // Expression inserting a source column, or None when it is left out
+    pub(crate) fn insert_value(&self, column: &str) -> Option<String> {
+        let quoted = identifier(column);
+        match self.change(column) {
+            None => Some(quoted),
+            Some(change) => match change.kind {
+                SchemaChangeKind::Incompatible | SchemaChangeKind::Missing => None,
+                SchemaChangeKind::Cast => Some(format!(
+                    "{}::{}",
+                    quoted,
+                    change.target_type.as_deref().unwrap_or("text")
+                )),
+                SchemaChangeKind::Added | SchemaChangeKind::Widened => Some(quoted),
+            },
+        }
+    }
+}
+
+// Raised under IncompatibleChangePolicy::Fail, before the table changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncompatibleSchemaError {
+    pub report: SchemaEvolutionReport,
+}
+
+impl fmt::Display for IncompatibleSchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self
+            .report
+            .incompatible()
+            .map(|change| {
+                format!(
+                    "{} ({} into {})",
+                    change.column,
+                    change.source_type.as_deref().unwrap_or("?"),
+                    change.target_type.as_deref().unwrap_or("?")
+                )
+            })
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "Source columns can't be appended to the table: {}",
+            columns.join(", ")
+        )
+    }
+}
+
+impl Error for IncompatibleSchemaError {}
+
+impl SchemaEvolution {
+    // Compares (name, type) pairs of the source with those of the table
+    // Fails under IncompatibleChangePolicy::Fail, and when a new column is found without add_columns
+    pub fn plan(
+        &self,
+        source: &[(String, String)],
+        target: &[(String, String)],
+    ) -> Result<SchemaEvolutionReport, Box<dyn Error>> {
+        let mut report = SchemaEvolutionReport::default();
+        for (column, source_type) in source {
+            let target_type = target
+                .iter()
+                .find(|(name, _)| name == column)
+                .map(|(_, data_type)| data_type);
+            let kind = match target_type {
+                None if self.add_columns => SchemaChangeKind::Added,
+                None => {
+                    return Err(format!(
+                        "Column {} is not in the table and SchemaEvolution::add_columns is off",
+                        column
+                    )
+                    .into())
+                }
+                Some(target_type) => match compare(source_type, target_type) {
+                    Compatibility::Same => continue,
+                    Compatibility::Cast => SchemaChangeKind::Cast,
+                    Compatibility::Widen if self.widen_types => SchemaChangeKind::Widened,
+                    Compatibility::Widen | Compatibility::Incompatible => {
+                        SchemaChangeKind::Incompatible
+                    }
+                },
+            };
+            report.changes.push(SchemaChange {
+                column: column.clone(),
+                kind,
+                source_type: Some(source_type.clone()),
+                target_type: target_type.cloned(),
+            });
+        }
+        for (column, target_type) in target {
+            if !source.iter().any(|(name, _)| name == column) {
+                report.changes.push(SchemaChange {
+                    column: column.clone(),
+                    kind: SchemaChangeKind::Missing,
+                    source_type: None,
+                    target_type: Some(target_type.clone()),
+                });
+            }
+        }
+
+        if self.on_incompatible == IncompatibleChangePolicy::Fail
+            && report.incompatible().next().is_some()
+        {
+            return Err(Box::new(IncompatibleSchemaError { report }));
+        }
+        Ok(report)
+    }
+}
+
+enum Compatibility {
+    Same,
+    // The table column holds every source value as it is
+    Cast,
+    // The table column has to become wider to hold the source values
+    Widen,
+    Incompatible,
+}
+
+#[derive(PartialEq)]
+enum Family {
+    Integer(u8),
+    Float(u8),
+    Numeric,
+    Text,
+    Date,
+    Timestamp(u8),
+}
+
+fn family(data_type: &str) -> Option<Family> {
+    Some(match data_type {
+        "smallint" => Family::Integer(1),
+        "integer" => Family::Integer(2),
+        "bigint" => Family::Integer(3),
+        "real" => Family::Float(1),
+        "double precision" => Family::Float(2),
+        "text" | "character varying" => Family::Text,
+        "date" => Family::Date,
+        "timestamp without time zone" => Family::Timestamp(1),
+        "timestamp with time zone" => Family::Timestamp(2),
+        _ if data_type.starts_with("numeric") => Family::Numeric,
+        _ if data_type.starts_with("character varying(") => Family::Text,
+        _ => return None,
+    })
+}
+
+fn compare(source_type: &str, target_type: &str) -> Compatibility {
+    if source_type == target_type {
+        return Compatibility::Same;
+    }
+    let (Some(source), Some(target)) = (family(source_type), family(target_type)) else {
+        return Compatibility::Incompatible;
+    };
+    match (source, target) {
+        // Unbounded text holds the text form of anything
+        (_, Family::Text) if !target_type.contains('(') => Compatibility::Cast,
+        (Family::Text, Family::Text) => Compatibility::Widen,
+        (Family::Integer(source), Family::Integer(target))
+        | (Family::Float(source), Family::Float(target))
+        | (Family::Timestamp(source), Family::Timestamp(target)) => {
+            if source < target {
+                Compatibility::Cast
+            } else {
+                Compatibility::Widen
+            }
+        }
+        (Family::Integer(_), Family::Float(_) | Family::Numeric) => Compatibility::Cast,
+        (Family::Float(_) | Family::Numeric, Family::Integer(_)) => Compatibility::Widen,
+        (Family::Float(_), Family::Numeric) => Compatibility::Cast,
+        (Family::Numeric, Family::Float(_)) => Compatibility::Cast,
+        // Precision or scale differs, so only an unconstrained numeric holds both
+        (Family::Numeric, Family::Numeric) => {
+            if target_type == "numeric" {
+                Compatibility::Cast
+            } else {
+                Compatibility::Widen
+            }
+        }
+        (Family::Date, Family::Timestamp(_)) => Compatibility::Cast,
+        (Family::Timestamp(_), Family::Date) => Compatibility::Widen,
+        _ => Compatibility::Incompatible,
+    }
+}
+
+// Type a column is widened to so it holds both its own values and the source's
+fn widened_type(source_type: &str, target_type: Option<&str>) -> String {
+    let target_type = target_type.unwrap_or(source_type);
+    match (family(source_type), family(target_type)) {
+        (Some(Family::Text), _) => "text".to_string(),
+        (Some(Family::Numeric), Some(Family::Numeric | Family::Integer(_))) => {
+            "numeric".to_string()
+        }
+        _ => source_type.to_string(),
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Sheet of a workbook to load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExcelSheet {
+    Name(String),
+    // Position in the workbook, starting at 0
+    Index(usize),
+}
+
+// How an Excel workbook is read - sheets are GDAL layers, so LoadOptions::layer also picks one by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExcelOptions {
+    // None reads the first sheet, or LoadOptions::layer when set
+    pub sheet: Option<ExcelSheet>,
+    // Whether the first row read holds the column names
+    pub header: bool,
+    // Rows above the header or data, e.g. a title block - counted from the start of the range when one is set
+    pub skip_rows: usize,
+    // Cell range to read, e.g. 'B3:F200', or 'B3:F' to read to the last row - the header is its first row
+    pub range: Option<String>,
+    // Extra GDAL XLSX open options, e.g. 'FIELD_TYPES=STRING'
+    pub open_options: Vec<String>,
+}
+
+impl Default for ExcelOptions {
+    fn default() -> Self {
+        Self {
+            sheet: None,
+            header: true,
+            skip_rows: 0,
+            range: None,
+            open_options: Vec::new(),
+        }
+    }
+}
+
+impl ExcelOptions {
+    // Skipped rows and ranges are applied by reading the sheet without headers and as text, so the
+    // columns of such a read are VARCHAR unless retyped through ColumnMapping::type_overrides
+    pub(crate) fn reads_cells(&self) -> bool {
+        self.skip_rows > 0 || self.range.is_some()
+    }
+}
+
+// A parsed cell range, with 1-based rows and columns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CellRange {
+    pub(crate) first_column: usize,
+    pub(crate) last_column: usize,
+    pub(crate) first_row: usize,
+    pub(crate) last_row: Option<usize>,
+}
+
+impl CellRange {
+    pub(crate) fn parse(range: &str) -> Result<Self, Box<dyn Error>> {
+        let invalid = || format!("Invalid cell range '{}', expected e.g. 'A1:D100'", range);
+        let (start, end) = range.split_once(':').ok_or_else(invalid)?;
+        let (first_column, first_row) = parse_cell(start).ok_or_else(invalid)?;
+        let (last_column, last_row) = parse_cell(end).ok_or_else(invalid)?;
+        let first_row = first_row.ok_or_else(invalid)?;
+        if last_column < first_column || last_row.is_some_and(|last_row| last_row < first_row) {
+            return Err(invalid().into());
+        }
+        Ok(Self {
+            first_column,
+            last_column,
+            first_row,
+            last_row,
+        })
+    }
+}
+
+// Splits a cell reference such as 'AB12' into its column number and row, if any
+fn parse_cell(cell: &str) -> Option<(usize, Option<usize>)> {
+    let cell = cell.trim();
+    let letters = cell
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(cell.len());
+    if letters == 0 {
+        return None;
+    }
+    let column = cell[..letters].chars().try_fold(0usize, |column, c| {
+        column
+            .checked_mul(26)?
+            .checked_add((c.to_ascii_uppercase() as u8 - b'A' + 1) as usize)
+    })?;
+    let row = match &cell[letters..] {
+        "" => None,
+        digits => Some(digits.parse().ok().filter(|row| *row > 0)?),
+    };
+    Some((column, row))
+}
@@ -0,0 +1,127 @@
+use super::catalog::literal;
+use super::geometry::{self, GeometryEncoding};
+use super::postgis::qualified_table;
+use super::query_log::LoggedConnection;
+use super::report::{GeometryColumnType, LoadResult};
+use super::sink::Sink;
+use std::error::Error;
+
+// Reads a table from the attached PostGIS database into DuckDB and writes it to a file sink
+// Geometries are read as WKB so the table matches what a load hands to the sink
+pub(crate) fn export_postgis_table(
+    conn: &LoggedConnection,
+    database: &str,
+    schema: Option<&str>,
+    table_name: &str,
+    sink: &Sink,
+) -> Result<LoadResult, Box<dyn Error>> {
+    // Without a schema, the one Postgres finds unqualified tables in
+    let schema = match schema {
+        Some(schema) => schema.to_string(),
+        None => conn.query_row(
+            &format!(
+                "SELECT * FROM postgres_query('{}', 'SELECT current_schema()::text');",
+                database
+            ),
+            [],
+            |row| row.get(0),
+        )?,
+    };
+
+    // Geometry columns registered by PostGIS, with their declared type and SRID
+    let geometry_columns = format!(
+        "SELECT f_geometry_column::text, type::text, srid, coord_dimension FROM geometry_columns WHERE f_table_schema = {} AND f_table_name = {}",
+        literal(&schema),
+        literal(table_name)
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM postgres_query('{}', {});",
+        database,
+        literal(&geometry_columns)
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut column_types = Vec::new();
+    while let Some(row) = rows.next()? {
+        let column: String = row.get(0)?;
+        let postgis_type: String = row.get(1)?;
+        let srid: i32 = row.get(2)?;
+        let coord_dimension: i32 = row.get(3)?;
+        if srid == 0 {
+            return Err(format!("Geometry column {} has no SRID", column).into());
+        }
+        // PostGIS names M-only types with a trailing M, e.g. POINTM, and counts the dimensions separately
+        let has_m = postgis_type.ends_with('M');
+        let has_z = coord_dimension - 2 - i32::from(has_m) > 0;
+        let base_type = if has_m {
+            &postgis_type[..postgis_type.len() - 1]
+        } else {
+            &postgis_type
+        };
+        let (geometry_type, _) = geometry::resolve_column_type(&[base_type.to_string()], false);
+        column_types.push(GeometryColumnType {
+            column,
+            geometry_type,
+            srid: srid.to_string(),
+            promoted_to_multi: false,
+            has_z,
+            has_m,
+        });
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT column_name FROM information_schema.columns WHERE table_catalog = ? AND table_schema = ? AND table_name = ? ORDER BY ordinal_position",
+    )?;
+    let mut rows = stmt.query([database, schema.as_str(), table_name])?;
+    let mut expressions = Vec::new();
+    while let Some(row) = rows.next()? {
+        let column: String = row.get(0)?;
+        if !column_types
+            .iter()
+            .any(|column_type| column_type.column == column)
+        {
+            expressions.push(format!("\"{}\"", column));
+        }
+    }
+    if expressions.is_empty() && column_types.is_empty() {
+        return Err(format!("Table {} not found", table_name).into());
+    }
+    for column_type in &column_types {
+        expressions.push(format!(
+            "ST_AsBinary(\"{}\") AS \"{}_wkb\"",
+            column_type.column, column_type.column
+        ));
+    }
+
+    let postgres_query = format!(
+        "SELECT {} FROM {}",
+        expressions.join(", "),
+        qualified_table(Some(&schema), table_name)
+    );
+    conn.execute(
+        &format!(
+            "CREATE TABLE export_data AS SELECT * FROM postgres_query('{}', '{}');",
+            database,
+            postgres_query.replace('\'', "''")
+        ),
+        [],
+    )?;
+    sink.write_file(
+        conn,
+        "export_data",
+        table_name,
+        &column_types,
+        GeometryEncoding::Wkb,
+    )?;
+    conn.execute("DROP TABLE export_data;", [])?;
+
+    Ok(LoadResult {
+        table_name: table_name.to_string(),
+        geometry_columns: column_types
+            .iter()
+            .map(|column_type| column_type.column.clone())
+            .collect(),
+        geometry_column_types: column_types,
+        query_log: conn.log(),
+        ..Default::default()
+    })
+}
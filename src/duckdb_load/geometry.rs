@@ -0,0 +1,93 @@
+use super::catalog::identifier;
+
+// PostGIS type modifier for a DuckDB spatial geometry type name
+fn postgis_type_name(geometry_type: &str) -> Option<&'static str> {
+    match geometry_type.to_uppercase().as_str() {
+        "POINT" => Some("Point"),
+        "LINESTRING" => Some("LineString"),
+        "POLYGON" => Some("Polygon"),
+        "MULTIPOINT" => Some("MultiPoint"),
+        "MULTILINESTRING" => Some("MultiLineString"),
+        "MULTIPOLYGON" => Some("MultiPolygon"),
+        "GEOMETRYCOLLECTION" => Some("GeometryCollection"),
+        _ => None,
+    }
+}
+
+fn multi_of(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "Point" | "MultiPoint" => Some("MultiPoint"),
+        "LineString" | "MultiLineString" => Some("MultiLineString"),
+        "Polygon" | "MultiPolygon" => Some("MultiPolygon"),
+        _ => None,
+    }
+}
+
+// Picks the PostGIS geometry type for a column from the distinct types it contains
+// Returns the type name and whether values must be wrapped in ST_Multi to fit it
+pub(crate) fn resolve_column_type(
+    geometry_types: &[String],
+    promote_to_multi: bool,
+) -> (String, bool) {
+    let type_names: Option<Vec<&str>> = geometry_types
+        .iter()
+        .map(|geometry_type| postgis_type_name(geometry_type))
+        .collect();
+    let Some(mut type_names) = type_names else {
+        return ("Geometry".to_string(), false);
+    };
+    type_names.sort_unstable();
+    type_names.dedup();
+
+    match type_names.as_slice() {
+        [] => ("Geometry".to_string(), false),
+        [type_name] if promote_to_multi => match multi_of(type_name) {
+            Some(multi) => (multi.to_string(), *type_name != multi),
+            None => (type_name.to_string(), false),
+        },
+        [type_name] => (type_name.to_string(), false),
+        [first, rest @ ..] if promote_to_multi => {
+            // Single and multi variants of the same family share a multi type
+            match multi_of(first) {
+                Some(multi)
+                    if rest
+                        .iter()
+                        .all(|type_name| multi_of(type_name) == Some(multi)) =>
+                {
+                    (multi.to_string(), true)
+                }
+                _ => ("Geometry".to_string(), false),
+            }
+        }
+        _ => ("Geometry".to_string(), false),
+    }
+}
+
+// How geometry columns are held in the transformed table
+// The PostGIS transfer and file exports need WKB, while DuckDB and Parquet sinks take DuckDB geometries as they are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum GeometryEncoding {
+    // A BLOB column named {column}_wkb in place of the geometry
+    Wkb,
+    // The GEOMETRY column under its own name
+    Native,
+}
+
+impl GeometryEncoding {
+    // Name of the column holding a geometry column's values, unquoted
+    pub(crate) fn stored_column(&self, column: &str) -> String {
+        match self {
+            GeometryEncoding::Wkb => format!("{}_wkb", column),
+            GeometryEncoding::Native => column.to_string(),
+        }
+    }
+
+    // Expression reading a geometry column as a DuckDB geometry
+    pub(crate) fn geometry(&self, column: &str) -> String {
+        let stored = identifier(&self.stored_column(column));
+        match self {
+            GeometryEncoding::Wkb => format!("ST_GeomFromWKB({})", stored),
+            GeometryEncoding::Native => stored,
+        }
+    }
+}
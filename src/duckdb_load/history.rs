@@ -0,0 +1,286 @@
+use super::catalog::{literal, text_array};
+use super::options::LoadOptions;
+use super::query_log::LoggedConnection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+const HISTORY_TABLE: &str = "CREATE TABLE IF NOT EXISTS gridwalk_load_history (
+    table_schema text NOT NULL,
+    table_name text NOT NULL,
+    fingerprint text NOT NULL,
+    source_files text[] NOT NULL,
+    row_count bigint NOT NULL,
+    loaded_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS gridwalk_load_history_table ON gridwalk_load_history (table_schema, table_name, loaded_at);";
+
+// Files a Shapefile is read from alongside the .shp
+const SHAPEFILE_SIDECARS: [&str; 4] = ["shx", "dbf", "prj", "cpg"];
+
+// BLAKE3 hash of the source files' contents and of the options that shape the loaded table, as hex
+// Renaming or moving a file keeps its fingerprint, while changing e.g. the target CRS changes it
+pub(crate) fn fingerprint(
+    file_paths: &[String],
+    options: &LoadOptions,
+) -> Result<String, Box<dyn Error>> {
+    let mut hasher = blake3::Hasher::new();
+    for file_path in file_paths {
+        let path = Path::new(file_path);
+        hash_file(&mut hasher, path)?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("shp"))
+        {
+            for extension in SHAPEFILE_SIDECARS {
+                let sidecar = path.with_extension(extension);
+                if sidecar.exists() {
+                    hash_file(&mut hasher, &sidecar)?;
+                }
+            }
+        }
+    }
+
+    // The skip settings themselves don't change what is loaded
+    // Going through a Value sorts the keys of maps such as the dictionary's descriptions
+    let mut options = options.clone();
+    options.skip_unchanged = false;
+    options.force = false;
+    hasher.update(serde_json::to_value(&options)?.to_string().as_bytes());
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn hash_file(hasher: &mut blake3::Hasher, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut file = File::open(path)
+        .map_err(|e| format!("Failed to read {} to fingerprint it: {}", path.display(), e))?;
+    // Each file is prefixed with its length so the boundaries between files are part of the hash
+    hasher.update(&file.metadata()?.len().to_le_bytes());
+    io::copy(&mut file, hasher)?;
+    Ok(())
+}
+
+// Fingerprint of the latest load of a table, provided the table is still there
+pub(crate) fn latest_fingerprint(
+    conn: &LoggedConnection,
+    database: &str,
+    schema: Option<&str>,
+    table_name: &str,
+    qualified_table: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let exists: bool = conn.query_row(
+        &format!(
+            "SELECT * FROM postgres_query('{}', 'SELECT to_regclass(''gridwalk_load_history'') IS NOT NULL');",
+            database
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(None);
+    }
+
+    let sql = format!(
+        "SELECT fingerprint FROM gridwalk_load_history
+        WHERE table_schema = {} AND table_name = {} AND to_regclass({}) IS NOT NULL
+        ORDER BY loaded_at DESC LIMIT 1",
+        schema.map_or("current_schema()".to_string(), literal),
+        literal(table_name),
+        literal(qualified_table)
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM postgres_query('{}', '{}');",
+        database,
+        sql.replace('\'', "''")
+    ))?;
+    let mut rows = stmt.query([])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+// Postgres statements recording a completed load, with the row count read from the loaded table
+pub(crate) fn history_statements(
+    schema: Option<&str>,
+    table_name: &str,
+    qualified_table: &str,
+    fingerprint: &str,
+    source_files: &[String],
+) -> String {
+    format!(
+        "{}
+        INSERT INTO gridwalk_load_history (table_schema, table_name, fingerprint, source_files, row_count)
+        SELECT {}, {}, {}, {}, count(*) FROM {};",
+        HISTORY_TABLE,
+        schema.map_or("current_schema()".to_string(), literal),
+        literal(table_name),
+        literal(fingerprint),
+        text_array(source_files),
+        qualified_table
+    )
+}
+
+// A load as recorded in the gridwalk_loads table by LoadOptions::write_load_history
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct LoadHistoryEntry {
+    pub table_schema: String,
+    pub table_name: String,
+    pub source_files: Vec<String>,
+    pub file_type: String,
+    pub status: LoadStatus,
+    // Rows written - None unless the load succeeded
+    pub row_count: Option<u64>,
+    pub duration: Duration,
+    // SRID of the first geometry column - None for tables without geometry and loads that failed
+    pub srid: Option<String>,
+    pub tags: Vec<String>,
+    pub error: Option<String>,
+    // Timestamp the load finished, as Postgres formats timestamptz
+    pub loaded_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LoadStatus {
+    #[default]
+    Succeeded,
+    // Skipped by LoadOptions::skip_unchanged
+    Skipped,
+    Cancelled,
+    Failed,
+}
+
+impl LoadStatus {
+    fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "Succeeded" => Ok(LoadStatus::Succeeded),
+            "Skipped" => Ok(LoadStatus::Skipped),
+            "Cancelled" => Ok(LoadStatus::Cancelled),
+            "Failed" => Ok(LoadStatus::Failed),
+            other => Err(format!("Unknown load status '{}' in gridwalk_loads", other).into()),
+        }
+    }
+}
+
+const LOADS_TABLE: &str = "CREATE TABLE IF NOT EXISTS gridwalk_loads (
+    id bigserial PRIMARY KEY,
+    table_schema text NOT NULL,
+    table_name text NOT NULL,
+    source_files text[] NOT NULL,
+    file_type text NOT NULL,
+    status text NOT NULL,
+    row_count bigint,
+    duration_ms bigint NOT NULL,
+    srid integer,
+    tags text[] NOT NULL DEFAULT '{}',
+    error text,
+    loaded_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS gridwalk_loads_table ON gridwalk_loads (table_schema, table_name, loaded_at);";
+
+// What a load records about itself, whether or not it succeeded
+pub(crate) struct LoadRecord<'a> {
+    pub(crate) schema: Option<&'a str>,
+    pub(crate) table_name: &'a str,
+    pub(crate) source_files: &'a [String],
+    pub(crate) file_type: String,
+    pub(crate) status: LoadStatus,
+    pub(crate) row_count: Option<u64>,
+    pub(crate) duration: Duration,
+    pub(crate) srid: Option<&'a str>,
+    pub(crate) tags: &'a [String],
+    pub(crate) error: Option<String>,
+}
+
+pub(crate) fn load_statements(record: &LoadRecord) -> String {
+    format!(
+        "{}
+        INSERT INTO gridwalk_loads (table_schema, table_name, source_files, file_type, status, row_count,
+            duration_ms, srid, tags, error)
+        VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+        LOADS_TABLE,
+        record
+            .schema
+            .map_or("current_schema()".to_string(), literal),
+        literal(record.table_name),
+        text_array(record.source_files),
+        literal(&record.file_type),
+        literal(&format!("{:?}", record.status)),
+        record
+            .row_count
+            .map_or("NULL".to_string(), |count| count.to_string()),
+        record.duration.as_millis(),
+        record.srid.unwrap_or("NULL"),
+        text_array(record.tags),
+        record.error.as_deref().map_or("NULL".to_string(), literal)
+    )
+}
+
+// Loads of a table in a schema, or in the current schema when None, newest first
+pub(crate) fn list_loads(
+    conn: &LoggedConnection,
+    database: &str,
+    schema: Option<&str>,
+    table_name: &str,
+) -> Result<Vec<LoadHistoryEntry>, Box<dyn Error>> {
+    let exists: bool = conn.query_row(
+        &format!(
+            "SELECT * FROM postgres_query('{}', 'SELECT to_regclass(''gridwalk_loads'') IS NOT NULL');",
+            database
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    // Arrays come back as JSON text, which postgres_query passes through unchanged
+    let sql = format!(
+        "SELECT table_schema, table_name, array_to_json(source_files)::text, file_type, status, row_count,
+            duration_ms, srid::text, array_to_json(tags)::text, error, loaded_at::text
+        FROM gridwalk_loads
+        WHERE table_schema = {} AND table_name = {}
+        ORDER BY loaded_at DESC, id DESC",
+        schema.map_or("current_schema()".to_string(), literal),
+        literal(table_name)
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM postgres_query('{}', '{}');",
+        database,
+        sql.replace('\'', "''")
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                LoadHistoryEntry {
+                    table_schema: row.get(0)?,
+                    table_name: row.get(1)?,
+                    file_type: row.get(3)?,
+                    row_count: row.get::<_, Option<i64>>(5)?.map(|count| count as u64),
+                    duration: Duration::from_millis(row.get::<_, i64>(6)? as u64),
+                    srid: row.get(7)?,
+                    error: row.get(9)?,
+                    loaded_at: row.get(10)?,
+                    ..Default::default()
+                },
+                [
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(8)?,
+                ],
+            ))
+        })?
+        .collect::<Result<Vec<(LoadHistoryEntry, [String; 3])>, _>>()?;
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for (mut entry, [source_files, status, tags]) in rows {
+        entry.source_files = serde_json::from_str(&source_files)?;
+        entry.status = LoadStatus::from_name(&status)?;
+        entry.tags = serde_json::from_str(&tags)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,55 @@
+use super::profile::ProfileReport;
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Summary of a source file as a load would read it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileInfo {
+    pub file_type: String,
+    pub row_count: u64,
+    // (name, DuckDB type) pairs
+    pub columns: Vec<(String, String)>,
+    pub geometry_columns: Vec<String>,
+    // Distinct geometry types across all geometry columns
+    pub geometry_types: Vec<String>,
+    // EPSG code of the source - None when there are no geometry columns
+    pub crs: Option<String>,
+    // (file path, encoding) of CSV and Shapefile sources, e.g. 'windows-1252'
+    pub source_encodings: Vec<(String, String)>,
+    // Set when LoadOptions::profile is enabled
+    pub profile: Option<ProfileReport>,
+}
+
+// A layer within a GDAL-readable source, e.g. a table in a GeoPackage or a sheet in a workbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerInfo {
+    pub name: String,
+    pub feature_count: i64,
+    // GDAL name of the first geometry field's type, e.g. 'Multi Polygon'
+    pub geometry_type: Option<String>,
+    pub crs: Option<String>,
+}
+
+pub(crate) fn read_layers(
+    conn: &Connection,
+    file_path: &str,
+) -> Result<Vec<LayerInfo>, Box<dyn Error>> {
+    let query = format!(
+        "SELECT layer.name, layer.feature_count, layer.geometry_fields[1].type, layer.geometry_fields[1].crs.auth_code
+        FROM (SELECT unnest(layers) AS layer FROM st_read_meta('{}'));",
+        file_path
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let mut rows = stmt.query([])?;
+    let mut layers = Vec::new();
+    while let Some(row) = rows.next()? {
+        layers.push(LayerInfo {
+            name: row.get(0)?,
+            feature_count: row.get(1)?,
+            geometry_type: row.get(2)?,
+            crs: row.get(3)?,
+        });
+    }
+    Ok(layers)
+}
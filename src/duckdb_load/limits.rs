@@ -0,0 +1,68 @@
+use super::query_log::LoggedConnection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Resources the embedded DuckDB database may use, set on the connection before a load starts
+// The settings apply to the whole database, so loads sharing a LoaderContext should use the same limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuckDbLimits {
+    // e.g. '4GB' - None leaves DuckDB's default of 80% of physical memory
+    pub memory_limit: Option<String>,
+    // None uses one thread per core
+    pub threads: Option<usize>,
+    // Where data beyond memory_limit is spilled - None disables spilling, so such loads fail instead
+    pub temp_directory: Option<String>,
+    // e.g. '50GB' - None lets spilled data fill the disk holding temp_directory
+    pub max_temp_directory_size: Option<String>,
+}
+
+impl Default for DuckDbLimits {
+    fn default() -> Self {
+        Self {
+            memory_limit: None,
+            threads: None,
+            // DuckDB would otherwise spill to .tmp in the working directory
+            temp_directory: Some(
+                std::env::temp_dir()
+                    .join("gridwalk_spill")
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            max_temp_directory_size: None,
+        }
+    }
+}
+
+impl DuckDbLimits {
+    pub(crate) fn apply(&self, conn: &LoggedConnection) -> Result<(), Box<dyn Error>> {
+        let quote = |value: &str| format!("'{}'", value.replace('\'', "''"));
+        let mut settings = Vec::new();
+        if let Some(memory_limit) = &self.memory_limit {
+            settings.push(("memory_limit", quote(memory_limit)));
+        }
+        if let Some(threads) = self.threads {
+            settings.push(("threads", threads.max(1).to_string()));
+        }
+        // DuckDB refuses to change the directory once anything has been spilled, even to the same path,
+        // so it is only set when it differs
+        let temp_directory = self.temp_directory.as_deref().unwrap_or("");
+        let current: String = conn.query_row(
+            "SELECT current_setting('temp_directory')::VARCHAR;",
+            [],
+            |row| row.get(0),
+        )?;
+        if current != temp_directory {
+            settings.push(("temp_directory", quote(temp_directory)));
+        }
+        if let Some(max_size) = &self.max_temp_directory_size {
+            settings.push(("max_temp_directory_size", quote(max_size)));
+        }
+
+        for (name, value) in settings {
+            conn.execute(&format!("SET {} = {};", name, value), [])
+                .map_err(|e| format!("Invalid DuckDB {} {}: {}", name, value, e))?;
+        }
+        Ok(())
+    }
+}
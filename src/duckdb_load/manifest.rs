@@ -0,0 +1,537 @@
+use super::{FileLoadOutcome, LoadMode, LoadOptions, LoaderContext};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// One file of a batch and where it is loaded - anything left as None comes from BatchOptions::load_options
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ManifestEntry {
+    // Relative paths are resolved against the manifest's directory
+    pub file: String,
+    pub table: String,
+    pub schema: Option<String>,
+    // EPSG code geometries are transformed to
+    #[serde(deserialize_with = "string_or_number")]
+    pub srid: Option<String>,
+    // EPSG code of the source, for files without CRS metadata
+    #[serde(deserialize_with = "string_or_number")]
+    pub source_srid: Option<String>,
+    // 'replace' or 'append'
+    pub mode: Option<String>,
+    pub layer: Option<String>,
+}
+
+// Files to load as one batch, read from a JSON, YAML or CSV manifest
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    // Directory relative entry paths are resolved against
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+// Options for loading a manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchOptions {
+    // Keep loading the remaining entries after one fails
+    pub continue_on_error: bool,
+    // Options applied to every entry, before the entry's own settings
+    pub load_options: LoadOptions,
+}
+
+// Outcome of every entry of a batch, in manifest order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub outcomes: Vec<FileLoadOutcome>,
+    // Entries not attempted because an earlier one failed without continue_on_error
+    pub skipped: Vec<ManifestEntry>,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.succeeded()
+    }
+}
+
+impl ManifestEntry {
+    fn load_mode(&self) -> Result<Option<LoadMode>, Box<dyn Error>> {
+        match self.mode.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            None => Ok(None),
+            Some("replace") => Ok(Some(LoadMode::Replace)),
+            Some("append") => Ok(Some(LoadMode::Append)),
+            Some(mode) => Err(format!(
+                "Unknown mode '{}' for '{}' - expected replace or append",
+                mode, self.file
+            )
+            .into()),
+        }
+    }
+
+    fn load_options(&self, defaults: &LoadOptions) -> Result<LoadOptions, Box<dyn Error>> {
+        let mut options = defaults.clone();
+        if let Some(schema) = &self.schema {
+            options.schema = Some(schema.clone());
+        }
+        if let Some(srid) = &self.srid {
+            options.target_crs = srid.clone();
+        }
+        if let Some(source_srid) = &self.source_srid {
+            options.source_crs = Some(source_srid.clone());
+        }
+        if let Some(mode) = self.load_mode()? {
+            options.load_mode = mode;
+        }
+        if let Some(layer) = &self.layer {
+            options.layer = Some(layer.clone());
+        }
+        Ok(options)
+    }
+}
+
+impl Manifest {
+    // The format follows the extension - .json holds an array of entries, .yaml/.yml a list of mappings
+    // and .csv a header row naming the entry fields
+    pub fn read(path: &str) -> Result<Manifest, io::Error> {
+        Self::parse(path)
+            .map_err(|e| io::Error::other(format!("Invalid manifest '{}': {}", path, e)))
+    }
+
+    fn parse(path: &str) -> Result<Manifest, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let extension = Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let entries: Vec<ManifestEntry> = match extension.as_deref() {
+            Some("json") => serde_json::from_str(&text)?,
+            Some("yaml") | Some("yml") => entries_from_rows(yaml_rows(&text)?)?,
+            Some("csv") => entries_from_rows(csv_rows(&text)?)?,
+            _ => return Err("expected a .json, .yaml, .yml or .csv file".into()),
+        };
+
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.file.is_empty() || entry.table.is_empty() {
+                return Err(format!("entry {} needs both a file and a table", i + 1).into());
+            }
+            entry.load_mode()?;
+        }
+        let base_dir = Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(Manifest { entries, base_dir })
+    }
+
+    fn file_path(&self, entry: &ManifestEntry) -> PathBuf {
+        let path = Path::new(&entry.file);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.base_dir.join(path)
+        }
+    }
+}
+
+pub fn load_manifest(path: &str, options: &BatchOptions) -> Result<BatchReport, io::Error> {
+    let manifest = Manifest::read(path)?;
+    load_batch(&manifest, options)
+}
+
+// Entries are loaded one after another through one DuckDB database, so later entries can append to
+// tables created by earlier ones
+pub fn load_batch(manifest: &Manifest, options: &BatchOptions) -> Result<BatchReport, io::Error> {
+    let context = LoaderContext::new()?;
+    let mut report = BatchReport {
+        outcomes: Vec::with_capacity(manifest.entries.len()),
+        skipped: Vec::new(),
+    };
+
+    for (i, entry) in manifest.entries.iter().enumerate() {
+        let file_path = manifest.file_path(entry);
+        println!(
+            "Loading {} of {}: {} into {}",
+            i + 1,
+            manifest.entries.len(),
+            file_path.display(),
+            entry.table
+        );
+        let result = entry
+            .load_options(&options.load_options)
+            .map_err(|e| e.to_string())
+            .and_then(|load_options| {
+                context
+                    .load_file(&file_path.to_string_lossy(), &entry.table, &load_options)
+                    .map_err(|e| e.to_string())
+            });
+        let failed = result.is_err();
+        report.outcomes.push(FileLoadOutcome {
+            file_path,
+            table_name: entry.table.clone(),
+            result,
+        });
+
+        if failed && !options.continue_on_error {
+            report.skipped = manifest.entries[i + 1..].to_vec();
+            break;
+        }
+    }
+    Ok(report)
+}
+
+// Line a row of a YAML or CSV manifest starts on, with its field names and values
+type Row = (usize, Vec<(String, String)>);
+type Record = (usize, Vec<String>);
+
+// SRIDs are often written as bare numbers in JSON manifests
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(Value::Number(value)) => Ok(Some(value.to_string())),
+        Some(value) => Err(serde::de::Error::custom(format!(
+            "expected a string or number, found {}",
+            value
+        ))),
+    }
+}
+
+// Builds entries from field name and value pairs, each row tagged with the line it starts on
+fn entries_from_rows(rows: Vec<Row>) -> Result<Vec<ManifestEntry>, Box<dyn Error>> {
+    rows.into_iter()
+        .map(|(line, fields)| -> Result<ManifestEntry, Box<dyn Error>> {
+            let mut object = Map::new();
+            for (key, value) in fields {
+                if object.insert(key.clone(), Value::String(value)).is_some() {
+                    return Err(format!("line {}: duplicate field '{}'", line, key).into());
+                }
+            }
+            serde_json::from_value(Value::Object(object))
+                .map_err(|e| format!("line {}: {}", line, e).into())
+        })
+        .collect()
+}
+
+// The subset of YAML a manifest needs - a list of flat mappings with plain or quoted scalar values
+// Nested mappings, flow collections such as [a, b], block scalars, anchors and tags are rejected rather
+// than read as text, as are 'key:value' pairs without a space, which YAML reads as a single string
+fn yaml_rows(text: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    let mut rows: Vec<Row> = Vec::new();
+    // Column the keys of the current row start at, once its first key is seen
+    let mut key_column = None;
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_yaml_comment(raw_line).trim_end();
+        if line.trim().is_empty() || line == "---" {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+
+        let (field, column) = if let Some(rest) = line.trim_start().strip_prefix('-') {
+            if !(rest.is_empty() || rest.starts_with(' ')) {
+                return Err(format!("line {}: expected '- key: value'", line_number).into());
+            }
+            if key_column.is_some_and(|column| indent >= column) {
+                return Err(format!("line {}: nested lists are not supported", line_number).into());
+            }
+            rows.push((line_number, Vec::new()));
+            key_column = None;
+            (
+                rest.trim(),
+                indent + 1 + rest.len() - rest.trim_start().len(),
+            )
+        } else if indent > 0 && !rows.is_empty() {
+            (line.trim(), indent)
+        } else {
+            return Err(format!("line {}: expected a list of mappings", line_number).into());
+        };
+        if field.is_empty() {
+            continue;
+        }
+        match key_column {
+            None => key_column = Some(column),
+            Some(key_column) if column > key_column => {
+                return Err(
+                    format!("line {}: nested mappings are not supported", line_number).into(),
+                )
+            }
+            Some(key_column) if column < key_column => {
+                return Err(format!("line {}: unexpected indentation", line_number).into())
+            }
+            _ => {}
+        }
+
+        if field.starts_with(['{', '[']) {
+            return Err(format!("line {}: flow mappings are not supported", line_number).into());
+        }
+        let Some((key, value)) = field
+            .split_once(": ")
+            .or_else(|| field.strip_suffix(':').map(|key| (key, "")))
+        else {
+            return Err(format!("line {}: expected 'key: value'", line_number).into());
+        };
+        let value =
+            yaml_scalar(value.trim()).map_err(|e| format!("line {}: {}", line_number, e))?;
+        if let Some(value) = value {
+            let (_, fields) = rows.last_mut().expect("a row was started");
+            fields.push((key.trim().to_string(), value));
+        }
+    }
+    Ok(rows)
+}
+
+// A # starts a comment at the start of a line or after whitespace, outside quotes
+fn strip_yaml_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return &line[..i],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+// None for null values, which leave the field unset
+fn yaml_scalar(value: &str) -> Result<Option<String>, String> {
+    if value.is_empty() || value == "~" || value == "null" {
+        return Ok(None);
+    }
+    if value.starts_with(['[', '{', '|', '>', '&', '*', '!', '%', '@', '`']) {
+        return Err(format!(
+            "'{}' is not a plain or quoted value - flow collections, block scalars, anchors and tags are not supported",
+            value
+        ));
+    }
+    if let Some(inner) = value.strip_prefix('\'') {
+        let inner = inner
+            .strip_suffix('\'')
+            .ok_or("unterminated quoted value")?;
+        return Ok(Some(inner.replace("''", "'")));
+    }
+    if let Some(inner) = value.strip_prefix('"') {
+        let inner = inner.strip_suffix('"').ok_or("unterminated quoted value")?;
+        let mut unescaped = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(escaped @ ('"' | '\\')) => unescaped.push(escaped),
+                _ => return Err("unsupported escape in quoted value".to_string()),
+            }
+        }
+        return Ok(Some(unescaped));
+    }
+    Ok(Some(value.to_string()))
+}
+
+// Rows of a CSV manifest keyed by the header, skipping empty values so they stay unset
+fn csv_rows(text: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    let mut records = csv_records(text)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    records
+        .filter(|(_, record)| record.iter().any(|value| !value.trim().is_empty()))
+        .map(|(line, record)| {
+            if record.len() != header.len() {
+                return Err(format!(
+                    "line {}: {} values for {} columns",
+                    line,
+                    record.len(),
+                    header.len()
+                )
+                .into());
+            }
+            let fields = header
+                .iter()
+                .zip(record)
+                .filter(|(_, value)| !value.trim().is_empty())
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect();
+            Ok((line, fields))
+        })
+        .collect()
+}
+
+// Comma separated records with double-quoted values, which may contain commas, quotes as "" and newlines
+fn csv_records(text: &str) -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                value.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if value.trim().is_empty() => {
+                value.clear();
+                quoted = true;
+            }
+            (false, ',') => record.push(std::mem::take(&mut value)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut value));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            (_, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                value.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("line {}: unterminated quoted value", record_line).into());
+    }
+    if !value.is_empty() || !record.is_empty() {
+        record.push(value);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(rows: &[Row]) -> Vec<Vec<(&str, &str)>> {
+        rows.iter()
+            .map(|(_, fields)| {
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn yaml_error(text: &str) -> String {
+        yaml_rows(text).expect_err("rejected").to_string()
+    }
+
+    #[test]
+    fn yaml_reads_a_list_of_flat_mappings() {
+        let text = "---\n# boundaries\n- file: wards.gpkg\n  table: wards # replaced nightly\n  srid: 27700\n\n-\n  file: 'it''s.csv'\n  table: \"a \\\"b\\\"\"\n  layer: ~\n";
+        let rows = yaml_rows(text).unwrap();
+        assert_eq!(
+            fields(&rows),
+            vec![
+                vec![
+                    ("file", "wards.gpkg"),
+                    ("table", "wards"),
+                    ("srid", "27700")
+                ],
+                vec![("file", "it's.csv"), ("table", "a \"b\"")],
+            ]
+        );
+        assert_eq!(rows[1].0, 7);
+    }
+
+    #[test]
+    fn yaml_keeps_hashes_and_colons_inside_values() {
+        let rows = yaml_rows("- file: 'a #1.csv'\n  table: t#1\n  layer: 'x: y'\n").unwrap();
+        assert_eq!(
+            fields(&rows),
+            vec![vec![
+                ("file", "a #1.csv"),
+                ("table", "t#1"),
+                ("layer", "x: y")
+            ]]
+        );
+    }
+
+    #[test]
+    fn yaml_rejects_forms_outside_the_subset() {
+        assert!(yaml_error("- file:a.csv\n").contains("expected 'key: value'"));
+        assert!(yaml_error("- file: a.csv\n  options:\n    srid: 1\n").contains("nested mappings"));
+        assert!(yaml_error("- file: a.csv\n  tags:\n    - x\n").contains("nested lists"));
+        assert!(yaml_error("- {file: a.csv, table: a}\n").contains("flow mappings"));
+        assert!(yaml_error("- file: a.csv\n  tags: [a, b]\n").contains("flow collections"));
+        assert!(yaml_error("- file: |\n    a.csv\n").contains("block scalars"));
+        assert!(yaml_error("- file: *path\n").contains("anchors"));
+        assert!(yaml_error("file: a.csv\n").contains("expected a list of mappings"));
+        assert!(yaml_error("-file: a.csv\n").contains("expected '- key: value'"));
+        assert!(yaml_error("- file: 'a.csv\n").contains("unterminated"));
+        assert!(yaml_error("- file: \"a\\x.csv\"\n").contains("unsupported escape"));
+        assert!(yaml_error("-   file: a.csv\n  table: a\n").contains("unexpected indentation"));
+    }
+
+    #[test]
+    fn rows_with_unknown_or_duplicate_fields_are_rejected() {
+        let rows = yaml_rows("- file: a.csv\n  table: a\n  file: b.csv\n").unwrap();
+        assert!(entries_from_rows(rows)
+            .unwrap_err()
+            .to_string()
+            .contains("duplicate field 'file'"));
+        let rows = yaml_rows("- file: a.csv\n  table: a\n  colour: red\n").unwrap();
+        assert!(entries_from_rows(rows)
+            .unwrap_err()
+            .to_string()
+            .starts_with("line 1:"));
+    }
+
+    #[test]
+    fn csv_reads_quoted_values() {
+        let text = "\u{feff}file,table,layer\r\n\"a, b.csv\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n,,\nc.csv, c ,\n";
+        let rows = csv_rows(text).unwrap();
+        assert_eq!(
+            fields(&rows),
+            vec![
+                vec![
+                    ("file", "a, b.csv"),
+                    ("table", "say \"hi\""),
+                    ("layer", "two\nlines")
+                ],
+                vec![("file", "c.csv"), ("table", "c")],
+            ]
+        );
+        assert_eq!(rows[1].0, 5);
+    }
+
+    #[test]
+    fn csv_rejects_malformed_records() {
+        let error = csv_rows("file,table\na.csv\n").unwrap_err().to_string();
+        assert_eq!(error, "line 2: 1 values for 2 columns");
+        let error = csv_rows("file,table\n\"a.csv,a\n").unwrap_err().to_string();
+        assert_eq!(error, "line 2: unterminated quoted value");
+    }
+
+    #[test]
+    fn empty_manifests_have_no_rows() {
+        assert!(yaml_rows("---\n# nothing yet\n").unwrap().is_empty());
+        assert!(csv_rows("").unwrap().is_empty());
+        assert!(csv_rows("file,table\n").unwrap().is_empty());
+    }
+}
@@ -1,313 +1,3591 @@
+mod cancellation;
+mod catalog;
+mod charset;
+mod context;
+mod contract;
+mod detect;
+mod dictionary;
+mod directory;
+mod drift;
+mod evolution;
+mod excel;
+mod export;
+mod geometry;
+mod history;
+mod inspect;
+mod limits;
+mod manifest;
+mod naming;
+mod options;
+mod osm;
+mod points;
+mod postgis;
+mod priority;
+mod profile;
+mod query_log;
+mod readers;
+mod report;
+mod resources;
+mod retry;
+mod round_trip;
+mod schema;
+mod sink;
+mod source;
+mod staging;
+mod styles;
+mod templates;
+mod units;
+mod validation;
+
+pub use cancellation::{CancellationToken, LoadCancelledError};
+pub use catalog::{CatalogEntry, CatalogQuery};
+pub use context::LoaderContext;
+pub use contract::{ContractColumn, ContractViolationError, SchemaContract};
+pub use dictionary::{DataDictionary, DataDictionaryOptions, DictionaryEntry, DictionaryFormat};
+pub use directory::{load_directory, DirectoryOptions, FileLoadOutcome};
+pub use evolution::{
+    IncompatibleChangePolicy, IncompatibleSchemaError, SchemaChange, SchemaChangeKind,
+    SchemaEvolution, SchemaEvolutionReport,
+};
+pub use excel::{ExcelOptions, ExcelSheet};
+pub use history::{LoadHistoryEntry, LoadStatus};
+pub use inspect::{FileInfo, LayerInfo};
+pub use limits::DuckDbLimits;
+pub use manifest::{load_batch, load_manifest, BatchOptions, BatchReport, Manifest, ManifestEntry};
+pub use naming::{IdentifierCase, NameMapping, NameNormalization};
+pub use options::{
+    AggregateFunction, Aggregation, ClipArea, ColumnMapping, CompanionTables, CrsMismatchPolicy,
+    DriftPolicy, DriftThresholds, GeometrySimplification, GeometryValidationPolicy, LoadMode,
+    LoadOptions, LoadPriority, NonFinitePolicy, ParallelTransfer, PrimaryKey, Resample,
+    SpatialFilter, TransferEngine, TransferVerification, VerificationPolicy,
+};
+pub use points::PointColumns;
+pub use postgis::PostGisSink;
+pub use profile::{ColumnProfile, ProfileOptions, ProfileReport};
+pub use query_log::{QueryEngine, QueryLogEntry};
+pub use readers::{CsvReaderOptions, ParquetReaderOptions, ReaderOptions, SpatialReaderOptions};
+pub use report::{
+    DriftReport, Extent, GeometryColumnType, GeometryStatistics, GeometryValidationReport,
+    LayerExtent, LineageEntry, LoadResult, ReplicaResult, VerificationReport,
+};
+pub use resources::ResourceUsage;
+pub use retry::{RetryPolicy, RetryStage};
+pub use round_trip::{ColumnMismatch, RoundTripOptions, RoundTripReport};
+pub use schema::{VersionedJson, SCHEMA_VERSION};
+pub use sink::{
+    CustomSink, DuckDbSink, ExportFormat, ExportSink, ParquetCompression, ParquetSink, Sink,
+    SinkContext, SinkReport, SinkWriter,
+};
+pub use source::{Confidence, DetectedSource, SourceHandler, SourceRegistry};
+pub use templates::SqlTemplates;
+pub use units::{Unit, UnitConversion};
+pub use validation::{
+    IssueCode, IssueSeverity, ValidationCheck, ValidationIssue, ValidationReport,
+};
+
+use cancellation::InterruptRegistration;
+use charset::SourceEncoding;
 use duckdb::arrow::datatypes::Schema;
 use duckdb::Connection;
+use excel::CellRange;
+use geometry::GeometryEncoding;
+use priority::PriorityGate;
+use query_log::LoggedConnection;
+use resources::ResourceSampler;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Read};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 // Enum that represents potential FileTypes
 // More will be added in the future
-#[derive(Debug, PartialEq)]
-enum FileType {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FileType {
     Geopackage,
     Shapefile,
     Geojson,
     Excel,
     Csv,
     Parquet,
+    // A table in a DuckDB database file, chosen with LoadOptions::layer
+    DuckDb,
+    // Arrow IPC file, also known as Feather v2
+    Arrow,
+    // GPS exchange format, read through GDAL's GPX driver - LoadOptions::layer picks tracks, routes,
+    // waypoints, track_points or route_points
+    Gpx,
+    // OpenStreetMap extract in protocol buffer format - LoadOptions::layer picks nodes, ways or relations
+    OsmPbf,
+    // A format read by a SourceHandler in LoadOptions::sources
+    Registered,
+}
+
+impl FileType {
+    // Every type with a built-in detector and reader, in the order detection ranks equal matches
+    pub const BUILT_IN: [FileType; 10] = [
+        FileType::Excel,
+        FileType::Geopackage,
+        FileType::Shapefile,
+        FileType::Parquet,
+        FileType::DuckDb,
+        FileType::Arrow,
+        FileType::OsmPbf,
+        FileType::Geojson,
+        FileType::Gpx,
+        FileType::Csv,
+    ];
+
+    // How sure this type's detector is about the start of a file - None for Registered
+    pub fn detect(&self, prefix: &[u8]) -> Option<Confidence> {
+        detect::detect(
+            *self,
+            &prefix[..prefix.len().min(detect::DETECTION_PREFIX_BYTES)],
+        )
+    }
+
+    pub(crate) fn from_extension(file_path: &str) -> Option<FileType> {
+        let extension = Path::new(file_path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "gpkg" => Some(FileType::Geopackage),
+            "shp" => Some(FileType::Shapefile),
+            "geojson" | "json" => Some(FileType::Geojson),
+            "xlsx" => Some(FileType::Excel),
+            "csv" => Some(FileType::Csv),
+            "parquet" => Some(FileType::Parquet),
+            "duckdb" => Some(FileType::DuckDb),
+            "arrow" | "feather" | "ipc" => Some(FileType::Arrow),
+            "gpx" => Some(FileType::Gpx),
+            "pbf" => Some(FileType::OsmPbf),
+            _ => None,
+        }
+    }
+
+    // Formats read through GDAL or ST_ReadOSM, which come with the spatial extension
+    pub(crate) fn needs_spatial(&self) -> bool {
+        matches!(
+            self,
+            FileType::Geopackage
+                | FileType::Shapefile
+                | FileType::Geojson
+                | FileType::Excel
+                | FileType::Gpx
+                | FileType::OsmPbf
+        )
+    }
+}
+
+// Errors in builds without the spatial feature, which never load the spatial extension
+pub(crate) fn require_spatial(what: &str) -> Result<(), Box<dyn Error>> {
+    if cfg!(feature = "spatial") {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} needs the spatial feature, which this build was compiled without",
+            what
+        )
+        .into())
+    }
 }
 
 // Struct representing core components
-// This will include a UUID in the future that will be used for the PostGIS table name
+// Intermediate tables are suffixed with the load id so loads sharing a LoaderContext don't collide
 struct DuckDBFileProcessor {
     file_path: String,
+    file_paths: Vec<String>,
     table_name: String,
     file_type: FileType,
-    conn: Connection,
+    options: LoadOptions,
+    source_crs: Option<String>,
+    data_table: String,
+    transformed_table: String,
+    // Unique across loads and processes, naming the temporary files the load writes
+    load_key: String,
+    // Postgres database loaded into - the primary, then each replica in turn
+    postgres_connection: String,
+    // Alias postgres_connection is attached under
+    postgres_database: String,
+    // Reader of a FileType::Registered source
+    source_handler: Option<Arc<dyn SourceHandler>>,
+    // Set for CSV and Shapefile sources when the data table is created
+    source_encodings: Vec<SourceEncoding>,
+    conn: LoggedConnection,
+    // Lets cancelling the load interrupt the query running on conn
+    _interrupt: InterruptRegistration,
 }
 
 // Implementation for DuckDBFileProcessor
 impl DuckDBFileProcessor {
-    fn new_file(file_path: &str, table_name: &str) -> Result<Self, Box<dyn Error>> {
-        // Determine FileType
-        let file_type = Self::determine_file_type(file_path)?;
+    fn new_file(
+        file_paths: &[&str],
+        table_name: &str,
+        options: &LoadOptions,
+        conn: Connection,
+        load_id: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let file_path = *file_paths.first().ok_or("No input files provided")?;
 
-        // Create Connection Object
-        let conn = Connection::open(":memory:")?;
-
-        // Install and load required extensions
-        conn.execute("INSTALL spatial;", [])?;
-        conn.execute("LOAD spatial;", [])?;
-        conn.execute("INSTALL postgres;", [])?;
-        conn.execute("LOAD postgres;", [])?;
+        // Determine FileType unless the caller has given a built-in one
+        let source = match options.file_type {
+            Some(file_type) if file_type != FileType::Registered => {
+                DetectedSource::BuiltIn(file_type)
+            }
+            _ => Self::determine_merged_file_type(file_paths, &options.sources)?,
+        };
+        let file_type = source.file_type();
+        if file_type.needs_spatial() {
+            require_spatial(&format!("Reading {:?} files", file_type))?;
+        }
+        let interrupt = options.cancellation.register(&conn);
+        let conn = LoggedConnection::new(conn);
+        options.duckdb_limits.apply(&conn)?;
 
         Ok(Self {
             file_path: file_path.to_string(),
+            file_paths: file_paths.iter().map(|path| path.to_string()).collect(),
             table_name: table_name.to_string(),
             file_type,
+            options: options.clone(),
+            source_crs: None,
+            data_table: format!("data_{}", load_id),
+            transformed_table: format!("transformed_data_{}", load_id),
+            load_key: staging::new_table_name(),
+            postgres_connection: options.postgres_connection.clone(),
+            postgres_database: postgis_database_alias(&options.postgres_connection),
+            source_handler: source.handler(),
+            source_encodings: Vec::new(),
             conn,
+            _interrupt: interrupt,
         })
     }
 
-    fn process_new_file(&self) -> Result<(), Box<dyn Error>> {
+    // Merged files must all be the same type
+    fn determine_merged_file_type(
+        file_paths: &[&str],
+        sources: &SourceRegistry,
+    ) -> Result<DetectedSource, Box<dyn Error>> {
+        let source = Self::determine_file_type(file_paths[0], sources)?;
+        for other_path in &file_paths[1..] {
+            let other_source = Self::determine_file_type(other_path, sources)?;
+            if other_source.name() != source.name() {
+                return Err(format!(
+                    "Cannot merge {} file '{}' with {} file '{}'",
+                    other_source.name(),
+                    other_path,
+                    source.name(),
+                    file_paths[0]
+                )
+                .into());
+            }
+        }
+        Ok(source)
+    }
+
+    fn process_new_file(&mut self, gate: &PriorityGate) -> Result<LoadResult, Box<dyn Error>> {
+        let priority = self.options.priority;
+        let cancellation = self.options.cancellation.clone();
+        let sampler = self
+            .options
+            .resource_sample_interval
+            .map(ResourceSampler::start);
+        let mut normalized_names = Vec::new();
+        if let Some(normalization) = &self.options.name_normalization {
+            let table_name = normalization.normalize(&self.table_name);
+            if table_name != self.table_name {
+                normalized_names.push(NameMapping {
+                    original: std::mem::replace(&mut self.table_name, table_name.clone()),
+                    normalized: table_name,
+                });
+            }
+        }
+        let mut result = LoadResult {
+            table_name: self.table_name.clone(),
+            ..Default::default()
+        };
+        if self.options.skip_unchanged && self.options.sink == Sink::PostGis {
+            let fingerprint = history::fingerprint(&self.file_paths, &self.options)?;
+            if !self.options.force && self.is_unchanged(&fingerprint)? {
+                println!(
+                    "Source of {} is unchanged since its last load, skipping it",
+                    self.table_name
+                );
+                result.fingerprint = Some(fingerprint);
+                result.skipped_unchanged = true;
+                result.query_log = self.conn.log();
+                return Ok(result);
+            }
+            result.fingerprint = Some(fingerprint);
+        }
+
         // Call all the required methods
-        self.create_data_table()?;
+        gate.checkpoint(priority, &self.table_name, &cancellation)?;
+        result.lineage.extend(self.create_data_table()?);
+        result.source_encodings = self
+            .source_encodings
+            .iter()
+            .map(|encoding| (encoding.file_path.clone(), encoding.encoding.clone()))
+            .collect();
+        gate.checkpoint(priority, &self.table_name, &cancellation)?;
+        if let Some(contract) = &self.options.schema_contract {
+            self.enforce_schema_contract(contract)?;
+        }
+        let (detected_point_columns, lineage) = self.construct_points()?;
+        result.detected_point_columns = detected_point_columns;
+        result.lineage.extend(lineage);
+        if let Some(filter) = &self.options.spatial_filter {
+            result.lineage.push(self.apply_spatial_filter(filter)?);
+        }
+        result.lineage.extend(self.apply_unit_conversions()?);
+        result.lineage.extend(self.apply_float_policy()?);
+        if let Some(policy) = self.options.geometry_validation {
+            let (reports, lineage) = self.validate_geometries(policy)?;
+            result.geometry_validation = reports;
+            result.lineage.extend(lineage);
+        }
+        result.lineage.extend(self.apply_column_mapping()?);
+        if let Some(resample) = &self.options.resample {
+            result.lineage.push(self.apply_resample(resample)?);
+        }
+        if let Some(sql) = &self.options.sql_transform {
+            result.lineage.push(self.apply_sql_transform(sql)?);
+        }
+        if let Some(normalization) = &self.options.name_normalization {
+            let (mappings, lineage) = self.normalize_column_names(normalization)?;
+            normalized_names.extend(mappings);
+            result.lineage.extend(lineage);
+        }
+        result.normalized_names = normalized_names;
+        if self.options.deduplicate {
+            result.lineage.push(self.drop_duplicate_rows()?);
+        }
+        if let Some(primary_key) = &self.options.primary_key {
+            result.lineage.push(self.add_primary_key(primary_key)?);
+        }
         self.query_and_print_schema()?;
 
+        gate.checkpoint(priority, &self.table_name, &cancellation)?;
+
+        // Pick typed PostGIS columns before the geometries are encoded as WKB
+        let column_types = self.resolve_geometry_column_types()?;
+
         // Transform geometry columns and store the result
         let geom_columns = self.transform_geom_columns()?;
+        if let Some(simplification) = &self.options.simplification {
+            for column in &geom_columns {
+                result.lineage.push(LineageEntry::new(
+                    "geometry_simplification",
+                    Some(column),
+                    &simplification.describe(),
+                ));
+            }
+        }
+        if self.options.add_lon_lat_columns {
+            result
+                .lineage
+                .extend(self.add_lon_lat_columns(&column_types)?);
+        }
+        result.geometry_statistics = self.geometry_statistics(&column_types)?;
 
-        // Pass the geometry columns to load_data_postgis
-        self.load_data_postgis(&geom_columns)?;
+        if let Some(dictionary_options) = &self.options.data_dictionary {
+            let dictionary = DataDictionary::build(
+                &self.conn,
+                &self.transformed_table,
+                &self.table_name,
+                &self.columns_of(&self.transformed_table)?,
+                &column_types,
+                self.options.sink.geometry_encoding(),
+                dictionary_options,
+            )?;
+            dictionary.write(dictionary_options)?;
+            result.data_dictionary = Some(dictionary);
+        }
 
-        Ok(())
-    }
+        gate.checkpoint(priority, &self.table_name, &cancellation)?;
+        match &self.options.sink {
+            Sink::PostGis => {
+                // Compare against the table being replaced before it is dropped
+                let retry_policy = &self.options.retry_policy;
+                let ((), retries) =
+                    retry_policy.run(RetryStage::Attach, |_| self.attach_postgis())?;
+                result.retries += retries;
+                result.drift = self.check_drift(&geom_columns)?;
 
-    fn determine_file_type(file_path: &str) -> Result<FileType, Box<dyn Error>> {
-        // Open file and read into buffer
-        let mut file = File::open(file_path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-
-        // Read in header of file
-        let header = &buffer[0..16.min(buffer.len())];
-
-        // Check for FileType
-        match header {
-            b"PK\x03\x04" => Ok(FileType::Excel),
-            b"SQLite format 3\0" => Ok(FileType::Geopackage),
-            [0, 0, 39, 10, ..] => Ok(FileType::Shapefile),
-            b"PAR1" => Ok(FileType::Parquet),
-            _ if header.starts_with(b"{") => {
-                let json_start = std::str::from_utf8(&buffer)?;
-                if json_start.contains("\"type\":")
-                    && (json_start.contains("\"FeatureCollection\"")
-                        || json_start.contains("\"Feature\""))
-                {
-                    Ok(FileType::Geojson)
-                } else {
-                    Err("Not a valid GeoJSON file".into())
+                let report = self.load_data_postgis(&column_types, gate)?;
+                result.retries += report.retries;
+                result.verification = report.verification.clone();
+                result.schema_evolution = report.schema_evolution.clone();
+                result.sink_report = Some(report);
+
+                if self.options.write_layer_extents && !column_types.is_empty() {
+                    result.layer_extents = self.layer_extents(&column_types)?;
+                    self.write_layer_extents(&result.layer_extents)?;
                 }
-            }
-            _ => {
-                let file_text = std::str::from_utf8(&buffer)?;
-                let lines: Vec<&str> = file_text.lines().collect();
-                if lines.len() >= 2
-                    && lines[0].split(',').count() > 1
-                    && lines[1].split(',').count() == lines[0].split(',').count()
-                    && file_text.is_ascii()
-                {
-                    Ok(FileType::Csv)
-                } else {
-                    Err("Unknown file type".into())
+                if self.options.write_layer_styles {
+                    result.layer_styles = self.write_layer_styles(&column_types)?;
                 }
+                if self.options.write_catalog {
+                    self.write_catalog_entry(&column_types)?;
+                }
+                if let Some(fingerprint) = &result.fingerprint {
+                    self.postgres_execute(&history::history_statements(
+                        self.options.schema.as_deref(),
+                        &self.table_name,
+                        &self.postgis_table(),
+                        fingerprint,
+                        &self.file_paths,
+                    ))?;
+                }
+
+                for connection in self.options.replica_connections.clone() {
+                    result
+                        .replicas
+                        .push(self.load_replica(&connection, &column_types, gate));
+                }
+            }
+            Sink::Custom(custom) => {
+                // Statements the writer runs itself are not in the query log
+                let report = custom.0.write(
+                    self.conn.connection(),
+                    &self.transformed_table,
+                    &SinkContext {
+                        table_name: &self.table_name,
+                        column_types: &column_types,
+                        options: &self.options,
+                    },
+                )?;
+                result.retries += report.retries;
+                result.verification = report.verification.clone();
+                result.schema_evolution = report.schema_evolution.clone();
+                result.sink_report = Some(report);
+            }
+            sink => sink.write_file(
+                &self.conn,
+                &self.transformed_table,
+                &self.table_name,
+                &column_types,
+                sink.geometry_encoding(),
+            )?,
+        }
+
+        result.geometry_columns = geom_columns;
+        result.geometry_column_types = column_types;
+        result.query_log = self.conn.log();
+        result.resource_usage = sampler.and_then(ResourceSampler::finish);
+        Ok(result)
+    }
+
+    fn determine_file_type(
+        file_path: &str,
+        sources: &SourceRegistry,
+    ) -> Result<DetectedSource, Box<dyn Error>> {
+        // Open file and read the start of it into buffer - the rest is left for DuckDB
+        let file = File::open(file_path)?;
+        let mut buffer = Vec::with_capacity(detect::DETECTION_PREFIX_BYTES);
+        file.take(detect::DETECTION_PREFIX_BYTES as u64)
+            .read_to_end(&mut buffer)?;
+        if buffer.is_empty() {
+            return Err(format!("'{}' is empty", file_path).into());
+        }
+
+        if let Some(source) = sources.detect(&buffer) {
+            return Ok(source);
+        }
+
+        // Content such as UTF-16 text or a top-level JSON array is ambiguous, so trust the extension
+        let source = match sources.handler_for_extension(file_path) {
+            Some(handler) => Some(DetectedSource::Registered(handler)),
+            None => FileType::from_extension(file_path).map(DetectedSource::BuiltIn),
+        };
+        match source {
+            Some(source) => {
+                println!(
+                    "Could not detect the type of '{}' from its content, using its extension: {}",
+                    file_path,
+                    source.name()
+                );
+                Ok(source)
             }
+            None => Err(format!(
+                "Unknown file type: the content of '{}' matches no supported format and its extension is not recognised",
+                file_path
+            )
+            .into()),
         }
     }
 
-    fn create_data_table(&self) -> Result<(), Box<dyn Error>> {
-        // Create initial 'data' table
+    fn source_query(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
         let query = match self.file_type {
-            FileType::Geopackage | FileType::Shapefile | FileType::Geojson => {
+            FileType::Geopackage | FileType::Shapefile | FileType::Geojson | FileType::Gpx => {
                 format!(
-                    "CREATE TABLE data AS SELECT * FROM ST_Read('{}');",
-                    self.file_path
+                    "SELECT * FROM ST_Read('{}'{}{}{})",
+                    file_path,
+                    self.layer_argument(),
+                    self.options.reader_options.spatial.arguments(
+                        &self
+                            .source_encoding(file_path)
+                            .and_then(|encoding| encoding.open_option.clone())
+                            .into_iter()
+                            .collect::<Vec<_>>()
+                    ),
+                    self.spatial_filter_argument(file_path)?
                 )
             }
-            FileType::Excel => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM st_read('{}');",
-                    self.file_path
-                )
+            FileType::Excel => self.excel_read(file_path, &self.layer_argument(), false),
+            // Overridden types are applied while parsing so values such as leading-zero codes survive
+            FileType::Csv => format!(
+                "SELECT * FROM read_csv('{}'{})",
+                file_path,
+                self.options
+                    .reader_options
+                    .csv
+                    .arguments(&self.options.column_mapping.type_overrides)
+            ),
+            FileType::Parquet => format!(
+                "SELECT * FROM parquet_scan('{}'{})",
+                file_path,
+                self.options.reader_options.parquet.arguments()
+            ),
+            FileType::DuckDb => format!(
+                "SELECT * FROM {}.\"{}\"",
+                source_database_alias(file_path),
+                self.options.layer.as_deref().unwrap_or_default()
+            ),
+            // Read through the nanoarrow community extension, as DuckDB has no built-in IPC reader
+            FileType::Arrow => format!("SELECT * FROM read_arrow('{}')", file_path),
+            FileType::OsmPbf => osm::layer_query(file_path, self.options.layer.as_deref())?,
+            FileType::Registered => self
+                .registered_handler()?
+                .ingest_sql(file_path, &self.options)?,
+        };
+        Ok(query)
+    }
+
+    // Bounding box of LoadOptions::spatial_filter in the file's CRS, for GDAL to skip features outside it
+    // The box is widened slightly as reprojecting only its corners can cut off curved edges
+    fn spatial_filter_argument(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        let Some(filter) = &self.options.spatial_filter else {
+            return Ok(String::new());
+        };
+        if self.options.reader_options.spatial.spatial_filter.is_some() {
+            return Ok(String::new());
+        }
+        let file_crs = self.get_crs_number(file_path)?;
+        let area = format!(
+            "ST_GeomFromText('{}')",
+            filter.area.to_wkt().replace('\'', "''")
+        );
+        let area = if file_crs == filter.srid {
+            area
+        } else {
+            format!(
+                "ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)",
+                area, filter.srid, file_crs
+            )
+        };
+        let Some(extent) = query_extent(
+            &self.conn,
+            &format!(
+                "SELECT ST_XMin(g), ST_YMin(g), ST_XMax(g), ST_YMax(g) FROM (SELECT {} AS g);",
+                area
+            ),
+        )?
+        else {
+            return Err("Spatial filter area is empty".into());
+        };
+        let margin_x = (extent.max_x - extent.min_x) * 0.01;
+        let margin_y = (extent.max_y - extent.min_y) * 0.01;
+        let extent = Extent {
+            min_x: extent.min_x - margin_x,
+            min_y: extent.min_y - margin_y,
+            max_x: extent.max_x + margin_x,
+            max_y: extent.max_y + margin_y,
+        };
+        Ok(format!(
+            ", spatial_filter := ST_AsWKB(ST_GeomFromText('{}'))",
+            extent.to_wkt()
+        ))
+    }
+
+    // Sheets of an Excel workbook are layers too
+    fn layer_argument(&self) -> String {
+        let sheet = match (&self.file_type, &self.options.excel.sheet) {
+            (FileType::Excel, Some(ExcelSheet::Name(name))) => Some(name),
+            _ => self.options.layer.as_ref(),
+        };
+        match sheet {
+            Some(layer) => format!(", layer := {}", catalog::literal(layer)),
+            None => String::new(),
+        }
+    }
+
+    // Reads a sheet with GDAL - as cells, every value is text under GDAL's Field1, Field2, ... names
+    fn excel_read(&self, file_path: &str, layer_argument: &str, as_cells: bool) -> String {
+        let excel = &self.options.excel;
+        let mut open_options = excel.open_options.clone();
+        if as_cells || !excel.header {
+            open_options.push("HEADERS=DISABLE".to_string());
+        }
+        if as_cells {
+            open_options.push("FIELD_TYPES=STRING".to_string());
+        }
+        let open_options = if open_options.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ", open_options := [{}]",
+                open_options
+                    .iter()
+                    .map(|option| catalog::literal(option))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        format!(
+            "SELECT * FROM st_read('{}'{}{})",
+            file_path, layer_argument, open_options
+        )
+    }
+
+    // Query reading a file into the data table, resolving what source_query cannot without reading the file
+    fn read_query(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        match self.file_type {
+            FileType::Excel => self.excel_query(file_path),
+            FileType::DuckDb => self.duckdb_query(file_path),
+            FileType::Arrow => {
+                self.conn.execute("INSTALL nanoarrow FROM community;", [])?;
+                self.conn.execute("LOAD nanoarrow;", [])?;
+                self.source_query(file_path)
+            }
+            FileType::Registered => {
+                for statement in self.registered_handler()?.setup_sql() {
+                    self.conn.execute(&statement, [])?;
+                }
+                self.source_query(file_path)
             }
             FileType::Csv => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM read_csv('{}');",
-                    self.file_path
-                )
+                let encoding = match self.source_encoding(file_path) {
+                    Some(encoding) => encoding.encoding.clone(),
+                    None => self.options.reader_options.csv.encoding_of(file_path)?,
+                };
+                match CsvReaderOptions::transcode(file_path, &encoding, &self.load_key)? {
+                    Some(path) => self.source_query(&path.to_string_lossy()),
+                    None => self.source_query(file_path),
+                }
             }
-            FileType::Parquet => {
-                format!(
-                    "CREATE TABLE data AS SELECT * FROM parquet_scan('{}');",
-                    self.file_path
-                )
+            _ => self.source_query(file_path),
+        }
+    }
+
+    fn registered_handler(&self) -> Result<&dyn SourceHandler, Box<dyn Error>> {
+        match &self.source_handler {
+            Some(handler) => Ok(handler.as_ref()),
+            None => Err("No SourceHandler in LoadOptions::sources recognises the file".into()),
+        }
+    }
+
+    // Reported file type - the handler's name for registered formats
+    fn file_type_name(&self) -> String {
+        match &self.source_handler {
+            Some(handler) => handler.name(),
+            None => format!("{:?}", self.file_type),
+        }
+    }
+
+    // Attaches the database read-only and reads the table named by the layer, or its only table
+    fn duckdb_query(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        let alias = source_database_alias(file_path);
+        self.conn.execute(
+            &format!(
+                "ATTACH IF NOT EXISTS '{}' AS {} (READ_ONLY);",
+                file_path, alias
+            ),
+            [],
+        )?;
+        if self.options.layer.is_some() {
+            return self.source_query(file_path);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT table_name FROM information_schema.tables WHERE table_catalog = ? ORDER BY table_name",
+        )?;
+        let tables = stmt
+            .query_map([alias.as_str()], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        match tables.as_slice() {
+            [table] => Ok(format!("SELECT * FROM {}.\"{}\"", alias, table)),
+            [] => Err(format!("Database '{}' has no tables", file_path).into()),
+            _ => Err(format!(
+                "Database '{}' has several tables, choose one with the layer option: {}",
+                file_path,
+                tables.join(", ")
+            )
+            .into()),
+        }
+    }
+
+    fn excel_query(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        let excel = &self.options.excel;
+        if !matches!(excel.sheet, Some(ExcelSheet::Index(_))) && !excel.reads_cells() {
+            return self.source_query(file_path);
+        }
+
+        let layer = match excel.sheet {
+            Some(ExcelSheet::Index(index)) => {
+                let name: Option<String> = self.conn.query_row(
+                    &format!(
+                        "SELECT layers[{}].name FROM st_read_meta('{}');",
+                        index + 1,
+                        file_path
+                    ),
+                    [],
+                    |row| row.get(0),
+                )?;
+                let name = name.ok_or_else(|| {
+                    format!("Workbook '{}' has no sheet at index {}", file_path, index)
+                })?;
+                format!(", layer := {}", catalog::literal(&name))
+            }
+            _ => self.layer_argument(),
+        };
+        if !excel.reads_cells() {
+            return Ok(self.excel_read(file_path, &layer, false));
+        }
+
+        // Number the rows of the raw cells in sheet order
+        let cells = format!(
+            "(SELECT row_number() OVER () AS gridwalk_row, * FROM ({}))",
+            self.excel_read(file_path, &layer, true)
+        );
+
+        let range = excel.range.as_deref().map(CellRange::parse).transpose()?;
+        let first_row = range.map_or(1, |range| range.first_row) + excel.skip_rows;
+        let last_row = range.and_then(|range| range.last_row);
+        let fields = match range {
+            Some(range) => (range.first_column..=range.last_column)
+                .map(|column| format!("Field{}", column))
+                .collect::<Vec<_>>(),
+            None => self
+                .conn
+                .prepare(&format!("DESCRIBE SELECT * FROM {};", cells))?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|column| column != "gridwalk_row")
+                .collect(),
+        };
+
+        // Name the columns from the header row, falling back to the GDAL name for blank headers
+        let mut names = fields.clone();
+        let mut first_data_row = first_row;
+        if excel.header {
+            let header_query = format!(
+                "SELECT {} FROM {} WHERE gridwalk_row = {};",
+                fields
+                    .iter()
+                    .map(|field| format!("\"{}\"::VARCHAR", field))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                cells,
+                first_row
+            );
+            let headers = self.conn.query_row(&header_query, [], |row| {
+                (0..fields.len())
+                    .map(|i| row.get::<_, Option<String>>(i))
+                    .collect::<Result<Vec<_>, _>>()
+            });
+            match headers {
+                Ok(headers) => {
+                    for (name, header) in names.iter_mut().zip(headers) {
+                        if let Some(header) = header.filter(|header| !header.trim().is_empty()) {
+                            *name = header.trim().to_string();
+                        }
+                    }
+                }
+                Err(duckdb::Error::QueryReturnedNoRows) => {
+                    return Err(format!("Workbook '{}' has no row {}", file_path, first_row).into())
+                }
+                Err(e) => return Err(e.into()),
             }
+            first_data_row += 1;
+        }
+
+        let columns = fields
+            .iter()
+            .zip(&names)
+            .map(|(field, name)| format!("\"{}\" AS \"{}\"", field, name.replace('"', "\"\"")))
+            .collect::<Vec<_>>();
+        let mut filter = format!("gridwalk_row >= {}", first_data_row);
+        if let Some(last_row) = last_row {
+            filter.push_str(&format!(" AND gridwalk_row <= {}", last_row));
+        }
+        Ok(format!(
+            "SELECT {} FROM {} WHERE {}",
+            columns.join(", "),
+            cells,
+            filter
+        ))
+    }
+
+    fn create_data_table(&mut self) -> Result<Vec<LineageEntry>, Box<dyn Error>> {
+        self.source_encodings = self.detect_encodings()?;
+        if self.file_type == FileType::Gpx && self.options.layer.is_none() {
+            self.options.layer = Some(self.default_gpx_layer()?);
+        }
+
+        // Single files are read as they are
+        if self.file_paths.len() == 1 {
+            self.ingest(&self.read_query(&self.file_path)?)?;
+            return Ok(Vec::new());
+        }
+
+        // Merged files are combined by column name into one data table
+        let (queries, lineage) = match self.file_type {
+            FileType::Geopackage | FileType::Shapefile | FileType::Geojson | FileType::Gpx => {
+                self.reconcile_merged_crs()?
+            }
+            _ => (
+                self.file_paths
+                    .iter()
+                    .map(|path| self.read_query(path))
+                    .collect::<Result<_, _>>()?,
+                Vec::new(),
+            ),
         };
+        self.ingest(&queries.join("\nUNION ALL BY NAME\n"))?;
+        Ok(lineage)
+    }
+
+    // Character encodings of CSV and Shapefile sources, found before the sources are read
+    fn detect_encodings(&self) -> Result<Vec<SourceEncoding>, Box<dyn Error>> {
+        let reader_options = &self.options.reader_options;
+        // An ENCODING the caller gives GDAL is used as it is
+        let gdal_encoding_given = reader_options
+            .spatial
+            .open_options
+            .iter()
+            .any(|option| option.to_ascii_uppercase().starts_with("ENCODING="));
+        let mut encodings = Vec::new();
+        for file_path in &self.file_paths {
+            let (encoding, open_option) = match self.file_type {
+                FileType::Csv => (Some(reader_options.csv.encoding_of(file_path)?), None),
+                FileType::Shapefile if !gdal_encoding_given => {
+                    charset::shapefile_encoding(file_path)?
+                }
+                _ => (None, None),
+            };
+            if let Some(encoding) = encoding {
+                if encoding != "utf-8" {
+                    println!("Reading '{}' as {}", file_path, encoding);
+                }
+                encodings.push(SourceEncoding {
+                    file_path: file_path.clone(),
+                    encoding,
+                    open_option,
+                });
+            }
+        }
+        Ok(encodings)
+    }
+
+    fn source_encoding(&self, file_path: &str) -> Option<&SourceEncoding> {
+        self.source_encodings
+            .iter()
+            .find(|encoding| encoding.file_path == file_path)
+    }
+
+    // GDAL lists every GPX layer whether or not the file has any, so take the first with features
+    fn default_gpx_layer(&self) -> Result<String, Box<dyn Error>> {
+        let layers = inspect::read_layers(self.conn.connection(), &self.file_path)?;
+        let layer = ["tracks", "routes", "waypoints"]
+            .into_iter()
+            .find(|name| {
+                layers
+                    .iter()
+                    .any(|layer| layer.name == *name && layer.feature_count > 0)
+            })
+            .unwrap_or("tracks");
+        println!("Reading GPX layer {} of '{}'", layer, self.file_path);
+        Ok(layer.to_string())
+    }
+
+    fn ingest(&self, source: &str) -> Result<(), Box<dyn Error>> {
+        let query = templates::render(
+            &self.options.sql_templates.ingest,
+            &[
+                ("table", &self.data_table),
+                ("source", source),
+                ("path", &self.file_path),
+            ],
+        )?;
         self.conn.execute(&query, [])?;
         Ok(())
     }
 
-    fn query_and_print_schema(&self) -> Result<Arc<Schema>, Box<dyn Error>> {
-        // Create and prep query
-        let query = "SELECT * FROM data LIMIT 10";
-        let mut stmt = self.conn.prepare(query)?;
+    fn reconcile_merged_crs(&mut self) -> Result<(Vec<String>, Vec<LineageEntry>), Box<dyn Error>> {
+        // Find the CRS of every input
+        let mut file_crs = Vec::new();
+        for path in &self.file_paths {
+            file_crs.push((path.clone(), self.get_crs_number(path)?));
+        }
 
-        // Run query
-        let arrow_result = stmt.query_arrow([])?;
-        let schema = arrow_result.get_schema();
+        let first_crs = file_crs[0].1.clone();
+        if file_crs.iter().all(|(_, crs)| *crs == first_crs) {
+            self.source_crs = Some(first_crs);
+            let queries = self
+                .file_paths
+                .iter()
+                .map(|path| self.source_query(path))
+                .collect::<Result<_, _>>()?;
+            return Ok((queries, Vec::new()));
+        }
 
-        // Print and return schema
-        println!("Schema: {:?}", schema);
-        Ok(schema)
-    }
+        let summary = file_crs
+            .iter()
+            .map(|(path, crs)| format!("'{}' (EPSG:{})", path, crs))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.options.crs_mismatch_policy == CrsMismatchPolicy::Fail {
+            return Err(format!("Input files use different CRSs: {}", summary).into());
+        }
+        println!("Input files use different CRSs: {}", summary);
 
-    fn get_crs_number(&self) -> Result<String, Box<dyn Error>> {
-        // Let and prep query
-        let query = format!(
-            "SELECT layers[1].geometry_fields[1].crs.auth_code AS crs_number
-            FROM st_read_meta('{}');",
-            self.file_path
-        );
-        let mut stmt = self.conn.prepare(&query)?;
+        // Reproject every input that is not already in the target CRS
+        let target_crs = self.options.target_crs.clone();
+        let mut queries = Vec::new();
+        let mut lineage = Vec::new();
+        for (path, crs) in &file_crs {
+            if *crs == target_crs {
+                queries.push(self.source_query(path)?);
+                continue;
+            }
 
-        // Run query and return CRS number
-        let mut rows = stmt.query([])?;
-        if let Some(row) = rows.next()? {
-            let crs_number: String = row.get(0)?;
-            Ok(crs_number)
-        } else {
-            Err(format!("CRS not found for the following file: {}", self.file_path).into())
+            let replacements = self
+                .source_geom_columns(path)?
+                .iter()
+                .map(|column| {
+                    format!(
+                        "ST_Transform({0}, 'EPSG:{1}', 'EPSG:{2}', always_xy := true) AS {0}",
+                        catalog::identifier(column),
+                        crs,
+                        target_crs
+                    )
+                })
+                .collect::<Vec<_>>();
+            if replacements.is_empty() {
+                queries.push(self.source_query(path)?);
+                continue;
+            }
+            queries.push(format!(
+                "SELECT * REPLACE ({}) FROM ({})",
+                replacements.join(", "),
+                self.source_query(path)?
+            ));
+            lineage.push(LineageEntry::new(
+                "crs_reconciliation",
+                None,
+                &format!(
+                    "Reprojected '{}' from EPSG:{} to EPSG:{} before merging",
+                    path, crs, target_crs
+                ),
+            ));
         }
+
+        // The merged data is now entirely in the target CRS
+        self.source_crs = Some(target_crs);
+        Ok((queries, lineage))
     }
 
-    fn transform_geom_columns(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        let query = "SELECT column_name FROM information_schema.columns WHERE table_name = 'data' AND data_type = 'GEOMETRY'";
-        let mut stmt = self.conn.prepare(query)?;
+    fn source_geom_columns(&self, file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let query = format!("DESCRIBE {};", self.source_query(file_path)?);
+        let mut stmt = self.conn.prepare(&query)?;
         let mut rows = stmt.query([])?;
         let mut geom_columns = Vec::new();
-
         while let Some(row) = rows.next()? {
             let column_name: String = row.get(0)?;
-            geom_columns.push(column_name);
-        }
-
-        println!("Geometry columns: {:?}", &geom_columns);
-
-        // Call transform_crs for each geometry column
-        let target_crs = "4326";
-        for column in &geom_columns {
-            self.transform_crs(column, target_crs)?;
+            let column_type: String = row.get(1)?;
+            if column_type == "GEOMETRY" {
+                geom_columns.push(column_name);
+            }
         }
-
         Ok(geom_columns)
     }
 
-    fn transform_crs(&self, geom_column: &str, target_crs: &str) -> Result<String, Box<dyn Error>> {
-        let current_crs = self.get_crs_number()?;
-        println!("Current CRS for column {}: {}", geom_column, current_crs);
+    fn enforce_schema_contract(&self, contract: &SchemaContract) -> Result<(), Box<dyn Error>> {
+        let columns = self.data_columns()?;
 
-        let create_table_query = if current_crs == target_crs {
-            format!(
-                "CREATE TABLE transformed_data AS SELECT *,
-                ST_AsText({}) as {}_wkt
-                FROM data;",
-                geom_column, geom_column
-            )
+        let geometry_types = self.geometry_types()?;
+
+        let srid = if contract.srid.is_some() && !geometry_types.is_empty() {
+            Some(self.current_crs()?)
         } else {
-            format!(
-                "CREATE TABLE transformed_data AS SELECT *,
-                ST_AsText(ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)) AS {}_wkt
-                FROM data;",
-                geom_column, current_crs, target_crs, geom_column
-            )
+            None
         };
 
-        self.conn.execute(&create_table_query, [])?;
-        self.conn.execute(
-            &format!("ALTER TABLE transformed_data DROP COLUMN {};", geom_column),
-            [],
-        )?;
-
-        if current_crs == target_crs {
-            Ok(format!(
-                "CRS for column {} is already {}. Geometry converted to WKT and original geom column dropped.",
-                geom_column, target_crs
-            ))
+        let violations = contract.violations(&columns, &geometry_types, srid.as_deref());
+        if violations.is_empty() {
+            Ok(())
         } else {
-            Ok(format!(
-                "Transformation of column {} from EPSG:{} to EPSG:{} completed. Geometry converted to WKT and original geom column dropped.",
-                geom_column, current_crs, target_crs
-            ))
+            Err(Box::new(ContractViolationError { violations }))
         }
     }
 
-    fn load_data_postgis(&self, geom_columns: &[String]) -> Result<(), Box<dyn Error>> {
-        // Attach Postgres DB instance
-        self.conn.execute(
-            "ATTACH 'dbname=gridwalk user=admin password=password host=localhost port=5432' AS gridwalk_db (TYPE POSTGRES)",
-            [],
-        )?;
+    // Distinct geometry types across all geometry columns
+    fn geometry_types(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut geometry_types = Vec::new();
+        for column in self.geom_columns()? {
+            for geometry_type in self.column_geometry_types(&catalog::identifier(&column))? {
+                if !geometry_types.contains(&geometry_type) {
+                    geometry_types.push(geometry_type);
+                }
+            }
+        }
+        Ok(geometry_types)
+    }
 
-        // Execute CRUD logic
-        let delete_if_table_exists_query =
-            &format!("DROP TABLE IF EXISTS gridwalk_db.{};", self.table_name);
-        self.conn.execute(delete_if_table_exists_query, [])?;
+    // Distinct types of a geometry column, or of a geometry expression over the data table
+    fn column_geometry_types(&self, geometry: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT DISTINCT ST_GeometryType({})::VARCHAR FROM {} WHERE {} IS NOT NULL;",
+            geometry, self.data_table, geometry
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut geometry_types = Vec::new();
+        while let Some(row) = rows.next()? {
+            geometry_types.push(row.get(0)?);
+        }
+        Ok(geometry_types)
+    }
 
-        let create_table_query = &format!(
-            "CREATE TABLE gridwalk_db.{} AS SELECT * FROM transformed_data;",
-            self.table_name
-        );
-        self.conn.execute(create_table_query, [])?;
-
-        // Construct PostGIS query for each geometry column
-        let mut postgis_queries = Vec::new();
-        for geom_column in geom_columns {
-            postgis_queries.push(format!(
-                "ALTER TABLE {} ADD COLUMN {} geometry;
-                UPDATE {} SET {} = ST_GeomFromText({}_wkt, 4326);
-                ALTER TABLE {} DROP COLUMN {}_wkt;",
-                self.table_name,
-                geom_column,
-                self.table_name,
-                geom_column,
-                geom_column,
-                self.table_name,
-                geom_column
-            ));
+    fn resolve_geometry_column_types(&self) -> Result<Vec<GeometryColumnType>, Box<dyn Error>> {
+        let geom_columns = self.geom_columns()?;
+        // Rounding can split a Polygon into a MultiPolygon or collapse it, so with a simplification the types
+        // are read from the geometries as they will be stored
+        let geometries = match self.options.simplification {
+            Some(_) => self.target_geometries(&geom_columns)?,
+            None => geom_columns
+                .iter()
+                .map(|c| catalog::identifier(c))
+                .collect(),
+        };
+        let mut column_types = Vec::new();
+        for (column, geometry) in geom_columns.into_iter().zip(geometries) {
+            let (geometry_type, promoted_to_multi) = geometry::resolve_column_type(
+                &self.column_geometry_types(&geometry)?,
+                self.options.promote_to_multi,
+            );
+            let (has_z, has_m) = self.conn.query_row(
+                &format!(
+                    "SELECT coalesce(bool_or(ST_HasZ({0})), false), coalesce(bool_or(ST_HasM({0})), false) FROM {1};",
+                    catalog::identifier(&column),
+                    self.data_table
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            let column_type = GeometryColumnType {
+                column,
+                geometry_type,
+                srid: self.options.target_crs.clone(),
+                promoted_to_multi,
+                has_z,
+                has_m,
+            };
+            println!(
+                "PostGIS type for column {}: {}",
+                column_type.column,
+                column_type.postgis_type()
+            );
+            column_types.push(column_type);
         }
+        Ok(column_types)
+    }
 
-        let postgis_query = &format!(
-            "CALL postgres_execute('gridwalk_db', '{}');",
-            postgis_queries.join("\n")
-        );
-        self.conn.execute(postgis_query, [])?;
+    fn data_columns(&self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        self.columns_of(&self.data_table)
+    }
 
-        println!(
-            "Table {} created and data inserted successfully with geometry columns: {:?}",
-            self.table_name, geom_columns
-        );
-        Ok(())
+    fn columns_of(&self, table: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let query = "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = ? ORDER BY ordinal_position";
+        let mut stmt = self.conn.prepare(query)?;
+        let mut rows = stmt.query([table])?;
+        let mut columns = Vec::new();
+        while let Some(row) = rows.next()? {
+            columns.push((row.get(0)?, row.get(1)?));
+        }
+        Ok(columns)
     }
-}
 
-pub fn launch_process_file(file_path: &str, table_name: &str) -> Result<(), io::Error> {
-    // Create new processor object
-    let processor = DuckDBFileProcessor::new_file(file_path, table_name).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Error creating FileProcessor for '{}': {}", file_path, e),
-        )
-    })?;
+    fn apply_unit_conversions(&self) -> Result<Vec<LineageEntry>, Box<dyn Error>> {
+        let mut lineage = Vec::new();
+        for conversion in &self.options.unit_conversions {
+            // Check the column exists and holds numbers
+            let mut stmt = self.conn.prepare(
+                "SELECT data_type FROM information_schema.columns WHERE table_name = ? AND column_name = ?",
+            )?;
+            let mut rows = stmt.query([self.data_table.as_str(), conversion.column.as_str()])?;
+            let data_type: String = match rows.next()? {
+                Some(row) => row.get(0)?,
+                None => {
+                    return Err(format!(
+                        "Unit conversion column {} not found in data",
+                        conversion.column
+                    )
+                    .into())
+                }
+            };
+            if !units::is_numeric_type(&data_type) {
+                return Err(format!(
+                    "Unit conversion column {} has non-numeric type {}",
+                    conversion.column, data_type
+                )
+                .into());
+            }
 
-    println!(
-        "Detected file type: {:?} for file: '{}'",
-        processor.file_type, file_path
-    );
+            // Convert in place, widening to DOUBLE so integer columns keep their precision
+            let factor = conversion.factor()?;
+            self.conn.execute(
+                &format!(
+                    "ALTER TABLE {} ALTER COLUMN \"{}\" SET DATA TYPE DOUBLE USING CAST(\"{}\" AS DOUBLE) * {};",
+                    self.data_table, conversion.column, conversion.column, factor
+                ),
+                [],
+            )?;
 
-    // Process the file
-    processor.process_new_file().map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "Error processing {:?} file '{}': {}",
-                processor.file_type, file_path, e
-            ),
-        )
-    })?;
+            lineage.push(LineageEntry::new(
+                "unit_conversion",
+                Some(&conversion.column),
+                &format!(
+                    "Converted from {} to {} (factor {})",
+                    conversion.from, conversion.to, factor
+                ),
+            ));
+        }
+        Ok(lineage)
+    }
+
+    fn apply_float_policy(&self) -> Result<Vec<LineageEntry>, Box<dyn Error>> {
+        let mut lineage = Vec::new();
+        for (column, data_type) in self.data_columns()? {
+            if data_type != "FLOAT" && data_type != "DOUBLE" {
+                continue;
+            }
+
+            let non_finite_count: i64 = self.conn.query_row(
+                &format!(
+                    "SELECT count(*) FROM {} WHERE NOT isfinite(\"{}\");",
+                    self.data_table, column
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+            if non_finite_count > 0 {
+                // The sentinel is a typed literal, as Rust's text for a large value is not a DOUBLE in SQL
+                let replacement = match self.options.non_finite_policy {
+                    NonFinitePolicy::Keep => None,
+                    NonFinitePolicy::Null => Some(("NULL".to_string(), "NULL".to_string())),
+                    NonFinitePolicy::Sentinel(value) if !value.is_finite() => {
+                        return Err(format!(
+                            "The sentinel for NaN and infinite values must be finite, not {}",
+                            value
+                        )
+                        .into())
+                    }
+                    NonFinitePolicy::Sentinel(value) => {
+                        Some((format!("'{}'::DOUBLE", value), value.to_string()))
+                    }
+                    NonFinitePolicy::Fail => {
+                        return Err(format!(
+                            "Column {} contains {} NaN or infinite values",
+                            column, non_finite_count
+                        )
+                        .into())
+                    }
+                };
+                if let Some((replacement, description)) = replacement {
+                    self.conn.execute(
+                        &format!(
+                            "UPDATE {} SET \"{}\" = {} WHERE NOT isfinite(\"{}\");",
+                            self.data_table, column, replacement, column
+                        ),
+                        [],
+                    )?;
+                    lineage.push(LineageEntry::new(
+                        "non_finite_values",
+                        Some(&column),
+                        &format!(
+                            "Replaced {} NaN or infinite values with {}",
+                            non_finite_count, description
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(decimal_places) = self.options.float_decimal_places {
+                self.conn.execute(
+                    &format!(
+                        "UPDATE {} SET \"{}\" = round(\"{}\", {}) WHERE isfinite(\"{}\");",
+                        self.data_table, column, column, decimal_places, column
+                    ),
+                    [],
+                )?;
+                lineage.push(LineageEntry::new(
+                    "float_formatting",
+                    Some(&column),
+                    &format!("Rounded to {} decimal places", decimal_places),
+                ));
+            }
+        }
+        Ok(lineage)
+    }
+
+    fn validate_geometries(
+        &self,
+        policy: GeometryValidationPolicy,
+    ) -> Result<(Vec<GeometryValidationReport>, Vec<LineageEntry>), Box<dyn Error>> {
+        let mut reports = Vec::new();
+        let mut lineage = Vec::new();
+        for column in self.geom_columns()? {
+            let quoted = catalog::identifier(&column);
+            let invalid_count: i64 = self.conn.query_row(
+                &format!(
+                    "SELECT count(*) FROM {} WHERE NOT ST_IsValid({});",
+                    self.data_table, quoted
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+            let mut report = GeometryValidationReport {
+                column: column.clone(),
+                invalid_count: invalid_count as u64,
+                skipped_count: 0,
+                repaired_count: 0,
+            };
+            println!("Invalid geometries in column {}: {}", column, invalid_count);
+
+            if invalid_count > 0 {
+                match policy {
+                    GeometryValidationPolicy::Fail => {
+                        return Err(format!(
+                            "Column {} contains {} invalid geometries",
+                            column, invalid_count
+                        )
+                        .into());
+                    }
+                    GeometryValidationPolicy::Skip => {
+                        self.conn.execute(
+                            &format!(
+                                "DELETE FROM {} WHERE NOT ST_IsValid({});",
+                                self.data_table, quoted
+                            ),
+                            [],
+                        )?;
+                        report.skipped_count = invalid_count as u64;
+                        lineage.push(LineageEntry::new(
+                            "geometry_validation",
+                            Some(&column),
+                            &format!("Dropped {} rows with invalid geometries", invalid_count),
+                        ));
+                    }
+                    GeometryValidationPolicy::Repair => {
+                        self.conn.execute(
+                            &format!(
+                                "UPDATE {} SET {1} = ST_MakeValid({1}) WHERE NOT ST_IsValid({1});",
+                                self.data_table, quoted
+                            ),
+                            [],
+                        )?;
+                        report.repaired_count = invalid_count as u64;
+                        lineage.push(LineageEntry::new(
+                            "geometry_validation",
+                            Some(&column),
+                            &format!(
+                                "Repaired {} invalid geometries with ST_MakeValid",
+                                invalid_count
+                            ),
+                        ));
+                    }
+                }
+            }
+            reports.push(report);
+        }
+        Ok((reports, lineage))
+    }
+
+    fn apply_column_mapping(&self) -> Result<Vec<LineageEntry>, Box<dyn Error>> {
+        let mapping = &self.options.column_mapping;
+        if mapping.select.is_none()
+            && mapping.rename.is_empty()
+            && mapping.type_overrides.is_empty()
+        {
+            return Ok(Vec::new());
+        }
+
+        let columns = self.data_columns()?;
+        let geom_columns = self.geom_columns()?;
+
+        // Every referenced column must exist
+        let referenced = mapping
+            .select
+            .iter()
+            .flatten()
+            .chain(mapping.rename.iter().map(|(column, _)| column))
+            .chain(mapping.type_overrides.iter().map(|(column, _)| column));
+        for column in referenced {
+            if !columns.iter().any(|(name, _)| name == column) {
+                return Err(format!("Mapped column {} not found in data", column).into());
+            }
+        }
+
+        let mut lineage = Vec::new();
+        let mut expressions = Vec::new();
+        for (name, data_type) in &columns {
+            let selected = match &mapping.select {
+                Some(select) => select.contains(name) || geom_columns.contains(name),
+                None => true,
+            };
+            if !selected {
+                lineage.push(LineageEntry::new(
+                    "column_mapping",
+                    Some(name),
+                    "Dropped column",
+                ));
+                continue;
+            }
+
+            let mut expression = format!("\"{}\"", name);
+            if let Some((_, new_type)) = mapping.type_overrides.iter().find(|(c, _)| c == name) {
+                expression = format!("CAST({} AS {})", expression, new_type);
+                lineage.push(LineageEntry::new(
+                    "column_mapping",
+                    Some(name),
+                    &format!("Cast from {} to {}", data_type, new_type),
+                ));
+            }
+            let output_name = match mapping.rename.iter().find(|(c, _)| c == name) {
+                Some((_, new_name)) => {
+                    lineage.push(LineageEntry::new(
+                        "column_mapping",
+                        Some(name),
+                        &format!("Renamed to {}", new_name),
+                    ));
+                    new_name
+                }
+                None => name,
+            };
+            expressions.push(format!("{} AS \"{}\"", expression, output_name));
+        }
+
+        // Rebuild the data table with the mapped columns
+        let mapped_table = format!("mapped_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT {} FROM {};",
+                mapped_table,
+                expressions.join(", "),
+                self.data_table
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                mapped_table, self.data_table
+            ),
+            [],
+        )?;
+        Ok(lineage)
+    }
+
+    fn normalize_column_names(
+        &self,
+        normalization: &NameNormalization,
+    ) -> Result<(Vec<NameMapping>, Vec<LineageEntry>), Box<dyn Error>> {
+        let columns = self
+            .data_columns()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        let normalized = normalization.normalize_all(&columns);
+        if normalized == columns {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let mut mappings = Vec::new();
+        let mut lineage = Vec::new();
+        let mut expressions = Vec::new();
+        for (name, new_name) in columns.iter().zip(&normalized) {
+            expressions.push(format!(
+                "\"{}\" AS \"{}\"",
+                name.replace('"', "\"\""),
+                new_name
+            ));
+            if name != new_name {
+                lineage.push(LineageEntry::new(
+                    "name_normalization",
+                    Some(name),
+                    &format!("Renamed to {}", new_name),
+                ));
+                mappings.push(NameMapping {
+                    original: name.clone(),
+                    normalized: new_name.clone(),
+                });
+            }
+        }
+
+        let normalized_table = format!("normalized_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT {} FROM {};",
+                normalized_table,
+                expressions.join(", "),
+                self.data_table
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                normalized_table, self.data_table
+            ),
+            [],
+        )?;
+        Ok((mappings, lineage))
+    }
+
+    fn drop_duplicate_rows(&self) -> Result<LineageEntry, Box<dyn Error>> {
+        let count = |table: &str| -> Result<i64, Box<dyn Error>> {
+            Ok(self
+                .conn
+                .query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                    row.get(0)
+                })?)
+        };
+        let rows = count(&self.data_table)?;
+
+        // Keeps the first row of each group, in the order the rows were read - NULLs count as equal
+        let distinct_table = format!("distinct_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT * EXCLUDE (gridwalk_first_row) FROM (
+                    SELECT *, min(rowid) AS gridwalk_first_row FROM {} GROUP BY ALL
+                ) ORDER BY gridwalk_first_row;",
+                distinct_table, self.data_table
+            ),
+            [],
+        )?;
+        let dropped = rows - count(&distinct_table)?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                distinct_table, self.data_table
+            ),
+            [],
+        )?;
+        Ok(LineageEntry::new(
+            "deduplication",
+            None,
+            &format!("Dropped {} duplicate rows", dropped),
+        ))
+    }
+
+    // Hash keys and file sink row numbers are added here - a PostGIS serial key is added once the
+    // table is created, so Postgres numbers the rows
+    fn add_primary_key(&self, primary_key: &PrimaryKey) -> Result<LineageEntry, Box<dyn Error>> {
+        let columns = self.data_columns()?;
+        let key_column = primary_key.column();
+        if columns
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(key_column))
+        {
+            return Err(format!(
+                "Primary key column {} already exists in the data",
+                key_column
+            )
+            .into());
+        }
+
+        let (expression, description) = match primary_key {
+            PrimaryKey::Serial(_) if self.options.sink == Sink::PostGis => {
+                return Ok(LineageEntry::new(
+                    "primary_key",
+                    Some(key_column),
+                    "Added as a BIGSERIAL primary key in PostGIS",
+                ));
+            }
+            PrimaryKey::Serial(_) => (
+                "row_number() OVER (ORDER BY rowid)".to_string(),
+                "Added as the row number".to_string(),
+            ),
+            PrimaryKey::Hash {
+                columns: hashed, ..
+            } => {
+                for column in hashed {
+                    if !columns.iter().any(|(name, _)| name == column) {
+                        return Err(
+                            format!("Primary key column {} not found in data", column).into()
+                        );
+                    }
+                }
+                let hashed = if hashed.is_empty() {
+                    columns.iter().map(|(name, _)| name.clone()).collect()
+                } else {
+                    hashed.clone()
+                };
+                // NULLs are marked so that (NULL, 'a') and ('', 'a') hash differently
+                let values = hashed
+                    .iter()
+                    .map(|column| format!("coalesce(\"{}\"::VARCHAR, '\\N')", column))
+                    .collect::<Vec<_>>();
+                (
+                    format!("md5(concat_ws(chr(31), {}))", values.join(", ")),
+                    format!("Added as an md5 hash of {}", hashed.join(", ")),
+                )
+            }
+        };
+
+        let keyed_table = format!("keyed_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT {} AS \"{}\", * FROM {};",
+                keyed_table, expression, key_column, self.data_table
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!("ALTER TABLE {} RENAME TO {};", keyed_table, self.data_table),
+            [],
+        )?;
+        Ok(LineageEntry::new(
+            "primary_key",
+            Some(key_column),
+            &description,
+        ))
+    }
+
+    // Returns any coordinate columns found by detection alongside the lineage of building the points
+    fn construct_points(
+        &mut self,
+    ) -> Result<(Option<PointColumns>, Vec<LineageEntry>), Box<dyn Error>> {
+        let mut detected = None;
+        let points = match &self.options.point_columns {
+            Some(points) => points.clone(),
+            None if self.options.detect_point_columns && self.geom_columns()?.is_empty() => {
+                let Some(points) = self.detect_point_columns()? else {
+                    return Ok((None, Vec::new()));
+                };
+                detected = Some(points.clone());
+                if !self.options.confirm_point_columns {
+                    println!(
+                        "Detected coordinate columns {} and {} (EPSG:{}) - confirm them to build point geometries",
+                        points.x_column, points.y_column, points.srid
+                    );
+                    return Ok((detected, Vec::new()));
+                }
+                points
+            }
+            None => return Ok((None, Vec::new())),
+        };
+        require_spatial("Building point geometries")?;
+
+        let columns = self.data_columns()?;
+        for column in [&points.x_column, &points.y_column] {
+            if !columns.iter().any(|(name, _)| name == column) {
+                return Err(format!("Coordinate column {} not found in data", column).into());
+            }
+        }
+        if columns
+            .iter()
+            .any(|(name, _)| *name == points.geometry_column)
+        {
+            return Err(format!(
+                "Cannot build point geometries: the data already has a column named {}",
+                points.geometry_column
+            )
+            .into());
+        }
+
+        let exclude = if points.keep_coordinate_columns {
+            String::new()
+        } else {
+            format!(
+                " EXCLUDE (\"{}\", \"{}\")",
+                points.x_column, points.y_column
+            )
+        };
+        let points_table = format!("points_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT *{}, ST_Point(CAST(\"{}\" AS DOUBLE), CAST(\"{}\" AS DOUBLE)) AS \"{}\" FROM {};",
+                points_table,
+                exclude,
+                points.x_column,
+                points.y_column,
+                points.geometry_column,
+                self.data_table
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                points_table, self.data_table
+            ),
+            [],
+        )?;
+        self.source_crs = Some(points.srid.clone());
+
+        Ok((
+            detected,
+            vec![LineageEntry::new(
+                "point_construction",
+                Some(&points.geometry_column),
+                &format!(
+                    "Built points from {} and {} in EPSG:{}",
+                    points.x_column, points.y_column, points.srid
+                ),
+            )],
+        ))
+    }
+
+    // Drops rows whose first geometry does not intersect the filter area, including rows without one
+    fn apply_spatial_filter(&self, filter: &SpatialFilter) -> Result<LineageEntry, Box<dyn Error>> {
+        let geom_columns = self.geom_columns()?;
+        let Some(geom_column) = geom_columns.first() else {
+            return Err("A spatial filter needs a geometry column".into());
+        };
+
+        let current_crs = self.current_crs()?;
+        let area = format!(
+            "ST_GeomFromText('{}')",
+            filter.area.to_wkt().replace('\'', "''")
+        );
+        let area = if current_crs == filter.srid {
+            area
+        } else {
+            format!(
+                "ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)",
+                area, filter.srid, current_crs
+            )
+        };
+
+        let row_count = |table: &str| {
+            self.conn
+                .query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                    row.get::<_, i64>(0)
+                })
+        };
+        let before = row_count(&self.data_table)?;
+        let clipped_table = format!("clipped_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT * FROM {} WHERE ST_Intersects({}, {});",
+                clipped_table,
+                self.data_table,
+                catalog::identifier(geom_column),
+                area
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                clipped_table, self.data_table
+            ),
+            [],
+        )?;
+        let after = row_count(&self.data_table)?;
+
+        println!("Spatial filter kept {} of {} features", after, before);
+        Ok(LineageEntry::new(
+            "spatial_filter",
+            Some(geom_column),
+            &format!(
+                "Dropped {} features outside the filter area (EPSG:{})",
+                before - after,
+                filter.srid
+            ),
+        ))
+    }
+
+    fn detect_point_columns(&self) -> Result<Option<PointColumns>, Box<dyn Error>> {
+        let numeric_columns = self
+            .data_columns()?
+            .into_iter()
+            .filter(|(_, data_type)| units::is_numeric_type(data_type))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        let Some((x_column, y_column)) = points::find_coordinate_pair(&numeric_columns) else {
+            return Ok(None);
+        };
+
+        // Values within longitude/latitude ranges are taken as WGS84, anything else needs the source CRS
+        let geographic: bool = self.conn.query_row(
+            &format!(
+                "SELECT coalesce(min(\"{}\") >= -180 AND max(\"{}\") <= 180 AND min(\"{}\") >= -90 AND max(\"{}\") <= 90, false) FROM {};",
+                x_column, x_column, y_column, y_column, self.data_table
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        let srid = match (&self.options.source_crs, geographic) {
+            (Some(crs), _) => crs.clone(),
+            (None, true) => "4326".to_string(),
+            (None, false) => {
+                println!(
+                    "Coordinate columns {} and {} are not longitude/latitude - set source_crs to build points from them",
+                    x_column, y_column
+                );
+                return Ok(None);
+            }
+        };
+        Ok(Some(PointColumns::new(&x_column, &y_column, &srid)))
+    }
+
+    // Replaces the data table with the result of the caller's query, once it is known to be a single
+    // SELECT whose result can be stored and loaded
+    fn apply_sql_transform(&self, sql: &str) -> Result<LineageEntry, Box<dyn Error>> {
+        let sql = sql.trim().trim_end_matches(';');
+        let query = format!(
+            "WITH data AS (SELECT * FROM {}) SELECT * FROM ({})",
+            self.data_table, sql
+        );
+
+        // Preparing rejects several statements, and DESCRIBE anything that is not a query
+        let mut stmt = self
+            .conn
+            .prepare(&format!("DESCRIBE {};", query))
+            .map_err(|e| format!("SQL transform must be a single SELECT over data: {}", e))?;
+        let columns = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut names = Vec::new();
+        for (name, data_type) in &columns {
+            if names.contains(&name.to_lowercase()) {
+                return Err(format!("SQL transform returns column {} more than once", name).into());
+            }
+            names.push(name.to_lowercase());
+
+            // Nested types other than lists have no Postgres equivalent the transfer can write
+            let nested = ["STRUCT", "MAP", "UNION"]
+                .iter()
+                .any(|nested| data_type.starts_with(nested));
+            if nested && self.options.sink == Sink::PostGis {
+                return Err(format!(
+                    "SQL transform column {} has type {}, which cannot be loaded into PostGIS",
+                    name, data_type
+                )
+                .into());
+            }
+        }
+
+        let transformed_table = format!("sql_{}", self.data_table);
+        self.conn.execute(
+            &format!("CREATE TABLE {} AS {};", transformed_table, query),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                transformed_table, self.data_table
+            ),
+            [],
+        )?;
+
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT count(*) FROM {};", self.data_table),
+            [],
+            |row| row.get(0),
+        )?;
+        println!(
+            "SQL transform produced {} rows and {} columns",
+            row_count,
+            columns.len()
+        );
+        Ok(LineageEntry::new("sql_transform", None, sql))
+    }
+
+    fn apply_resample(&self, resample: &Resample) -> Result<LineageEntry, Box<dyn Error>> {
+        if resample.aggregations.is_empty() {
+            return Err("Resampling needs at least one aggregation".into());
+        }
+        let columns = self.data_columns()?;
+        let referenced = std::iter::once(&resample.time_column)
+            .chain(&resample.group_by)
+            .chain(
+                resample
+                    .aggregations
+                    .iter()
+                    .map(|aggregation| &aggregation.column),
+            );
+        for column in referenced {
+            if !columns.iter().any(|(name, _)| name == column) {
+                return Err(format!("Resample column {} not found in data", column).into());
+            }
+        }
+
+        let mut expressions = vec![format!(
+            "time_bucket(INTERVAL '{}', CAST(\"{}\" AS TIMESTAMP)) AS \"{}\"",
+            resample.interval, resample.time_column, resample.time_column
+        )];
+        let mut group_by = vec!["1".to_string()];
+        for (i, column) in resample.group_by.iter().enumerate() {
+            expressions.push(format!("\"{}\"", column));
+            group_by.push((i + 2).to_string());
+        }
+        for column in self.geom_columns()? {
+            expressions.push(format!("any_value(\"{}\") AS \"{}\"", column, column));
+        }
+        for aggregation in &resample.aggregations {
+            expressions.push(format!(
+                "{}(\"{}\") AS \"{}_{}\"",
+                aggregation.function.sql(),
+                aggregation.column,
+                aggregation.column,
+                aggregation.function.suffix()
+            ));
+        }
+
+        let row_count = |table: &str| -> Result<i64, Box<dyn Error>> {
+            Ok(self
+                .conn
+                .query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                    row.get(0)
+                })?)
+        };
+        let rows_before = row_count(&self.data_table)?;
+
+        // Rebuild the data table with one row per series and bucket
+        let resampled_table = format!("resampled_{}", self.data_table);
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT {} FROM {} GROUP BY {} ORDER BY {};",
+                resampled_table,
+                expressions.join(", "),
+                self.data_table,
+                group_by.join(", "),
+                group_by.join(", ")
+            ),
+            [],
+        )?;
+        self.conn
+            .execute(&format!("DROP TABLE {};", self.data_table), [])?;
+        self.conn.execute(
+            &format!(
+                "ALTER TABLE {} RENAME TO {};",
+                resampled_table, self.data_table
+            ),
+            [],
+        )?;
+
+        let rows_after = row_count(&self.data_table)?;
+        Ok(LineageEntry::new(
+            "resample",
+            Some(&resample.time_column),
+            &format!(
+                "Resampled into {} buckets grouped by [{}]: {} rows to {}",
+                resample.interval,
+                resample.group_by.join(", "),
+                rows_before,
+                rows_after
+            ),
+        ))
+    }
+
+    fn query_and_print_schema(&self) -> Result<Arc<Schema>, Box<dyn Error>> {
+        // Create and prep query
+        let query = format!("SELECT * FROM {} LIMIT 10", self.data_table);
+        let mut stmt = self.conn.prepare(&query)?;
+
+        // Run query
+        let arrow_result = stmt.query_arrow([])?;
+        let schema = arrow_result.get_schema();
+
+        // Print and return schema
+        println!("Schema: {:?}", schema);
+        Ok(schema)
+    }
+
+    fn current_crs(&self) -> Result<String, Box<dyn Error>> {
+        match &self.source_crs {
+            Some(crs) => Ok(crs.clone()),
+            None => self.get_crs_number(&self.file_path),
+        }
+    }
+
+    fn get_crs_number(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        // OSM coordinates are always WGS84
+        if self.file_type == FileType::OsmPbf {
+            return Ok("4326".to_string());
+        }
+
+        // Sources read without GDAL carry no CRS DuckDB can see
+        if !matches!(
+            self.file_type,
+            FileType::Geopackage
+                | FileType::Shapefile
+                | FileType::Geojson
+                | FileType::Excel
+                | FileType::Gpx
+        ) {
+            let handler_crs = self
+                .source_handler
+                .as_ref()
+                .and_then(|handler| handler.crs(file_path));
+            return self
+                .options
+                .source_crs
+                .clone()
+                .or(handler_crs)
+                .ok_or_else(|| {
+                    format!(
+                    "CRS of '{}' is not recorded in the file, set it with the source_crs option",
+                    file_path
+                )
+                    .into()
+                });
+        }
+
+        // Let and prep query
+        let query = format!(
+            "SELECT {}.geometry_fields[1].crs.auth_code AS crs_number
+            FROM st_read_meta('{}');",
+            self.layer_meta(),
+            file_path
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+
+        // Run query and return CRS number
+        let mut rows = stmt.query([])?;
+        if let Some(row) = rows.next()? {
+            let crs_number: String = row.get(0)?;
+            Ok(crs_number)
+        } else {
+            Err(format!("CRS not found for the following file: {}", file_path).into())
+        }
+    }
+
+    // st_read_meta expression for the layer being read
+    fn layer_meta(&self) -> String {
+        match &self.options.layer {
+            Some(layer) => format!(
+                "list_filter(layers, l -> l.name = {})[1]",
+                catalog::literal(layer)
+            ),
+            None => "layers[1]".to_string(),
+        }
+    }
+
+    fn geom_columns(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let query = "SELECT column_name FROM information_schema.columns WHERE table_name = ? AND data_type = 'GEOMETRY'";
+        let mut stmt = self.conn.prepare(query)?;
+        let mut rows = stmt.query([self.data_table.as_str()])?;
+        let mut geom_columns = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let column_name: String = row.get(0)?;
+            geom_columns.push(column_name);
+        }
+        Ok(geom_columns)
+    }
+
+    fn transform_geom_columns(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let geom_columns = self.geom_columns()?;
+
+        // Non-spatial data such as a plain CSV is carried over as it is, with no CRS to look up
+        if geom_columns.is_empty() {
+            println!("No geometry columns - loading attributes only");
+            self.conn.execute(
+                &format!(
+                    "CREATE TABLE {} AS SELECT * FROM {};",
+                    self.transformed_table, self.data_table
+                ),
+                [],
+            )?;
+            return Ok(geom_columns);
+        }
+        println!("Geometry columns: {:?}", &geom_columns);
+
+        // Every geometry column is converted in one pass, each from its own CRS
+        let encoding = self.options.sink.geometry_encoding();
+        let mut expressions = Vec::new();
+        for ((column, current_crs), geometry) in geom_columns
+            .iter()
+            .zip(self.geometry_column_crs(&geom_columns)?)
+            .zip(self.target_geometries(&geom_columns)?)
+        {
+            println!("Current CRS for column {}: {}", column, current_crs);
+            expressions.push(match encoding {
+                GeometryEncoding::Wkb => format!(
+                    "ST_AsWKB({})::BLOB AS {}",
+                    geometry,
+                    catalog::identifier(&encoding.stored_column(column))
+                ),
+                GeometryEncoding::Native => {
+                    format!("{} AS {}", geometry, catalog::identifier(column))
+                }
+            });
+        }
+
+        let create_table_query = match encoding {
+            GeometryEncoding::Wkb => format!(
+                "CREATE TABLE {} AS SELECT * EXCLUDE ({}), {} FROM {};",
+                self.transformed_table,
+                geom_columns
+                    .iter()
+                    .map(|column| catalog::identifier(column))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                expressions.join(", "),
+                self.data_table
+            ),
+            GeometryEncoding::Native => format!(
+                "CREATE TABLE {} AS SELECT * REPLACE ({}) FROM {};",
+                self.transformed_table,
+                expressions.join(", "),
+                self.data_table
+            ),
+        };
+        self.conn.execute(&create_table_query, [])?;
+
+        Ok(geom_columns)
+    }
+
+    // Expressions giving each geometry column as it is stored - transformed to the target CRS, then simplified
+    fn target_geometries(&self, geom_columns: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+        let target_crs = &self.options.target_crs;
+        let mut geometries = Vec::new();
+        for (column, current_crs) in geom_columns
+            .iter()
+            .zip(self.geometry_column_crs(geom_columns)?)
+        {
+            let column = catalog::identifier(column);
+            let geometry = if current_crs == *target_crs {
+                column
+            } else {
+                format!(
+                    "ST_Transform({}, 'EPSG:{}', 'EPSG:{}', always_xy := true)",
+                    column, current_crs, target_crs
+                )
+            };
+            geometries.push(match &self.options.simplification {
+                Some(simplification) => simplification.apply(&geometry),
+                None => geometry,
+            });
+        }
+        Ok(geometries)
+    }
+
+    // CRS of each geometry column - GDAL records one per geometry field, in the order ST_Read returns them,
+    // and fields without one fall back to the CRS of the layer
+    fn geometry_column_crs(&self, geom_columns: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+        let current_crs = self.current_crs()?;
+        let gdal_source = matches!(
+            self.file_type,
+            FileType::Geopackage | FileType::Shapefile | FileType::Geojson | FileType::Gpx
+        );
+        if self.source_crs.is_some() || !gdal_source || geom_columns.len() == 1 {
+            return Ok(vec![current_crs; geom_columns.len()]);
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT field.crs.auth_code
+            FROM (SELECT unnest({}.geometry_fields) AS field FROM st_read_meta('{}'));",
+            self.layer_meta(),
+            self.file_path
+        ))?;
+        let field_crs = stmt
+            .query_map([], |row| row.get::<_, Option<String>>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if field_crs.len() != geom_columns.len() {
+            return Ok(vec![current_crs; geom_columns.len()]);
+        }
+        Ok(field_crs
+            .into_iter()
+            .map(|crs| crs.unwrap_or_else(|| current_crs.clone()))
+            .collect())
+    }
+
+    fn add_lon_lat_columns(
+        &self,
+        column_types: &[GeometryColumnType],
+    ) -> Result<Vec<LineageEntry>, Box<dyn Error>> {
+        let Some(point_column) = column_types
+            .iter()
+            .find(|column_type| column_type.geometry_type == "Point")
+        else {
+            println!("No Point geometry column found - longitude/latitude columns not added");
+            return Ok(Vec::new());
+        };
+
+        let existing: i64 = self.conn.query_row(
+            "SELECT count(*) FROM information_schema.columns WHERE table_name = ? AND column_name IN ('longitude', 'latitude')",
+            [self.transformed_table.as_str()],
+            |row| row.get(0),
+        )?;
+        if existing > 0 {
+            return Err("Cannot add longitude/latitude columns: the data already has a column with that name".into());
+        }
+
+        // Coordinates are always WGS84, whatever CRS the geometry is loaded in
+        let point = self
+            .options
+            .sink
+            .geometry_encoding()
+            .geometry(&point_column.column);
+        let point = if self.options.target_crs == "4326" {
+            point
+        } else {
+            format!(
+                "ST_Transform({}, 'EPSG:{}', 'EPSG:4326', always_xy := true)",
+                point, self.options.target_crs
+            )
+        };
+        for (column, function) in [("longitude", "ST_X"), ("latitude", "ST_Y")] {
+            self.conn.execute(
+                &format!(
+                    "ALTER TABLE {} ADD COLUMN {} DOUBLE;",
+                    self.transformed_table, column
+                ),
+                [],
+            )?;
+            self.conn.execute(
+                &format!(
+                    "UPDATE {} SET {} = {}({});",
+                    self.transformed_table, column, function, point
+                ),
+                [],
+            )?;
+        }
+
+        Ok(vec![LineageEntry::new(
+            "lon_lat_columns",
+            Some(&point_column.column),
+            "Added WGS84 longitude and latitude columns",
+        )])
+    }
+
+    // Repeats the PostGIS load against another database, recording a failure rather than returning it
+    fn load_replica(
+        &mut self,
+        connection: &str,
+        column_types: &[GeometryColumnType],
+        gate: &PriorityGate,
+    ) -> ReplicaResult {
+        let redacted = query_log::redact_secrets(connection);
+        println!("Loading {} into replica {}", self.table_name, redacted);
+        // The replica has an alias of its own, so the primary stays attached for other loads sharing the database
+        self.postgres_connection = connection.to_string();
+        self.postgres_database = postgis_database_alias(connection);
+        let mut replica = ReplicaResult {
+            connection: redacted,
+            retries: 0,
+            verification: None,
+            error: None,
+        };
+
+        let outcome = (|| -> Result<(), Box<dyn Error>> {
+            self.options.cancellation.check()?;
+            let report = self.load_data_postgis(column_types, gate)?;
+            replica.retries += report.retries;
+            replica.verification = report.verification;
+            if self.options.write_layer_extents && !column_types.is_empty() {
+                self.write_layer_extents(&self.layer_extents(column_types)?)?;
+            }
+            if self.options.write_layer_styles {
+                self.write_layer_styles(column_types)?;
+            }
+            if self.options.write_catalog {
+                self.write_catalog_entry(column_types)?;
+            }
+            Ok(())
+        })();
+        if let Err(e) = outcome {
+            println!("Failed to load replica {}: {}", replica.connection, e);
+            replica.error = Some(e.to_string());
+        }
+        replica
+    }
+
+    fn attach_postgis(&self) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            &postgis_attach_query(&self.postgres_connection, &self.postgres_database),
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn generate_schema_contract(&mut self) -> Result<SchemaContract, Box<dyn Error>> {
+        self.create_data_table()?;
+
+        let columns = self
+            .data_columns()?
+            .into_iter()
+            .map(|(name, data_type)| ContractColumn { name, data_type })
+            .collect();
+
+        // Only pin a geometry type when the data has exactly one
+        let geometry_types = self.geometry_types()?;
+        let geometry_type = match geometry_types.as_slice() {
+            [geometry_type] => Some(geometry_type.clone()),
+            _ => None,
+        };
+        let srid = if self.geom_columns()?.is_empty() {
+            None
+        } else {
+            Some(self.current_crs()?)
+        };
+
+        Ok(SchemaContract {
+            columns,
+            geometry_type,
+            srid,
+            allow_extra_columns: false,
+        })
+    }
+
+    fn inspect(&mut self) -> Result<FileInfo, Box<dyn Error>> {
+        self.create_data_table()?;
+
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT count(*) FROM {};", self.data_table),
+            [],
+            |row| row.get(0),
+        )?;
+        let geometry_columns = self.geom_columns()?;
+        let crs = if geometry_columns.is_empty() {
+            None
+        } else {
+            Some(self.current_crs()?)
+        };
+        let columns = self.data_columns()?;
+        let profile = match &self.options.profile {
+            Some(profile_options) => {
+                println!("Profiling {} columns...", columns.len());
+                Some(profile::profile_table(
+                    &self.conn,
+                    &self.data_table,
+                    &columns,
+                    &geometry_columns,
+                    profile_options,
+                )?)
+            }
+            None => None,
+        };
+
+        Ok(FileInfo {
+            file_type: self.file_type_name(),
+            row_count: row_count as u64,
+            columns,
+            geometry_types: self.geometry_types()?,
+            geometry_columns,
+            crs,
+            source_encodings: self
+                .source_encodings
+                .iter()
+                .map(|encoding| (encoding.file_path.clone(), encoding.encoding.clone()))
+                .collect(),
+            profile,
+        })
+    }
+
+    // Runs the checks of a load up to the transfer without writing anything, recording what fails
+    fn validate(&mut self) -> ValidationReport {
+        let mut report = ValidationReport {
+            file_type: Some(self.file_type_name()),
+            table_name: self.table_name.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = self.create_data_table() {
+            report.push(
+                ValidationCheck::Detection,
+                IssueSeverity::Error,
+                IssueCode::UnreadableSource,
+                None,
+                &format!("Failed to read the source: {}", e),
+            );
+            return report;
+        }
+        let columns = match self.validate_source(&mut report) {
+            Ok(columns) => columns,
+            Err(e) => {
+                report.push(
+                    ValidationCheck::Schema,
+                    IssueSeverity::Error,
+                    IssueCode::UnreadableSource,
+                    None,
+                    &format!("Failed to check the source: {}", e),
+                );
+                return report;
+            }
+        };
+
+        if self.options.sink == Sink::PostGis {
+            let target = self
+                .attach_postgis()
+                .and_then(|_| self.validate_target(&mut report, &columns));
+            if let Err(e) = target {
+                report.push(
+                    ValidationCheck::Target,
+                    IssueSeverity::Error,
+                    IssueCode::TargetUnavailable,
+                    None,
+                    &format!("Failed to check the target table: {}", e),
+                );
+            }
+        }
+        report
+    }
+
+    // Returns the column names the load would write
+    fn validate_source(
+        &self,
+        report: &mut ValidationReport,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT count(*) FROM {};", self.data_table),
+            [],
+            |row| row.get(0),
+        )?;
+        report.row_count = Some(row_count as u64);
+        if row_count == 0 {
+            report.push(
+                ValidationCheck::Schema,
+                IssueSeverity::Warning,
+                IssueCode::EmptySource,
+                None,
+                "The source has no rows",
+            );
+        }
+        report.columns = self.data_columns()?;
+        report.geometry_columns = self.geom_columns()?;
+
+        // CRS first, as the schema contract may pin it
+        if !report.geometry_columns.is_empty() {
+            match self.geometry_column_crs(&report.geometry_columns) {
+                Ok(column_crs) => self.validate_transforms(report, &column_crs),
+                Err(e) => report.push(
+                    ValidationCheck::Crs,
+                    IssueSeverity::Error,
+                    IssueCode::MissingCrs,
+                    None,
+                    &e.to_string(),
+                ),
+            }
+        }
+        if let Some(contract) = &self.options.schema_contract {
+            let violations = contract.violations(
+                &report.columns,
+                &self.geometry_types()?,
+                report.crs.as_deref(),
+            );
+            for violation in violations {
+                report.push(
+                    ValidationCheck::Schema,
+                    IssueSeverity::Error,
+                    IssueCode::ContractViolation,
+                    None,
+                    &violation,
+                );
+            }
+        }
+        for column in report.geometry_columns.clone() {
+            self.validate_geometry_column(report, &column)?;
+        }
+        Ok(self.validate_names(report))
+    }
+
+    fn validate_transforms(&self, report: &mut ValidationReport, column_crs: &[String]) {
+        report.crs = column_crs.first().cloned();
+        let target_crs = &self.options.target_crs;
+        for (column, crs) in report.geometry_columns.clone().iter().zip(column_crs) {
+            if crs == target_crs {
+                continue;
+            }
+            let transform = self.conn.execute(
+                &format!(
+                    "SELECT ST_Transform(ST_Point(0, 0), 'EPSG:{}', 'EPSG:{}', always_xy := true);",
+                    crs, target_crs
+                ),
+                [],
+            );
+            if let Err(e) = transform {
+                report.push(
+                    ValidationCheck::Crs,
+                    IssueSeverity::Error,
+                    IssueCode::UnsupportedTransform,
+                    Some(column),
+                    &format!(
+                        "Can't transform from EPSG:{} to EPSG:{}: {}",
+                        crs, target_crs, e
+                    ),
+                );
+            }
+        }
+    }
+
+    fn validate_geometry_column(
+        &self,
+        report: &mut ValidationReport,
+        column: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let invalid_count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT count(*) FROM {} WHERE NOT ST_IsValid({});",
+                self.data_table,
+                catalog::identifier(column)
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        if invalid_count > 0 {
+            let (severity, outcome) = match self.options.geometry_validation {
+                None => (IssueSeverity::Warning, "would be loaded as they are"),
+                Some(GeometryValidationPolicy::Skip) => {
+                    (IssueSeverity::Warning, "would be dropped with their rows")
+                }
+                Some(GeometryValidationPolicy::Repair) => (
+                    IssueSeverity::Warning,
+                    "would be repaired with ST_MakeValid",
+                ),
+                Some(GeometryValidationPolicy::Fail) => {
+                    (IssueSeverity::Error, "would fail the load")
+                }
+            };
+            report.push(
+                ValidationCheck::Geometry,
+                severity,
+                IssueCode::InvalidGeometries,
+                Some(column),
+                &format!("{} invalid geometries {}", invalid_count, outcome),
+            );
+        }
+
+        let geometry_types = self.column_geometry_types(&catalog::identifier(column))?;
+        let (geometry_type, _) =
+            geometry::resolve_column_type(&geometry_types, self.options.promote_to_multi);
+        if geometry_type == "Geometry" && geometry_types.len() > 1 {
+            report.push(
+                ValidationCheck::Geometry,
+                IssueSeverity::Warning,
+                IssueCode::MixedGeometryTypes,
+                Some(column),
+                &format!(
+                    "Holds {}, so it would be loaded as a generic geometry column",
+                    geometry_types.join(", ")
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    // Names of the table and its columns as the load would write them, after the column mapping and
+    // name normalization - columns added later, e.g. by sql_transform, are not known before the load
+    fn validate_names(&self, report: &mut ValidationReport) -> Vec<String> {
+        let mapping = &self.options.column_mapping;
+        let mut columns = Vec::new();
+        let referenced = mapping
+            .select
+            .iter()
+            .flatten()
+            .chain(mapping.rename.iter().map(|(column, _)| column))
+            .chain(mapping.type_overrides.iter().map(|(column, _)| column));
+        for column in referenced {
+            if !report.columns.iter().any(|(name, _)| name == column) {
+                report.push(
+                    ValidationCheck::Schema,
+                    IssueSeverity::Error,
+                    IssueCode::UnknownColumn,
+                    Some(column),
+                    &format!("Mapped column {} is not in the source", column),
+                );
+            }
+        }
+        for (name, _) in &report.columns {
+            let selected = match &mapping.select {
+                Some(select) => select.contains(name) || report.geometry_columns.contains(name),
+                None => true,
+            };
+            if selected {
+                let renamed = mapping.rename.iter().find(|(column, _)| column == name);
+                columns.push(renamed.map_or(name, |(_, new_name)| new_name).clone());
+            }
+        }
+
+        let mut names = vec![(self.table_name.clone(), None)];
+        match &self.options.name_normalization {
+            Some(normalization) => {
+                let table_name = normalization.normalize(&self.table_name);
+                report.table_name = table_name.clone();
+                names[0].1 = Some(table_name);
+                let normalized = normalization.normalize_all(&columns);
+                names.extend(columns.into_iter().zip(normalized.into_iter().map(Some)));
+            }
+            None => names.extend(columns.into_iter().map(|column| (column, None))),
+        }
+
+        let mut written = Vec::new();
+        for (i, (name, normalized)) in names.iter().enumerate() {
+            // The table is reported without a column
+            let column = (i > 0).then_some(name.as_str());
+            let what = match column {
+                Some(_) => "Column",
+                None => "Table",
+            };
+            let final_name = match normalized {
+                Some(normalized) if normalized != name => {
+                    report.push(
+                        ValidationCheck::Naming,
+                        IssueSeverity::Warning,
+                        IssueCode::RenamedIdentifier,
+                        column,
+                        &format!("{} {} would be renamed to {}", what, name, normalized),
+                    );
+                    normalized
+                }
+                _ => name,
+            };
+            if final_name.len() > validation::MAX_IDENTIFIER_LENGTH {
+                report.push(
+                    ValidationCheck::Naming,
+                    IssueSeverity::Warning,
+                    IssueCode::LongIdentifier,
+                    column,
+                    &format!(
+                        "{} {} is longer than {} bytes and would be truncated by Postgres",
+                        what,
+                        final_name,
+                        validation::MAX_IDENTIFIER_LENGTH
+                    ),
+                );
+            } else if !validation::is_plain_identifier(final_name) {
+                report.push(
+                    ValidationCheck::Naming,
+                    IssueSeverity::Warning,
+                    IssueCode::QuotedIdentifier,
+                    column,
+                    &format!(
+                        "{} {} would have to be quoted in Postgres queries",
+                        what, final_name
+                    ),
+                );
+            }
+            if column.is_none() {
+                continue;
+            }
+            let truncated = naming::truncate(final_name, validation::MAX_IDENTIFIER_LENGTH);
+            if written.iter().any(|other: &String| {
+                naming::truncate(other, validation::MAX_IDENTIFIER_LENGTH) == truncated
+            }) {
+                report.push(
+                    ValidationCheck::Naming,
+                    IssueSeverity::Error,
+                    IssueCode::DuplicateColumn,
+                    column,
+                    &format!("Column {} would have the same name as another column", name),
+                );
+            }
+            written.push(final_name.clone());
+        }
+        written
+    }
+
+    // Table conflicts and privileges in the PostGIS database, with the database already attached
+    fn validate_target(
+        &self,
+        report: &mut ValidationReport,
+        columns: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let schema = self.options.schema.as_deref();
+        let table_name = report.table_name.clone();
+        let qualified_table = postgis::qualified_table(schema, &table_name);
+        let exists =
+            postgis::table_exists(&self.conn, &self.postgres_database, schema, &table_name)?;
+        report.table_exists = Some(exists);
+
+        if exists && self.options.load_mode == LoadMode::Replace {
+            report.push(
+                ValidationCheck::Target,
+                IssueSeverity::Warning,
+                IssueCode::TableReplaced,
+                None,
+                &format!("Table {} exists and would be replaced", qualified_table),
+            );
+        }
+        if exists && self.options.load_mode == LoadMode::Append {
+            self.validate_append_columns(report, &qualified_table, columns)?;
+        }
+
+        // A schema that doesn't exist yet is created by the load, which needs CREATE on the database
+        let schema_name = schema.map_or("current_schema()".to_string(), catalog::literal);
+        let query = format!(
+            "SELECT
+                coalesce((SELECT has_schema_privilege(oid, 'CREATE') FROM pg_namespace WHERE nspname = {}), has_database_privilege(current_database(), 'CREATE')),
+                coalesce((SELECT has_schema_privilege(oid, 'CREATE') FROM pg_namespace WHERE nspname = {}), has_database_privilege(current_database(), 'CREATE')),
+                coalesce((SELECT pg_has_role(relowner, 'USAGE') FROM pg_class WHERE oid = to_regclass({})), true),
+                coalesce(has_table_privilege(to_regclass({}), 'INSERT'), true)",
+            schema_name,
+            catalog::literal(staging::STAGING_SCHEMA),
+            catalog::literal(&qualified_table),
+            catalog::literal(&qualified_table)
+        );
+        let (create_table, create_staging, owns_table, insert): (bool, bool, bool, bool) =
+            self.conn.query_row(
+                &format!(
+                    "SELECT * FROM postgres_query('{}', '{}');",
+                    self.postgres_database,
+                    query.replace('\'', "''")
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
+
+        let mut denied = Vec::new();
+        if !create_staging {
+            denied.push(format!(
+                "create staging tables in schema {}",
+                staging::STAGING_SCHEMA
+            ));
+        }
+        match (exists, self.options.load_mode) {
+            (false, _) if !create_table => {
+                denied.push(format!("create table {}", qualified_table));
+            }
+            // Only the owner of a table may drop it
+            (true, LoadMode::Replace) if !owns_table || !create_table => {
+                denied.push(format!("replace table {}", qualified_table));
+            }
+            (true, LoadMode::Append) if !insert => {
+                denied.push(format!("insert into table {}", qualified_table));
+            }
+            _ => {}
+        }
+        for action in denied {
+            report.push(
+                ValidationCheck::Target,
+                IssueSeverity::Error,
+                IssueCode::PermissionDenied,
+                None,
+                &format!("The Postgres user may not {}", action),
+            );
+        }
+        Ok(())
+    }
+
+    // Names of the columns an append would write against those of the existing table
+    fn validate_append_columns(
+        &self,
+        report: &mut ValidationReport,
+        qualified_table: &str,
+        columns: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let query = format!(
+            "SELECT attname::text FROM pg_attribute WHERE attrelid = to_regclass({}) AND attnum > 0 AND NOT attisdropped ORDER BY attnum",
+            catalog::literal(qualified_table)
+        );
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT * FROM postgres_query('{}', '{}');",
+            self.postgres_database,
+            query.replace('\'', "''")
+        ))?;
+        let table_columns = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let add_columns = self
+            .options
+            .schema_evolution
+            .as_ref()
+            .is_some_and(|evolution| evolution.add_columns);
+        for column in columns {
+            if !table_columns.contains(column) {
+                let (severity, outcome) = if add_columns {
+                    (IssueSeverity::Warning, "would be added to it")
+                } else {
+                    (IssueSeverity::Error, "would fail the append")
+                };
+                report.push(
+                    ValidationCheck::Target,
+                    severity,
+                    IssueCode::ColumnMismatch,
+                    Some(column),
+                    &format!(
+                        "Column {} is not in {} and {}",
+                        column, qualified_table, outcome
+                    ),
+                );
+            }
+        }
+        for column in &table_columns {
+            if !columns.contains(column) {
+                report.push(
+                    ValidationCheck::Target,
+                    IssueSeverity::Warning,
+                    IssueCode::ColumnMismatch,
+                    Some(column),
+                    &format!(
+                        "Column {} of {} is not in the source and would be left NULL",
+                        column, qualified_table
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Compares the source as read, in the target CRS, with a GeoParquet export of the loaded table
+    fn compare_round_trip(
+        &mut self,
+        exported_path: &str,
+        options: &RoundTripOptions,
+    ) -> Result<RoundTripReport, Box<dyn Error>> {
+        self.create_data_table()?;
+        let geom_columns = self.geom_columns()?;
+        let source_table = format!("source_{}", self.data_table);
+        let round_trip_table = format!("round_trip_{}", self.data_table);
+
+        let target_crs = &self.options.target_crs;
+        let source_crs = if geom_columns.is_empty() {
+            target_crs.clone()
+        } else {
+            self.current_crs()?
+        };
+        let replace = if source_crs == *target_crs || geom_columns.is_empty() {
+            String::new()
+        } else {
+            let transforms = geom_columns
+                .iter()
+                .map(|column| {
+                    format!(
+                        "ST_Transform(\"{}\", 'EPSG:{}', 'EPSG:{}', always_xy := true) AS \"{}\"",
+                        column, source_crs, target_crs, column
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!(" REPLACE ({})", transforms.join(", "))
+        };
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT row_number() OVER () AS round_trip_row, *{} FROM {};",
+                source_table, replace, self.data_table
+            ),
+            [],
+        )?;
+        self.conn.execute(
+            &format!(
+                "CREATE TABLE {} AS SELECT row_number() OVER () AS round_trip_row, * FROM read_parquet('{}');",
+                round_trip_table, exported_path
+            ),
+            [],
+        )?;
+
+        let mut report = RoundTripReport::default();
+        for (table, count) in [
+            (&source_table, &mut report.source_rows),
+            (&round_trip_table, &mut report.round_trip_rows),
+        ] {
+            let rows: i64 =
+                self.conn
+                    .query_row(&format!("SELECT count(*) FROM {};", table), [], |row| {
+                        row.get(0)
+                    })?;
+            *count = rows as u64;
+        }
+
+        // Postgres folds unquoted names to lower case, so columns are matched case-insensitively
+        let key = options.key_column.as_deref().unwrap_or("round_trip_row");
+        let round_trip_columns = self.columns_of(&round_trip_table)?;
+        for (column, data_type) in self.columns_of(&source_table)? {
+            if column == "round_trip_row" {
+                continue;
+            }
+            let Some((round_trip_column, _)) = round_trip_columns
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&column))
+            else {
+                report.missing_columns.push(column);
+                continue;
+            };
+
+            let source_value = format!("s.\"{}\"", column);
+            let geometry = geom_columns.contains(&column);
+            let matches = if geometry {
+                round_trip::geometry_matches(
+                    &source_value,
+                    &format!("ST_GeomFromWKB(r.\"{}\")", round_trip_column),
+                    options.tolerance,
+                )
+            } else {
+                round_trip::attribute_matches(
+                    &source_value,
+                    &format!("r.\"{}\"", round_trip_column),
+                    units::is_numeric_type(&data_type),
+                    options.tolerance,
+                )
+            };
+            let mismatched_rows: i64 = self.conn.query_row(
+                &format!(
+                    "SELECT count(*) FILTER (WHERE NOT ({})) FROM {} s JOIN {} r ON s.\"{}\" = r.\"{}\";",
+                    matches, source_table, round_trip_table, key, key
+                ),
+                [],
+                |row| row.get(0),
+            )?;
+            if mismatched_rows > 0 {
+                let mismatch = ColumnMismatch {
+                    column,
+                    mismatched_rows: mismatched_rows as u64,
+                };
+                if geometry {
+                    report.geometry_mismatches.push(mismatch);
+                } else {
+                    report.attribute_mismatches.push(mismatch);
+                }
+            }
+        }
+
+        for table in [&source_table, &round_trip_table] {
+            self.conn.execute(&format!("DROP TABLE {};", table), [])?;
+        }
+        Ok(report)
+    }
+
+    fn check_drift(&self, geom_columns: &[String]) -> Result<Option<DriftReport>, Box<dyn Error>> {
+        let Some(thresholds) = &self.options.drift_thresholds else {
+            return Ok(None);
+        };
+
+        // Appends keep the previous rows, and there is nothing to compare against on the first load
+        if self.options.load_mode == LoadMode::Append || !self.postgis_table_exists()? {
+            return Ok(None);
+        }
+
+        let previous_row_count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT count(*) FROM {}.{};",
+                self.postgres_database,
+                self.postgis_table()
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        let row_count: i64 = self.conn.query_row(
+            &format!("SELECT count(*) FROM {};", self.transformed_table),
+            [],
+            |row| row.get(0),
+        )?;
+
+        // Extents are compared on the first geometry column
+        let (previous_extent, extent) = match geom_columns.first() {
+            Some(geom_column) => (
+                self.previous_extent(geom_column)?,
+                self.compute_extent(geom_column)?,
+            ),
+            None => (None, None),
+        };
+
+        let report = drift::assess_drift(
+            previous_row_count as u64,
+            row_count as u64,
+            previous_extent,
+            extent,
+            thresholds,
+        );
+        for reason in &report.reasons {
+            println!("Drift warning for table {}: {}", self.table_name, reason);
+        }
+        if report.suspicious
+            && self.options.drift_policy == DriftPolicy::RequireConfirmation
+            && !self.options.confirm_drift
+        {
+            return Err(format!(
+                "Load into {} differs from the previous version and needs confirmation: {}",
+                self.table_name,
+                report.reasons.join("; ")
+            )
+            .into());
+        }
+        Ok(Some(report))
+    }
+
+    // Name of the loaded table in Postgres statements, qualified when LoadOptions::schema is set
+    fn postgis_table(&self) -> String {
+        postgis::qualified_table(self.options.schema.as_deref(), &self.table_name)
+    }
+
+    fn postgis_table_exists(&self) -> Result<bool, Box<dyn Error>> {
+        postgis::table_exists(
+            &self.conn,
+            &self.postgres_database,
+            self.options.schema.as_deref(),
+            &self.table_name,
+        )
+    }
+
+    fn previous_extent(&self, geom_column: &str) -> Result<Option<Extent>, Box<dyn Error>> {
+        // The previous table may not have this column, in which case there is no extent to compare
+        match self.postgis_extent(geom_column) {
+            Ok(extent) => Ok(extent),
+            Err(e) => {
+                println!(
+                    "Could not read previous extent of {}.{}: {}",
+                    self.table_name, geom_column, e
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    fn postgis_extent(&self, geom_column: &str) -> Result<Option<Extent>, Box<dyn Error>> {
+        let extent = format!(
+            "SELECT ST_XMin(e)::float8, ST_YMin(e)::float8, ST_XMax(e)::float8, ST_YMax(e)::float8 FROM (SELECT ST_Extent({}) AS e FROM {}) AS extent",
+            catalog::identifier(geom_column),
+            self.postgis_table()
+        );
+        let query = format!(
+            "SELECT * FROM postgres_query('{}', {});",
+            self.postgres_database,
+            catalog::literal(&extent)
+        );
+        query_extent(&self.conn, &query)
+    }
+
+    fn geometry_statistics(
+        &self,
+        column_types: &[GeometryColumnType],
+    ) -> Result<Vec<GeometryStatistics>, Box<dyn Error>> {
+        let encoding = self.options.sink.geometry_encoding();
+        let mut statistics = Vec::new();
+        for column_type in column_types {
+            let source = format!(
+                "(SELECT {} AS g FROM {})",
+                encoding.geometry(&column_type.column),
+                self.transformed_table
+            );
+            // Empty geometries have no bounds, so they are left out of the extent
+            let (extent, counts) = self.conn.query_row(
+                &format!(
+                    "SELECT min(ST_XMin(g)) FILTER (WHERE NOT ST_IsEmpty(g)),
+                        min(ST_YMin(g)) FILTER (WHERE NOT ST_IsEmpty(g)),
+                        max(ST_XMax(g)) FILTER (WHERE NOT ST_IsEmpty(g)),
+                        max(ST_YMax(g)) FILTER (WHERE NOT ST_IsEmpty(g)),
+                        count(*) FILTER (WHERE g IS NULL),
+                        count(*) FILTER (WHERE ST_IsEmpty(g)),
+                        coalesce(sum(ST_NPoints(g)), 0)::BIGINT,
+                        coalesce(max(ST_NPoints(g)), 0)::BIGINT
+                    FROM {};",
+                    source
+                ),
+                [],
+                |row| {
+                    let bounds: [Option<f64>; 4] =
+                        [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+                    let counts: [i64; 4] = [row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?];
+                    Ok((bounds, counts))
+                },
+            )?;
+            let [null_count, empty_count, total_vertices, max_vertices] = counts.map(|n| n as u64);
+
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT ST_GeometryType(g)::VARCHAR, count(*) FROM {} WHERE g IS NOT NULL GROUP BY 1 ORDER BY 2 DESC, 1;",
+                source
+            ))?;
+            let type_counts = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let column_statistics = GeometryStatistics {
+                column: column_type.column.clone(),
+                srid: column_type.srid.clone(),
+                extent: match extent {
+                    [Some(min_x), Some(min_y), Some(max_x), Some(max_y)] => Some(Extent {
+                        min_x,
+                        min_y,
+                        max_x,
+                        max_y,
+                    }),
+                    _ => None,
+                },
+                type_counts,
+                null_count,
+                empty_count,
+                total_vertices,
+                max_vertices,
+            };
+            println!(
+                "Geometry column {}: {} vertices (at most {} in one geometry), {} empty, {} NULL",
+                column_statistics.column, total_vertices, max_vertices, empty_count, null_count
+            );
+            statistics.push(column_statistics);
+        }
+        Ok(statistics)
+    }
+
+    // Extents of the loaded table, covering earlier rows when appending
+    fn layer_extents(
+        &self,
+        column_types: &[GeometryColumnType],
+    ) -> Result<Vec<LayerExtent>, Box<dyn Error>> {
+        let feature_count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT count(*) FROM {}.{};",
+                self.postgres_database,
+                self.postgis_table()
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        let mut extents = Vec::new();
+        for column_type in column_types {
+            extents.push(LayerExtent {
+                column: column_type.column.clone(),
+                srid: column_type.srid.clone(),
+                extent: self.postgis_extent(&column_type.column)?,
+                feature_count: feature_count as u64,
+            });
+        }
+        Ok(extents)
+    }
+
+    // Keeps one row per geometry column of the table in layer_extents, for clients that zoom to a layer
+    fn write_layer_extents(&self, extents: &[LayerExtent]) -> Result<(), Box<dyn Error>> {
+        let table_literal = format!("'{}'", self.postgis_table().replace('\'', "''"));
+        let mut queries = vec![
+            "CREATE TABLE IF NOT EXISTS layer_extents (
+                table_name text NOT NULL,
+                geometry_column text NOT NULL,
+                srid integer NOT NULL,
+                min_x double precision,
+                min_y double precision,
+                max_x double precision,
+                max_y double precision,
+                feature_count bigint NOT NULL,
+                updated_at timestamptz NOT NULL DEFAULT now(),
+                PRIMARY KEY (table_name, geometry_column)
+            );"
+            .to_string(),
+            format!(
+                "DELETE FROM layer_extents WHERE table_name = {};",
+                table_literal
+            ),
+        ];
+        for layer_extent in extents {
+            let bounds = match &layer_extent.extent {
+                Some(extent) => format!(
+                    "{}, {}, {}, {}",
+                    extent.min_x, extent.min_y, extent.max_x, extent.max_y
+                ),
+                None => "NULL, NULL, NULL, NULL".to_string(),
+            };
+            queries.push(format!(
+                "INSERT INTO layer_extents (table_name, geometry_column, srid, min_x, min_y, max_x, max_y, feature_count)
+                VALUES ({}, '{}', {}, {}, {});",
+                table_literal,
+                layer_extent.column.replace('\'', "''"),
+                layer_extent.srid,
+                bounds,
+                layer_extent.feature_count
+            ));
+        }
+        self.postgres_execute(&queries.join("\n"))
+    }
+
+    fn write_catalog_entry(
+        &self,
+        column_types: &[GeometryColumnType],
+    ) -> Result<(), Box<dyn Error>> {
+        self.postgres_execute(&catalog::catalog_statements(&catalog::CatalogRecord {
+            schema: self.options.schema.as_deref(),
+            table_name: &self.table_name,
+            qualified_table: &self.postgis_table(),
+            source_files: &self.file_paths,
+            file_type: self.file_type_name(),
+            column_types,
+            tags: &self.options.tags,
+            labels: &self.options.labels,
+        }))
+    }
+
+    // Adds the outcome of the load to gridwalk_loads in the primary database
+    // Failures are only printed, so an unreachable database doesn't hide why the load itself failed
+    fn record_load(&mut self, outcome: &Result<LoadResult, Box<dyn Error>>, duration: Duration) {
+        let (status, row_count, srid, error) = match outcome {
+            Ok(result) if result.skipped_unchanged => (LoadStatus::Skipped, None, None, None),
+            Ok(result) => (
+                LoadStatus::Succeeded,
+                result
+                    .sink_report
+                    .as_ref()
+                    .map(|report| report.rows_written),
+                result
+                    .geometry_column_types
+                    .first()
+                    .map(|column_type| column_type.srid.clone()),
+                None,
+            ),
+            Err(e) if e.is::<LoadCancelledError>() => {
+                (LoadStatus::Cancelled, None, None, Some(e.to_string()))
+            }
+            Err(e) => (LoadStatus::Failed, None, None, Some(e.to_string())),
+        };
+        let statements = history::load_statements(&history::LoadRecord {
+            schema: self.options.schema.as_deref(),
+            table_name: &self.table_name,
+            source_files: &self.file_paths,
+            file_type: self.file_type_name(),
+            status,
+            row_count,
+            duration,
+            srid: srid.as_deref(),
+            tags: &self.options.tags,
+            error,
+        });
+
+        let recorded = (|| -> Result<(), Box<dyn Error>> {
+            // Replicas are loaded after the primary, each under its own alias, so switch back to the primary
+            if self.postgres_connection != self.options.postgres_connection {
+                self.postgres_connection = self.options.postgres_connection.clone();
+                self.postgres_database = postgis_database_alias(&self.postgres_connection);
+            }
+            self.attach_postgis()?;
+            self.postgres_execute(&statements)
+        })();
+        if let Err(e) = recorded {
+            println!(
+                "Failed to record the load of {} in gridwalk_loads: {}",
+                self.table_name, e
+            );
+        }
+    }
+
+    // Whether the table's latest load recorded in gridwalk_load_history had the same fingerprint
+    fn is_unchanged(&self, fingerprint: &str) -> Result<bool, Box<dyn Error>> {
+        let ((), _) = self
+            .options
+            .retry_policy
+            .run(RetryStage::Attach, |_| self.attach_postgis())?;
+        let latest = history::latest_fingerprint(
+            &self.conn,
+            &self.postgres_database,
+            self.options.schema.as_deref(),
+            &self.table_name,
+            &self.postgis_table(),
+        )?;
+        Ok(latest.as_deref() == Some(fingerprint))
+    }
+
+    // Returns the names of the styles stored
+    fn write_layer_styles(
+        &self,
+        column_types: &[GeometryColumnType],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let styles = styles::find_styles(
+            &self.conn,
+            &self.file_path,
+            &self.file_type,
+            self.options.layer.as_deref(),
+        )?;
+        if styles.is_empty() {
+            println!("No layer styles found for {}", self.file_path);
+            return Ok(Vec::new());
+        }
+        self.postgres_execute(&styles::layer_styles_statements(
+            &self.table_name,
+            self.options.schema.as_deref(),
+            column_types,
+            &styles,
+        ))?;
+
+        let names = styles
+            .into_iter()
+            .map(|style| style.name)
+            .collect::<Vec<_>>();
+        println!("Layer styles stored for {}: {:?}", self.table_name, names);
+        Ok(names)
+    }
+
+    fn compute_extent(&self, geom_column: &str) -> Result<Option<Extent>, Box<dyn Error>> {
+        geometry_extent(
+            &self.conn,
+            &self.transformed_table,
+            geom_column,
+            GeometryEncoding::Wkb,
+        )
+    }
+
+    // Writes the transformed table to the attached database, the primary or a replica
+    fn load_data_postgis(
+        &self,
+        column_types: &[GeometryColumnType],
+        gate: &PriorityGate,
+    ) -> Result<SinkReport, Box<dyn Error>> {
+        PostGisSink::new(&self.postgres_connection).write_logged(
+            &self.conn,
+            &self.transformed_table,
+            &SinkContext {
+                table_name: &self.table_name,
+                column_types,
+                options: &self.options,
+            },
+            gate,
+        )
+    }
 
-    println!(
-        "Successfully loaded {:?} file: '{}'",
-        processor.file_type, file_path
+    fn postgres_execute(&self, statements: &str) -> Result<(), Box<dyn Error>> {
+        postgis::execute(&self.conn, &self.postgres_database, statements)
+    }
+}
+
+// Extent of a geometry column of the transformed or exported data
+pub(crate) fn geometry_extent(
+    conn: &LoggedConnection,
+    table: &str,
+    geom_column: &str,
+    encoding: GeometryEncoding,
+) -> Result<Option<Extent>, Box<dyn Error>> {
+    let query = format!(
+        "SELECT min(ST_XMin(g)), min(ST_YMin(g)), max(ST_XMax(g)), max(ST_YMax(g))
+        FROM (SELECT {} AS g FROM {});",
+        encoding.geometry(geom_column),
+        table
     );
+    query_extent(conn, &query)
+}
+
+fn query_extent(conn: &LoggedConnection, query: &str) -> Result<Option<Extent>, Box<dyn Error>> {
+    let bounds: [Option<f64>; 4] = conn.query_row(query, [], |row| {
+        Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?])
+    })?;
+    match bounds {
+        [Some(min_x), Some(min_y), Some(max_x), Some(max_y)] => Ok(Some(Extent {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        })),
+        _ => Ok(None),
+    }
+}
+
+// Remove this load's intermediate tables from the shared database
+impl Drop for DuckDBFileProcessor {
+    fn drop(&mut self) {
+        for table in [
+            &self.data_table,
+            &format!("mapped_{}", self.data_table),
+            &format!("resampled_{}", self.data_table),
+            &format!("points_{}", self.data_table),
+            &format!("clipped_{}", self.data_table),
+            &format!("sql_{}", self.data_table),
+            &format!("normalized_{}", self.data_table),
+            &format!("distinct_{}", self.data_table),
+            &format!("keyed_{}", self.data_table),
+            &self.transformed_table,
+        ] {
+            let _ = self
+                .conn
+                .execute(&format!("DROP TABLE IF EXISTS {};", table), []);
+        }
+        if self.file_type == FileType::Csv {
+            for path in &self.file_paths {
+                let _ = std::fs::remove_file(readers::transcoded_path(path, &self.load_key));
+            }
+        }
+    }
+}
+
+fn open_connection() -> Result<Connection, Box<dyn Error>> {
+    // Create Connection Object
+    let conn = Connection::open(":memory:")?;
+
+    // Install and load required extensions
+    if cfg!(feature = "spatial") {
+        conn.execute("INSTALL spatial;", [])?;
+        conn.execute("LOAD spatial;", [])?;
+    }
+    conn.execute("INSTALL postgres;", [])?;
+    conn.execute("LOAD postgres;", [])?;
+    Ok(conn)
+}
+
+pub(crate) const DEFAULT_POSTGRES_CONNECTION: &str =
+    "dbname=gridwalk user=admin password=password host=localhost port=5432";
+
+// Alias a source DuckDB database is attached under, the same for every load of the file
+fn source_database_alias(file_path: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    file_path.hash(&mut hasher);
+    format!("duckdb_source_{:x}", hasher.finish())
+}
+
+// Alias a Postgres database is attached under, one per connection string so loads sharing a LoaderContext
+// with different targets never write to each other's database
+pub(crate) fn postgis_database_alias(postgres_connection: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    postgres_connection.hash(&mut hasher);
+    format!("gridwalk_db_{:x}", hasher.finish())
+}
+
+// Attach Postgres DB instance
+// A shared database may already have it attached under the same alias
+pub(crate) fn postgis_attach_query(postgres_connection: &str, alias: &str) -> String {
+    format!(
+        "ATTACH IF NOT EXISTS '{}' AS {} (TYPE POSTGRES)",
+        postgres_connection.replace('\'', "''"),
+        alias
+    )
+}
+
+// Returns the alias the database is attached under
+fn attach_postgis(conn: &Connection, postgres_connection: &str) -> Result<String, Box<dyn Error>> {
+    let alias = postgis_database_alias(postgres_connection);
+    conn.execute(&postgis_attach_query(postgres_connection, &alias), [])?;
+    Ok(alias)
+}
+
+// Builds a schema contract describing a reference file as it would be read by a load
+pub fn generate_contract_from_file(
+    file_path: &str,
+    options: &LoadOptions,
+) -> Result<SchemaContract, io::Error> {
+    let processor = open_connection()
+        .and_then(|conn| DuckDBFileProcessor::new_file(&[file_path], "contract", options, conn, 0));
+    let mut processor = processor.map_err(|e| {
+        io::Error::other(format!(
+            "Error creating FileProcessor for '{}': {}",
+            file_path, e
+        ))
+    })?;
+    processor.generate_schema_contract().map_err(|e| {
+        io::Error::other(format!(
+            "Error generating schema contract from '{}': {}",
+            file_path, e
+        ))
+    })
+}
+
+// Builds a schema contract from a table already loaded into PostGIS
+// The SRID is left unset as the loaded table holds the target CRS rather than the supplier's
+// schema None looks in the current schema
+pub fn generate_contract_from_table(
+    postgres_connection: &str,
+    schema: Option<&str>,
+    table_name: &str,
+) -> Result<SchemaContract, io::Error> {
+    let contract = open_connection().and_then(|conn| {
+        let database = attach_postgis(&conn, postgres_connection)?;
+        contract::contract_from_table(&conn, &database, schema, table_name)
+    });
+    contract.map_err(|e| {
+        io::Error::other(format!(
+            "Error generating schema contract from table '{}': {}",
+            table_name, e
+        ))
+    })
+}
+
+// Describes a file as a load with these options would read it, without loading it anywhere
+pub fn inspect_file(file_path: &str, options: &LoadOptions) -> Result<FileInfo, io::Error> {
+    let processor = open_connection()
+        .and_then(|conn| DuckDBFileProcessor::new_file(&[file_path], "inspect", options, conn, 0));
+    let mut processor = processor.map_err(|e| {
+        io::Error::other(format!(
+            "Error creating FileProcessor for '{}': {}",
+            file_path, e
+        ))
+    })?;
+    processor
+        .inspect()
+        .map_err(|e| io::Error::other(format!("Error inspecting '{}': {}", file_path, e)))
+}
+
+// Checks what a load of a file into table_name with these options would do and what would stop it,
+// without writing anything - failures to read the file or reach the database are issues in the report
+pub fn validate(file_path: &str, table_name: &str, options: &LoadOptions) -> ValidationReport {
+    let processor = open_connection()
+        .and_then(|conn| DuckDBFileProcessor::new_file(&[file_path], table_name, options, conn, 0));
+    match processor {
+        Ok(mut processor) => processor.validate(),
+        Err(e) => {
+            let mut report = ValidationReport {
+                table_name: table_name.to_string(),
+                ..Default::default()
+            };
+            report.push(
+                ValidationCheck::Detection,
+                IssueSeverity::Error,
+                IssueCode::UnreadableSource,
+                None,
+                &format!("Failed to open '{}': {}", file_path, e),
+            );
+            report
+        }
+    }
+}
+
+// Lists the layers of a GDAL-readable file such as a GeoPackage
+pub fn list_layers(file_path: &str) -> Result<Vec<LayerInfo>, io::Error> {
+    require_spatial("Listing layers")
+        .and_then(|_| open_connection())
+        .and_then(|conn| {
+            match DuckDBFileProcessor::determine_file_type(file_path, &SourceRegistry::default())?
+                .file_type()
+            {
+                FileType::OsmPbf => osm::read_layers(&conn, file_path),
+                _ => inspect::read_layers(&conn, file_path),
+            }
+        })
+        .map_err(|e| io::Error::other(format!("Error listing layers of '{}': {}", file_path, e)))
+}
+
+// Detects a file type from the start of a file's content, as loads do before falling back to the extension
+// Only the first 64 KiB are looked at - None means they match no supported format
+pub fn sniff_bytes(bytes: &[u8]) -> Option<FileType> {
+    detect::sniff_prefix(&bytes[..bytes.len().min(detect::DETECTION_PREFIX_BYTES)])
+}
+
+// Lists the sheet names of an Excel workbook in workbook order
+pub fn list_sheets(file_path: &str) -> Result<Vec<String>, io::Error> {
+    require_spatial("Listing sheets")
+        .and_then(|_| open_connection())
+        .and_then(|conn| inspect::read_layers(&conn, file_path))
+        .map(|layers| layers.into_iter().map(|layer| layer.name).collect())
+        .map_err(|e| io::Error::other(format!("Error listing sheets of '{}': {}", file_path, e)))
+}
+
+// Writes a table from the PostGIS database to a file sink, keeping its columns and CRS - schema None exports
+// from the current schema
+pub fn export_table(
+    table_name: &str,
+    postgres_connection: &str,
+    schema: Option<&str>,
+    sink: &Sink,
+) -> Result<LoadResult, io::Error> {
+    let result = open_connection().and_then(|conn| {
+        let database = attach_postgis(&conn, postgres_connection)?;
+        export::export_postgis_table(
+            &LoggedConnection::new(conn),
+            &database,
+            schema,
+            table_name,
+            sink,
+        )
+    });
+    result.map_err(|e| io::Error::other(format!("Error exporting table '{}': {}", table_name, e)))
+}
+
+// Finds tables recorded in the gridwalk_catalog table by loads with LoadOptions::write_catalog, newest first
+pub fn search_catalog(
+    postgres_connection: &str,
+    query: &CatalogQuery,
+) -> Result<Vec<CatalogEntry>, io::Error> {
+    let entries = open_connection().and_then(|conn| {
+        let database = attach_postgis(&conn, postgres_connection)?;
+        catalog::search(&LoggedConnection::new(conn), &database, query)
+    });
+    entries.map_err(|e| io::Error::other(format!("Error searching the catalog: {}", e)))
+}
+
+// Loads of a table recorded in the gridwalk_loads table by loads with LoadOptions::write_load_history,
+// newest first - schema None looks in the current schema
+pub fn list_loads(
+    postgres_connection: &str,
+    schema: Option<&str>,
+    table_name: &str,
+) -> Result<Vec<LoadHistoryEntry>, io::Error> {
+    let entries = open_connection().and_then(|conn| {
+        let database = attach_postgis(&conn, postgres_connection)?;
+        history::list_loads(&LoggedConnection::new(conn), &database, schema, table_name)
+    });
+    entries.map_err(|e| io::Error::other(format!("Error listing loads of {}: {}", table_name, e)))
+}
+
+// Drops staging tables older than max_age left behind by crashed loads, e.g. from a scheduled job
+pub fn clean_staging_tables(
+    postgres_connection: &str,
+    max_age: Duration,
+) -> Result<Vec<String>, io::Error> {
+    let result = open_connection().and_then(|conn| {
+        let database = attach_postgis(&conn, postgres_connection)?;
+        staging::drop_stale_tables(&LoggedConnection::new(conn), &database, max_age)
+    });
+    result.map_err(|e| io::Error::other(format!("Error cleaning staging tables: {}", e)))
+}
+
+// Loads a file into PostGIS, exports the table back out as GeoParquet and compares the two copies
+// Changes made on purpose, such as unit conversions or column mappings, are reported as differences too
+pub fn round_trip(
+    file_path: &str,
+    table_name: &str,
+    options: &RoundTripOptions,
+) -> Result<RoundTripReport, io::Error> {
+    let load_options = LoadOptions {
+        sink: Sink::PostGis,
+        ..options.load_options.clone()
+    };
+    LoaderContext::new()?.load_file(file_path, table_name, &load_options)?;
+
+    let exported = round_trip::ExportedFile::new();
+    export_table(
+        table_name,
+        &load_options.postgres_connection,
+        load_options.schema.as_deref(),
+        &Sink::Parquet(ParquetSink::new(&exported.path)),
+    )?;
+
+    let report = open_connection().and_then(|conn| {
+        let mut processor =
+            DuckDBFileProcessor::new_file(&[file_path], table_name, &load_options, conn, 0)?;
+        processor.compare_round_trip(&exported.path, options)
+    });
+    report.map_err(|e| {
+        io::Error::other(format!(
+            "Error comparing round trip of '{}': {}",
+            file_path, e
+        ))
+    })
+}
+
+pub fn launch_process_file(file_path: &str, table_name: &str) -> Result<(), io::Error> {
+    launch_process_file_with_options(file_path, table_name, &LoadOptions::default())?;
     Ok(())
 }
+
+pub fn launch_process_file_with_options(
+    file_path: &str,
+    table_name: &str,
+    options: &LoadOptions,
+) -> Result<LoadResult, io::Error> {
+    launch_process_files(&[file_path], table_name, options)
+}
+
+// Merges several files of the same type into a single table
+pub fn launch_process_files(
+    file_paths: &[&str],
+    table_name: &str,
+    options: &LoadOptions,
+) -> Result<LoadResult, io::Error> {
+    LoaderContext::new()?.load_files(file_paths, table_name, options)
+}
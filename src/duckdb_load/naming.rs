@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+// Letter case of normalized identifiers
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum IdentifierCase {
+    // Keep the letters as they are
+    Preserve,
+    // Lowercase, as Postgres folds unquoted identifiers to
+    Lower,
+    // Lowercase with words separated by _, splitting camelCase, e.g. 'PopDensity 2021' -> 'pop_density_2021'
+    #[default]
+    SnakeCase,
+}
+
+// How the table name and column names are rewritten into identifiers that are usable without quoting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NameNormalization {
+    pub case: IdentifierCase,
+    // Replace accented Latin letters with their base letters, e.g. 'Zürich' -> 'Zurich'
+    pub strip_accents: bool,
+    // Replace anything other than ASCII letters, digits and _ with _, and prefix names starting with a digit
+    pub replace_invalid: bool,
+    // Longest name in bytes - Postgres silently truncates identifiers beyond 63
+    pub max_length: usize,
+}
+
+impl Default for NameNormalization {
+    fn default() -> Self {
+        Self {
+            case: IdentifierCase::default(),
+            strip_accents: true,
+            replace_invalid: true,
+            max_length: 63,
+        }
+    }
+}
+
+// A name changed by normalization, as reported in LoadResult::normalized_names
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NameMapping {
+    pub original: String,
+    pub normalized: String,
+}
+
+// Letters folded by strip_accents
+const ACCENTS: [(&str, &str); 40] = [
+    ("ÀÁÂÃÄÅĀĂĄ", "A"),
+    ("àáâãäåāăą", "a"),
+    ("ÇĆĈĊČ", "C"),
+    ("çćĉċč", "c"),
+    ("ĎĐ", "D"),
+    ("ďđ", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"),
+    ("èéêëēĕėęě", "e"),
+    ("ĜĞĠĢ", "G"),
+    ("ĝğġģ", "g"),
+    ("ĤĦ", "H"),
+    ("ĥħ", "h"),
+    ("ÌÍÎÏĨĪĬĮİ", "I"),
+    ("ìíîïĩīĭįı", "i"),
+    ("Ĵ", "J"),
+    ("ĵ", "j"),
+    ("Ķ", "K"),
+    ("ķ", "k"),
+    ("ĹĻĽĿŁ", "L"),
+    ("ĺļľŀł", "l"),
+    ("ÑŃŅŇ", "N"),
+    ("ñńņň", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"),
+    ("òóôõöøōŏő", "o"),
+    ("ŔŖŘ", "R"),
+    ("ŕŗř", "r"),
+    ("ŚŜŞŠ", "S"),
+    ("śŝşš", "s"),
+    ("ŢŤŦ", "T"),
+    ("ţťŧ", "t"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"),
+    ("ùúûüũūŭůűų", "u"),
+    ("ŴẀẂẄ", "W"),
+    ("ŵẁẃẅ", "w"),
+    ("ÝŶŸ", "Y"),
+    ("ýÿŷ", "y"),
+    ("ŹŻŽ", "Z"),
+    ("źżž", "z"),
+    ("ÆŒ", "AE"),
+    ("æœ", "ae"),
+];
+
+impl NameNormalization {
+    pub(crate) fn normalize(&self, name: &str) -> String {
+        let mut normalized = name.to_string();
+        if self.strip_accents {
+            normalized = strip_accents(&normalized);
+        }
+        if self.case == IdentifierCase::SnakeCase {
+            normalized = split_camel_case(&normalized);
+        }
+        if self.replace_invalid || self.case == IdentifierCase::SnakeCase {
+            normalized = replace_invalid(&normalized);
+        }
+        if self.case != IdentifierCase::Preserve {
+            normalized = normalized.to_lowercase();
+        }
+        truncate(&normalized, self.max_length).to_string()
+    }
+
+    // Normalizes every column name, numbering any that end up the same as an earlier one, e.g. 'name_2'
+    // Names that differ only in case count as the same, as they do in DuckDB and unquoted in Postgres
+    pub(crate) fn normalize_all(&self, names: &[String]) -> Vec<String> {
+        let mut normalized: Vec<String> = Vec::with_capacity(names.len());
+        for name in names {
+            let base = self.normalize(name);
+            let taken = |candidate: &str| {
+                normalized
+                    .iter()
+                    .any(|existing| existing.eq_ignore_ascii_case(candidate))
+            };
+            let mut candidate = base.clone();
+            let mut n = 2;
+            while taken(&candidate) {
+                let suffix = format!("_{}", n);
+                let room = self.max_length.saturating_sub(suffix.len());
+                candidate = format!("{}{}", truncate(&base, room), suffix);
+                n += 1;
+            }
+            normalized.push(candidate);
+        }
+        normalized
+    }
+}
+
+fn strip_accents(name: &str) -> String {
+    let mut stripped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match ACCENTS.iter().find(|(accented, _)| accented.contains(c)) {
+            Some((_, base)) => stripped.push_str(base),
+            None if c == 'ß' => stripped.push_str("ss"),
+            None => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+// 'PopDensity' -> 'Pop_Density', 'HTTPCode' -> 'HTTP_Code', 'Area2021' is left alone
+fn split_camel_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut split = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower)
+            {
+                split.push('_');
+            }
+        }
+        split.push(c);
+    }
+    split
+}
+
+// Runs of other characters become a single _, and an empty result becomes 'column'
+fn replace_invalid(name: &str) -> String {
+    let mut replaced = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            replaced.push(c);
+        } else if !replaced.ends_with('_') {
+            replaced.push('_');
+        }
+    }
+    let trimmed = replaced.trim_matches('_');
+    match trimmed.chars().next() {
+        None => "column".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("_{}", trimmed),
+        Some(_) => trimmed.to_string(),
+    }
+}
+
+// Cuts at a character boundary so multi-byte names stay valid
+pub(crate) fn truncate(name: &str, max_length: usize) -> &str {
+    if name.len() <= max_length {
+        return name;
+    }
+    let mut end = max_length;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn default_normalization_makes_snake_case_identifiers() {
+        let normalization = NameNormalization::default();
+        assert_eq!(
+            normalization.normalize("PopDensity 2021"),
+            "pop_density_2021"
+        );
+        assert_eq!(normalization.normalize("Zürich Straße"), "zurich_strasse");
+        assert_eq!(normalization.normalize("2021 total"), "_2021_total");
+        assert_eq!(normalization.normalize("  --name--  "), "name");
+    }
+
+    #[test]
+    fn all_non_latin_names_collapse_to_column() {
+        let normalization = NameNormalization::default();
+        assert_eq!(normalization.normalize("人口"), "column");
+        assert_eq!(normalization.normalize("Население"), "column");
+        assert_eq!(
+            normalization.normalize_all(&names(&["人口", "Население", "σύνολο"])),
+            vec!["column", "column_2", "column_3"]
+        );
+    }
+
+    #[test]
+    fn lower_case_keeps_camel_case_words_together() {
+        let normalization = NameNormalization {
+            case: IdentifierCase::Lower,
+            ..Default::default()
+        };
+        assert_eq!(normalization.normalize("PopDensity"), "popdensity");
+    }
+
+    #[test]
+    fn duplicates_are_numbered_ignoring_case() {
+        let normalization = NameNormalization::default();
+        assert_eq!(
+            normalization.normalize_all(&names(&["Name", "name", "NAME", "name_2"])),
+            vec!["name", "name_2", "name_3", "name_2_2"]
+        );
+    }
+
+    #[test]
+    fn dedupe_suffixes_fit_within_the_length_limit() {
+        let normalization = NameNormalization::default();
+        let long = "a".repeat(70);
+        let normalized = normalization.normalize_all(&[long.clone(), format!("{}b", long)]);
+        assert_eq!(normalized[0], "a".repeat(63));
+        assert_eq!(normalized[1], format!("{}_2", "a".repeat(61)));
+        assert!(normalized.iter().all(|name| name.len() <= 63));
+    }
+
+    #[test]
+    fn multibyte_names_are_cut_at_character_boundaries() {
+        let normalization = NameNormalization {
+            case: IdentifierCase::Preserve,
+            strip_accents: false,
+            replace_invalid: false,
+            max_length: 63,
+        };
+        let long = "é".repeat(40);
+        assert_eq!(normalization.normalize(&long), "é".repeat(31));
+        assert_eq!(
+            normalization.normalize_all(&[long.clone(), long]),
+            vec!["é".repeat(31), format!("{}_2", "é".repeat(30))]
+        );
+    }
+
+    #[test]
+    fn camel_case_splits_at_word_boundaries() {
+        assert_eq!(split_camel_case("PopDensity"), "Pop_Density");
+        assert_eq!(split_camel_case("HTTPCode"), "HTTP_Code");
+        assert_eq!(split_camel_case("Area2021"), "Area2021");
+        assert_eq!(split_camel_case("area2021Total"), "area2021_Total");
+        assert_eq!(split_camel_case("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn truncate_never_splits_a_character() {
+        assert_eq!(truncate("abc", 5), "abc");
+        assert_eq!(truncate("abcdef", 3), "abc");
+        assert_eq!(truncate("aé", 2), "a");
+        assert_eq!(truncate("", 0), "");
+    }
+}
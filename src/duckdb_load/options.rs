@@ -0,0 +1,521 @@
+use super::cancellation::CancellationToken;
+use super::contract::SchemaContract;
+use super::dictionary::DataDictionaryOptions;
+use super::evolution::SchemaEvolution;
+use super::excel::ExcelOptions;
+use super::limits::DuckDbLimits;
+use super::naming::NameNormalization;
+use super::points::PointColumns;
+use super::profile::ProfileOptions;
+use super::readers::ReaderOptions;
+use super::report::Extent;
+use super::retry::RetryPolicy;
+use super::sink::Sink;
+use super::source::SourceRegistry;
+use super::templates::SqlTemplates;
+use super::units::UnitConversion;
+use super::{FileType, DEFAULT_POSTGRES_CONNECTION};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// What to do when merged input files do not share a CRS
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CrsMismatchPolicy {
+    // Reproject every input to the target CRS before merging
+    #[default]
+    Reproject,
+    // Refuse to merge the inputs
+    Fail,
+}
+
+// How a load treats an existing table of the same name
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LoadMode {
+    // Drop the existing table and create it from the new data
+    #[default]
+    Replace,
+    // Insert the new rows into the existing table, creating it if it doesn't exist
+    Append,
+}
+
+// How a load sharing a LoaderContext is scheduled against the others
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LoadPriority {
+    // Runs straight away, e.g. a file uploaded by a user waiting on the result
+    #[default]
+    Interactive,
+    // Pauses between stages while any interactive load is running, e.g. a nightly refresh
+    Batch,
+}
+
+// Limits on how far a replace-mode load may differ from the table it replaces
+// Changes are fractions of the previous value, e.g. 0.3 allows a 30% drop in rows, except the extent change,
+// which is the fraction of the area covered by the old and new extents that only one of them covers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriftThresholds {
+    pub max_row_decrease: f64,
+    pub max_row_increase: f64,
+    pub max_extent_change: f64,
+}
+
+impl Default for DriftThresholds {
+    fn default() -> Self {
+        Self {
+            max_row_decrease: 0.3,
+            max_row_increase: 1.0,
+            max_extent_change: 0.5,
+        }
+    }
+}
+
+// What to do when a load exceeds the drift thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DriftPolicy {
+    // Load the data and mark the result as suspicious
+    #[default]
+    Flag,
+    // Refuse to replace the table unless the load has been confirmed
+    RequireConfirmation,
+}
+
+// What to do when the rows that reached Postgres differ from the rows sent
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum VerificationPolicy {
+    // Load the data and record the difference in the result
+    #[default]
+    Flag,
+    // Fail the load, leaving any previous table in place
+    Fail,
+}
+
+// Checks the staged Postgres table against the DuckDB data before it is published
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferVerification {
+    pub policy: VerificationPolicy,
+    // Rows sampled from the DuckDB data whose hashes must all be found in Postgres - None only compares row counts
+    pub sample_rows: Option<usize>,
+}
+
+// How the transformed rows are copied into the Postgres staging table
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TransferEngine {
+    // Insert through the attached database with SqlTemplates::transfer
+    #[default]
+    Attached,
+    // Stream the rows from DuckDB through COPY FROM STDIN over a connection of its own - much faster for wide tables
+    // The connection uses TLS as its sslmode says, without verifying the server certificate as libpq doesn't
+    // for prefer and require
+    // Tables with LIST, STRUCT, MAP or UNION columns fall back to Attached, as their text form isn't one Postgres reads
+    Copy,
+}
+
+// Splits the transfer to Postgres into partitions copied concurrently, each over its own connection
+// The partitions fill one staging table, so the table is published whole or not at all as before
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParallelTransfer {
+    pub partitions: usize,
+    // Partitions transferred at once
+    pub max_connections: usize,
+    // Rows with the same value go to the same partition - None splits the table into ranges of rows
+    pub partition_column: Option<String>,
+}
+
+impl Default for ParallelTransfer {
+    fn default() -> Self {
+        Self {
+            partitions: 8,
+            max_connections: 4,
+            partition_column: None,
+        }
+    }
+}
+
+// What to do with geometries that fail ST_IsValid
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GeometryValidationPolicy {
+    // Drop rows with invalid geometries
+    Skip,
+    // Repair invalid geometries with ST_MakeValid
+    Repair,
+    // Fail the load if any geometry is invalid
+    Fail,
+}
+
+// What to do with NaN and infinite values in FLOAT and DOUBLE columns, which not every sink can represent
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum NonFinitePolicy {
+    // Write the values as they are
+    Keep,
+    // Replace them with NULL
+    #[default]
+    Null,
+    // Replace them with a fixed value, e.g. -9999
+    Sentinel(f64),
+    // Fail the load if any are found
+    Fail,
+}
+
+// Selection, renaming and retyping of source columns, applied before the data reaches PostGIS
+// All names refer to the columns as they appear in the source file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    // Columns to keep - None keeps every column, geometry columns are always kept
+    pub select: Option<Vec<String>>,
+    // (source name, new name) pairs
+    pub rename: Vec<(String, String)>,
+    // (source name, DuckDB type) pairs, e.g. ("ward_code", "VARCHAR") to keep leading zeros
+    pub type_overrides: Vec<(String, String)>,
+}
+
+// Aggregate applied to a column within each time bucket
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AggregateFunction {
+    Mean,
+    Min,
+    Max,
+    Sum,
+    Count,
+    First,
+    Last,
+}
+
+impl AggregateFunction {
+    pub(crate) fn sql(&self) -> &'static str {
+        match self {
+            AggregateFunction::Mean => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Count => "count",
+            AggregateFunction::First => "first",
+            AggregateFunction::Last => "last",
+        }
+    }
+
+    pub(crate) fn suffix(&self) -> &'static str {
+        match self {
+            AggregateFunction::Mean => "mean",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Count => "count",
+            AggregateFunction::First => "first",
+            AggregateFunction::Last => "last",
+        }
+    }
+}
+
+// An aggregated output column, named {column}_{function}, e.g. reading_mean
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Aggregation {
+    pub column: String,
+    pub function: AggregateFunction,
+}
+
+// Aggregates observation data into fixed time buckets, e.g. hourly means per station
+// Names refer to the columns after the column mapping, and geometry columns keep a value from each group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resample {
+    // Timestamp column the buckets are built from - it keeps its name and holds the bucket start
+    pub time_column: String,
+    // DuckDB interval, e.g. '1 hour' or '15 minutes'
+    pub interval: String,
+    // Columns identifying a series, e.g. the station id
+    pub group_by: Vec<String>,
+    pub aggregations: Vec<Aggregation>,
+}
+
+// Lightens detailed geometries for web maps, applied after they are transformed to the target CRS
+// Both are in the units of the target CRS, e.g. metres for EPSG:27700 and degrees for EPSG:4326
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeometrySimplification {
+    // Distance tolerance for ST_SimplifyPreserveTopology - None skips simplification
+    pub tolerance: Option<f64>,
+    // Snap coordinates to this many decimal places - None keeps full precision
+    pub decimal_places: Option<u32>,
+}
+
+impl GeometrySimplification {
+    // Wraps a geometry expression in the simplification and rounding
+    pub(crate) fn apply(&self, geometry: &str) -> String {
+        let mut geometry = geometry.to_string();
+        if let Some(tolerance) = self.tolerance {
+            geometry = format!("ST_SimplifyPreserveTopology({}, {})", geometry, tolerance);
+        }
+        if let Some(decimal_places) = self.decimal_places {
+            geometry = format!(
+                "ST_ReducePrecision({}, {})",
+                geometry,
+                10f64.powi(-(decimal_places as i32))
+            );
+        }
+        geometry
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match (self.tolerance, self.decimal_places) {
+            (Some(tolerance), Some(decimal_places)) => format!(
+                "Simplified with tolerance {} and rounded to {} decimal places",
+                tolerance, decimal_places
+            ),
+            (Some(tolerance), None) => format!("Simplified with tolerance {}", tolerance),
+            (None, Some(decimal_places)) => {
+                format!("Coordinates rounded to {} decimal places", decimal_places)
+            }
+            (None, None) => "Left as they are".to_string(),
+        }
+    }
+}
+
+// Tables derived from the loaded table for the gridwalk map UI, rebuilt in the same Postgres batch as each
+// load so they never lag behind it - PostGIS sink only, and skipped for data without geometry columns
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompanionTables {
+    // A {table}_centroid table with each geometry replaced by its centroid
+    pub centroid: bool,
+    // A {table}_display table with geometries simplified at this tolerance, in the units of the target CRS
+    // - None skips it
+    pub display_tolerance: Option<f64>,
+}
+
+// Area a clipped load keeps features from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClipArea {
+    BoundingBox(Extent),
+    // Polygon or multipolygon, e.g. a city boundary
+    Wkt(String),
+}
+
+impl ClipArea {
+    pub(crate) fn to_wkt(&self) -> String {
+        match self {
+            ClipArea::BoundingBox(extent) => extent.to_wkt(),
+            ClipArea::Wkt(wkt) => wkt.clone(),
+        }
+    }
+}
+
+// Surrogate key column added to the loaded table, for editing tools that need a primary key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrimaryKey {
+    // BIGSERIAL primary key numbering the rows in PostGIS - file sinks get the row number instead
+    Serial(String),
+    // md5 of the listed columns as they are after any mapping and normalization - all columns when empty
+    // The same rows get the same key on every reload, but rows that share the values fail the load
+    Hash {
+        column: String,
+        columns: Vec<String>,
+    },
+}
+
+impl PrimaryKey {
+    pub(crate) fn column(&self) -> &str {
+        match self {
+            PrimaryKey::Serial(column) | PrimaryKey::Hash { column, .. } => column,
+        }
+    }
+}
+
+// Loads only the features whose geometry intersects an area, e.g. one city from a national dataset
+// GeoPackages, Shapefiles and GeoJSON pass the area's bounding box to GDAL so the rest is never read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpatialFilter {
+    pub area: ClipArea,
+    // EPSG code the area is given in
+    pub srid: String,
+}
+
+impl SpatialFilter {
+    pub fn new(area: ClipArea, srid: &str) -> Self {
+        Self {
+            area,
+            srid: srid.to_string(),
+        }
+    }
+}
+
+// Options that control how a file is processed and loaded
+// Defaults match the behaviour of launch_process_file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadOptions {
+    // libpq connection string or postgres:// URI of the PostGIS database
+    // Loads sharing a LoaderContext use the connection of the first load to attach it
+    pub postgres_connection: String,
+    // Further Postgres databases the same load is applied to once postgres_connection has it, e.g. a
+    // replica in another region - each is reported in LoadResult::replicas and a failure doesn't fail the load
+    pub replica_connections: Vec<String>,
+    pub load_mode: LoadMode,
+    // Fingerprint the source files and options, and skip the load when the table's latest load in the
+    // gridwalk_load_history table had the same fingerprint - PostGIS sink only
+    pub skip_unchanged: bool,
+    // Load even when skip_unchanged finds nothing has changed, still recording the fingerprint
+    pub force: bool,
+    // Add new columns and widen types when appending to a table with a different schema - None inserts
+    // the columns as they are, failing on any the table doesn't have
+    pub schema_evolution: Option<SchemaEvolution>,
+    pub priority: LoadPriority,
+    // Cancelling it interrupts the running query and stops the load, dropping anything staged in Postgres
+    #[serde(skip)]
+    pub cancellation: CancellationToken,
+    // Postgres schema the table is loaded into, created if it doesn't exist - None uses the current schema
+    pub schema: Option<String>,
+    // Layer to read from multi-layer sources such as GeoPackages - None reads the first layer
+    pub layer: Option<String>,
+    pub excel: ExcelOptions,
+    // Settings for the CSV, Parquet and ST_Read readers
+    pub reader_options: ReaderOptions,
+    // Features outside the area are dropped, testing the first geometry column
+    pub spatial_filter: Option<SpatialFilter>,
+    // Builds a point geometry from coordinate columns in tabular data
+    pub point_columns: Option<PointColumns>,
+    // Looks for coordinate columns such as lon/lat or easting/northing when the data has no geometry
+    // Detected columns are only reported unless confirm_point_columns is set
+    pub detect_point_columns: bool,
+    pub confirm_point_columns: bool,
+    // Skip content detection and read every input as this type - Registered still picks the handler by detection
+    pub file_type: Option<FileType>,
+    // Formats added by downstream code, detected alongside the built-in ones
+    #[serde(skip)]
+    pub sources: SourceRegistry,
+    // EPSG code that geometry columns are transformed to
+    pub target_crs: String,
+    // EPSG code of sources that do not record one, e.g. DuckDB and Arrow files - GDAL sources use their own
+    pub source_crs: Option<String>,
+    // Unit conversions applied to numeric columns before the data is loaded
+    pub unit_conversions: Vec<UnitConversion>,
+    // How differing CRSs are handled when merging multiple files
+    pub crs_mismatch_policy: CrsMismatchPolicy,
+    pub non_finite_policy: NonFinitePolicy,
+    // Round FLOAT and DOUBLE columns to this many decimal places - None keeps full precision
+    pub float_decimal_places: Option<u32>,
+    // Validate geometry columns before loading - None skips validation
+    pub geometry_validation: Option<GeometryValidationPolicy>,
+    // Fail the load if the source data does not match this schema
+    pub schema_contract: Option<SchemaContract>,
+    pub column_mapping: ColumnMapping,
+    pub resample: Option<Resample>,
+    // SELECT over a table named data, run after the column mapping and resampling, e.g.
+    // "SELECT *, ST_Area(geom) AS area FROM data WHERE status = 'active'"
+    // Geometry columns it returns must stay in the source CRS - they are transformed afterwards
+    pub sql_transform: Option<String>,
+    // Rewrite the table name and column names into plain identifiers, after any column mapping and SQL
+    // transform - None keeps the names as given
+    pub name_normalization: Option<NameNormalization>,
+    // Drop rows that are exact duplicates of an earlier row
+    pub deduplicate: bool,
+    pub primary_key: Option<PrimaryKey>,
+    // Create a GIST index on each geometry column and ANALYZE the table after loading
+    pub create_spatial_index: bool,
+    // Record the extent and feature count of each geometry column in a layer_extents table after loading
+    pub write_layer_extents: bool,
+    // Store QML/SLD sidecar files and GeoPackage layer_styles rows in QGIS's layer_styles table so the
+    // loaded layer opens styled in QGIS
+    pub write_layer_styles: bool,
+    // Record the load in the gridwalk_catalog table, with its tags and labels, so search_catalog finds it
+    pub write_catalog: bool,
+    // Record every load, including failed ones, with its duration and row count in the gridwalk_loads
+    // table - PostGIS sink only
+    pub write_load_history: bool,
+    // Free-form tags, e.g. 'boundaries'
+    pub tags: Vec<String>,
+    // Key-value labels, e.g. ('owner', 'planning')
+    pub labels: Vec<(String, String)>,
+    // Promote single geometries to their multi type so mixed inputs fit one typed column
+    pub promote_to_multi: bool,
+    // Simplify geometries and round their coordinates before they are written - None keeps them as they are
+    pub simplification: Option<GeometrySimplification>,
+    pub companion_tables: CompanionTables,
+    // Compare against the previous version of the table - None disables the check
+    pub drift_thresholds: Option<DriftThresholds>,
+    pub drift_policy: DriftPolicy,
+    // Confirms a load that exceeds the thresholds under DriftPolicy::RequireConfirmation
+    pub confirm_drift: bool,
+    // Add WGS84 longitude/latitude columns for the first Point geometry column, for tools that can't read PostGIS types
+    pub add_lon_lat_columns: bool,
+    pub data_dictionary: Option<DataDictionaryOptions>,
+    // Compute per-column statistics in inspect_file - None skips the profiling pass
+    pub profile: Option<ProfileOptions>,
+    pub retry_policy: RetryPolicy,
+    pub duckdb_limits: DuckDbLimits,
+    // Staging tables older than this are dropped at the start of each PostGIS load - None keeps them
+    pub stale_staging_age: Option<Duration>,
+    pub transfer_verification: Option<TransferVerification>,
+    pub transfer_engine: TransferEngine,
+    // None copies the whole table in one statement
+    pub parallel_transfer: Option<ParallelTransfer>,
+    // Samples memory and CPU use at this interval during the load - None disables sampling
+    pub resource_sample_interval: Option<Duration>,
+    // Where the transformed data is written
+    pub sink: Sink,
+    pub sql_templates: SqlTemplates,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            postgres_connection: DEFAULT_POSTGRES_CONNECTION.to_string(),
+            replica_connections: Vec::new(),
+            load_mode: LoadMode::default(),
+            skip_unchanged: false,
+            force: false,
+            schema_evolution: None,
+            priority: LoadPriority::default(),
+            cancellation: CancellationToken::default(),
+            schema: None,
+            layer: None,
+            excel: ExcelOptions::default(),
+            reader_options: ReaderOptions::default(),
+            spatial_filter: None,
+            point_columns: None,
+            detect_point_columns: false,
+            confirm_point_columns: false,
+            file_type: None,
+            sources: SourceRegistry::default(),
+            target_crs: "4326".to_string(),
+            source_crs: None,
+            unit_conversions: Vec::new(),
+            crs_mismatch_policy: CrsMismatchPolicy::default(),
+            non_finite_policy: NonFinitePolicy::default(),
+            float_decimal_places: None,
+            geometry_validation: None,
+            schema_contract: None,
+            column_mapping: ColumnMapping::default(),
+            resample: None,
+            sql_transform: None,
+            name_normalization: None,
+            deduplicate: false,
+            primary_key: None,
+            create_spatial_index: true,
+            write_layer_extents: false,
+            write_layer_styles: false,
+            write_catalog: false,
+            write_load_history: false,
+            tags: Vec::new(),
+            labels: Vec::new(),
+            promote_to_multi: false,
+            simplification: None,
+            companion_tables: CompanionTables::default(),
+            drift_thresholds: Some(DriftThresholds::default()),
+            drift_policy: DriftPolicy::default(),
+            confirm_drift: false,
+            add_lon_lat_columns: false,
+            data_dictionary: None,
+            profile: None,
+            retry_policy: RetryPolicy::default(),
+            duckdb_limits: DuckDbLimits::default(),
+            stale_staging_age: Some(Duration::from_secs(24 * 60 * 60)),
+            transfer_verification: Some(TransferVerification::default()),
+            transfer_engine: TransferEngine::default(),
+            parallel_transfer: None,
+            resource_sample_interval: None,
+            sink: Sink::default(),
+            sql_templates: SqlTemplates::default(),
+        }
+    }
+}
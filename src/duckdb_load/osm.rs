@@ -0,0 +1,77 @@
+use super::inspect::LayerInfo;
+use duckdb::Connection;
+use std::error::Error;
+
+// Tables an OSM extract is read as, chosen with LoadOptions::layer - nodes when none is given
+pub(crate) const LAYERS: [&str; 3] = ["nodes", "ways", "relations"];
+
+// SELECT reading one layer of a PBF extract with ST_ReadOSM
+// Tags become JSON objects, as Postgres has no equivalent of DuckDB's MAP type
+pub(crate) fn layer_query(file_path: &str, layer: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let osm = format!("ST_ReadOSM('{}')", file_path);
+    match layer.unwrap_or(LAYERS[0]) {
+        // Untagged nodes are only the vertices of ways, so points are the tagged ones
+        "nodes" => Ok(format!(
+            "SELECT id, to_json(tags) AS tags, ST_Point(lon, lat) AS geom
+            FROM {} WHERE kind = 'node' AND cardinality(tags) > 0",
+            osm
+        )),
+        // Lines through the way's nodes in order - ways missing from a clipped extract lose those vertices
+        "ways" => Ok(format!(
+            "WITH nodes AS (
+                SELECT id, lon, lat FROM {0} WHERE kind = 'node'
+            ), way_nodes AS (
+                SELECT id, tags, unnest(refs) AS node_id, generate_subscripts(refs, 1) AS position
+                FROM {0} WHERE kind = 'way'
+            )
+            SELECT way_nodes.id, to_json(any_value(way_nodes.tags)) AS tags,
+                ST_MakeLine(list(ST_Point(nodes.lon, nodes.lat) ORDER BY way_nodes.position)) AS geom
+            FROM way_nodes JOIN nodes ON nodes.id = way_nodes.node_id
+            GROUP BY way_nodes.id
+            HAVING count(*) >= 2",
+            osm
+        )),
+        // Members are kept as parallel lists rather than assembled into geometries
+        "relations" => Ok(format!(
+            "SELECT id, to_json(tags) AS tags, refs AS member_ids, ref_types::VARCHAR[] AS member_types,
+                ref_roles AS member_roles
+            FROM {} WHERE kind = 'relation'",
+            osm
+        )),
+        other => Err(format!(
+            "Unknown OSM layer '{}', expected one of: {}",
+            other,
+            LAYERS.join(", ")
+        )
+        .into()),
+    }
+}
+
+// Feature counts of each layer, counted from one pass over the file
+pub(crate) fn read_layers(
+    conn: &Connection,
+    file_path: &str,
+) -> Result<Vec<LayerInfo>, Box<dyn Error>> {
+    let (nodes, ways, relations): (i64, i64, i64) = conn.query_row(
+        &format!(
+            "SELECT count(*) FILTER (WHERE kind = 'node' AND cardinality(tags) > 0),
+                count(*) FILTER (WHERE kind = 'way'),
+                count(*) FILTER (WHERE kind = 'relation')
+            FROM ST_ReadOSM('{}');",
+            file_path
+        ),
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let layer = |name: &str, feature_count: i64, geometry_type: Option<&str>| LayerInfo {
+        name: name.to_string(),
+        feature_count,
+        geometry_type: geometry_type.map(str::to_string),
+        crs: geometry_type.map(|_| "4326".to_string()),
+    };
+    Ok(vec![
+        layer("nodes", nodes, Some("Point")),
+        layer("ways", ways, Some("Line String")),
+        layer("relations", relations, None),
+    ])
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+// Builds a Point geometry column from a pair of numeric coordinate columns in tabular data such as CSV or Parquet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointColumns {
+    // Longitude or easting
+    pub x_column: String,
+    // Latitude or northing
+    pub y_column: String,
+    // EPSG code of the coordinates, e.g. 4326 for longitude/latitude or 27700 for British National Grid
+    pub srid: String,
+    pub geometry_column: String,
+    // Keep the coordinate columns next to the new geometry
+    pub keep_coordinate_columns: bool,
+}
+
+impl PointColumns {
+    pub fn new(x_column: &str, y_column: &str, srid: &str) -> Self {
+        Self {
+            x_column: x_column.to_string(),
+            y_column: y_column.to_string(),
+            srid: srid.to_string(),
+            geometry_column: "geom".to_string(),
+            keep_coordinate_columns: false,
+        }
+    }
+}
+
+// Conventional (x, y) column names, most specific first - matched without regard to case
+const COORDINATE_NAMES: [(&str, &str); 7] = [
+    ("longitude", "latitude"),
+    ("lon", "lat"),
+    ("long", "lat"),
+    ("lng", "lat"),
+    ("easting", "northing"),
+    ("east", "north"),
+    ("x", "y"),
+];
+
+// First pair of numeric columns named like coordinates, with the names as they appear in the data
+pub(crate) fn find_coordinate_pair(numeric_columns: &[String]) -> Option<(String, String)> {
+    let find = |name: &str| {
+        numeric_columns
+            .iter()
+            .find(|column| column.eq_ignore_ascii_case(name))
+    };
+    COORDINATE_NAMES
+        .iter()
+        .find_map(|(x, y)| Some((find(x)?.clone(), find(y)?.clone())))
+}
@@ -0,0 +1,144 @@
+use super::{CancellationToken, LoadPriority};
+use std::error::Error;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+// How often a paused batch load checks whether it has been cancelled
+const CANCELLATION_POLL: Duration = Duration::from_millis(200);
+
+// Tracks the interactive loads running through a LoaderContext, so batch loads sharing the context can
+// step aside for them
+#[derive(Default)]
+pub(crate) struct PriorityGate {
+    interactive: Mutex<usize>,
+    finished: Condvar,
+}
+
+// Held for the duration of an interactive load
+pub(crate) struct InteractiveGuard<'a> {
+    gate: &'a PriorityGate,
+}
+
+impl PriorityGate {
+    pub(crate) fn enter(&self, priority: LoadPriority) -> Option<InteractiveGuard<'_>> {
+        match priority {
+            LoadPriority::Interactive => {
+                *self.interactive.lock().unwrap() += 1;
+                Some(InteractiveGuard { gate: self })
+            }
+            LoadPriority::Batch => None,
+        }
+    }
+
+    // Called by loads between stages - a statement already running is never interrupted, so a batch load
+    // pauses at its next stage boundary, and a cancelled load stops there
+    pub(crate) fn checkpoint(
+        &self,
+        priority: LoadPriority,
+        table_name: &str,
+        cancellation: &CancellationToken,
+    ) -> Result<(), Box<dyn Error>> {
+        cancellation.check()?;
+        if priority != LoadPriority::Batch {
+            return Ok(());
+        }
+        let mut interactive = self.interactive.lock().unwrap();
+        if *interactive > 0 {
+            println!(
+                "Pausing batch load of {} while {} interactive loads run",
+                table_name, *interactive
+            );
+            while *interactive > 0 {
+                interactive = self
+                    .finished
+                    .wait_timeout(interactive, CANCELLATION_POLL)
+                    .unwrap()
+                    .0;
+                cancellation.check()?;
+            }
+            println!("Resuming batch load of {}", table_name);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for InteractiveGuard<'_> {
+    fn drop(&mut self) {
+        let mut interactive = self.gate.interactive.lock().unwrap();
+        *interactive -= 1;
+        if *interactive == 0 {
+            self.gate.finished.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckdb_load::LoadCancelledError;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // Transfers partitions in waves as PostGisLoad::transfer_partitions does, passing the gate before each
+    fn transfer_waves(
+        gate: &PriorityGate,
+        cancellation: &CancellationToken,
+        waves: usize,
+        started: mpsc::Sender<usize>,
+        after_first: mpsc::Receiver<()>,
+    ) -> Result<(), String> {
+        for wave in 0..waves {
+            gate.checkpoint(LoadPriority::Batch, "partitions", cancellation)
+                .map_err(|e| e.to_string())?;
+            started.send(wave).unwrap();
+            if wave == 0 {
+                after_first.recv().unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn batch_transfer_pauses_between_waves_while_an_interactive_load_runs() {
+        let gate = PriorityGate::default();
+        let cancellation = CancellationToken::new();
+        let (started, waves) = mpsc::channel();
+        let (interactive_entered, after_first) = mpsc::channel();
+        thread::scope(|scope| {
+            let transfer =
+                scope.spawn(|| transfer_waves(&gate, &cancellation, 3, started, after_first));
+            assert_eq!(waves.recv().unwrap(), 0);
+
+            let interactive = gate.enter(LoadPriority::Interactive);
+            interactive_entered.send(()).unwrap();
+            assert!(waves.recv_timeout(Duration::from_millis(500)).is_err());
+
+            drop(interactive);
+            assert_eq!(waves.recv().unwrap(), 1);
+            assert_eq!(waves.recv().unwrap(), 2);
+            assert_eq!(transfer.join().unwrap(), Ok(()));
+        });
+    }
+
+    #[test]
+    fn paused_transfer_stops_when_cancelled() {
+        let gate = PriorityGate::default();
+        let cancellation = CancellationToken::new();
+        let (started, waves) = mpsc::channel();
+        let (interactive_entered, after_first) = mpsc::channel();
+        thread::scope(|scope| {
+            let transfer =
+                scope.spawn(|| transfer_waves(&gate, &cancellation, 3, started, after_first));
+            assert_eq!(waves.recv().unwrap(), 0);
+
+            let _interactive = gate.enter(LoadPriority::Interactive);
+            interactive_entered.send(()).unwrap();
+            cancellation.cancel();
+            assert_eq!(
+                transfer.join().unwrap(),
+                Err(LoadCancelledError.to_string())
+            );
+            assert!(waves.try_recv().is_err());
+        });
+    }
+}
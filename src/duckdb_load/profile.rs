@@ -0,0 +1,174 @@
+use super::query_log::LoggedConnection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Settings for the profiling pass of inspect_file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileOptions {
+    // Most frequent values reported per column
+    pub top_values: usize,
+    // Count distinct values exactly rather than with HyperLogLog, which is within a few percent and much
+    // cheaper on large files
+    pub exact_distinct: bool,
+    // Fraction of NULLs above which a column is flagged as mostly empty
+    pub mostly_null_fraction: f64,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        Self {
+            top_values: 5,
+            exact_distinct: false,
+            mostly_null_fraction: 0.9,
+        }
+    }
+}
+
+// Per-column statistics of a source file, for flagging suspicious columns before a load
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ProfileReport {
+    pub row_count: u64,
+    pub columns: Vec<ColumnProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ColumnProfile {
+    pub name: String,
+    pub data_type: String,
+    pub null_count: u64,
+    pub null_fraction: f64,
+    pub distinct_count: u64,
+    // Smallest and largest values as text - None for geometry and nested columns
+    pub min: Option<String>,
+    pub max: Option<String>,
+    // (value, count) pairs, most frequent first - empty for geometry and nested columns
+    pub top_values: Vec<(String, u64)>,
+    // Reasons the column looks suspicious, e.g. 'All values are NULL'
+    pub warnings: Vec<String>,
+}
+
+impl ProfileReport {
+    pub fn suspicious_columns(&self) -> impl Iterator<Item = &ColumnProfile> {
+        self.columns
+            .iter()
+            .filter(|column| !column.warnings.is_empty())
+    }
+}
+
+pub(crate) fn profile_table(
+    conn: &LoggedConnection,
+    table: &str,
+    columns: &[(String, String)],
+    geometry_columns: &[String],
+    options: &ProfileOptions,
+) -> Result<ProfileReport, Box<dyn Error>> {
+    // Geometries and nested values have no useful ordering or frequent values
+    let comparable = |name: &str, data_type: &str| {
+        !geometry_columns.iter().any(|geometry| geometry == name)
+            && !["STRUCT", "MAP", "UNION", "BLOB"]
+                .iter()
+                .any(|nested| data_type.starts_with(nested))
+            && !data_type.ends_with(']')
+    };
+
+    // Every column's counts and range in one scan
+    let distinct = if options.exact_distinct {
+        "count(DISTINCT {})"
+    } else {
+        "approx_count_distinct({})"
+    };
+    let mut aggregates = vec!["count(*)".to_string()];
+    for (name, data_type) in columns {
+        let column = format!("\"{}\"", name.replace('"', "\"\""));
+        aggregates.push(format!("count({})", column));
+        aggregates.push(distinct.replace("{}", &column));
+        if comparable(name, data_type) {
+            aggregates.push(format!("min({})::VARCHAR", column));
+            aggregates.push(format!("max({})::VARCHAR", column));
+        } else {
+            aggregates.push("NULL::VARCHAR".to_string());
+            aggregates.push("NULL::VARCHAR".to_string());
+        }
+    }
+    let (row_count, mut profiles) = conn.query_row(
+        &format!("SELECT {} FROM {};", aggregates.join(", "), table),
+        [],
+        |row| {
+            let row_count = row.get::<_, i64>(0)? as u64;
+            let mut profiles = Vec::with_capacity(columns.len());
+            for (i, (name, data_type)) in columns.iter().enumerate() {
+                let first = 1 + i * 4;
+                let non_null = row.get::<_, i64>(first)? as u64;
+                profiles.push(ColumnProfile {
+                    name: name.clone(),
+                    data_type: data_type.clone(),
+                    null_count: row_count - non_null,
+                    null_fraction: if row_count == 0 {
+                        0.0
+                    } else {
+                        (row_count - non_null) as f64 / row_count as f64
+                    },
+                    distinct_count: row.get::<_, i64>(first + 1)? as u64,
+                    min: row.get(first + 2)?,
+                    max: row.get(first + 3)?,
+                    ..Default::default()
+                });
+            }
+            Ok((row_count, profiles))
+        },
+    )?;
+
+    for profile in &mut profiles {
+        if options.top_values > 0 && comparable(&profile.name, &profile.data_type) {
+            let column = format!("\"{}\"", profile.name.replace('"', "\"\""));
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}::VARCHAR, count(*) FROM {} WHERE {} IS NOT NULL GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT {};",
+                column, table, column, options.top_values
+            ))?;
+            profile.top_values = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+        profile.warnings = warnings(profile, row_count, options);
+    }
+
+    Ok(ProfileReport {
+        row_count,
+        columns: profiles,
+    })
+}
+
+fn warnings(profile: &ColumnProfile, row_count: u64, options: &ProfileOptions) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if row_count == 0 {
+        return warnings;
+    }
+    if profile.null_count == row_count {
+        warnings.push("All values are NULL".to_string());
+        return warnings;
+    }
+    if profile.null_fraction > options.mostly_null_fraction {
+        warnings.push(format!(
+            "{:.1}% of values are NULL",
+            profile.null_fraction * 100.0
+        ));
+    }
+    if profile.distinct_count == 1 && row_count > 1 {
+        warnings.push("Every non-NULL value is the same".to_string());
+    }
+    // Text that is mostly empty strings or placeholders often hides missing values
+    if let Some((value, count)) = profile.top_values.first() {
+        let placeholder =
+            ["", "-", "NA", "N/A", "NULL", "null", "None", "0"].contains(&value.trim());
+        if profile.data_type == "VARCHAR" && placeholder && *count * 2 > row_count {
+            warnings.push(format!(
+                "Most values are the placeholder '{}' ({} rows)",
+                value, count
+            ));
+        }
+    }
+    warnings
+}
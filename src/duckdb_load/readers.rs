@@ -0,0 +1,180 @@
+use super::charset;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+// Settings passed through to the DuckDB reader of each format, for files the default reads can't handle
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReaderOptions {
+    pub csv: CsvReaderOptions,
+    pub parquet: ParquetReaderOptions,
+    // GeoPackages, Shapefiles and GeoJSON - Excel workbooks have their own ExcelOptions
+    pub spatial: SpatialReaderOptions,
+}
+
+// read_csv settings - anything left as None is sniffed by DuckDB
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvReaderOptions {
+    // Rows sampled to detect the dialect and column types, -1 samples the whole file
+    pub sample_size: Option<i64>,
+    // Column types applied while parsing - ColumnMapping::type_overrides take precedence for the same column
+    pub types: Vec<(String, String)>,
+    // Values read as NULL, e.g. 'NA' or '-'
+    pub null_strings: Vec<String>,
+    // Character encoding of the file - 'utf-16', 'utf-16le', 'utf-16be', 'latin-1' and 'windows-1252' files
+    // are converted to a temporary UTF-8 copy first, as read_csv only reads UTF-8
+    // None detects it from a byte order mark or the bytes themselves, which reads the file an extra time
+    pub encoding: Option<String>,
+    pub delimiter: Option<String>,
+    pub header: Option<bool>,
+}
+
+// parquet_scan settings, mostly relevant for globs over partitioned datasets
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParquetReaderOptions {
+    // Read key=value directories in the path as columns
+    pub hive_partitioning: bool,
+    // Combine files with different columns by name rather than by position
+    pub union_by_name: bool,
+}
+
+// ST_Read settings
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpatialReaderOptions {
+    // GDAL open options for the driver, e.g. 'LIST_ALL_TABLES=YES'
+    pub open_options: Vec<String>,
+    // WKT geometry in the source CRS - only features intersecting it are read, in place of the box
+    // LoadOptions::spatial_filter passes down
+    pub spatial_filter: Option<String>,
+}
+
+impl CsvReaderOptions {
+    // Named read_csv arguments, each starting with ', '
+    pub(crate) fn arguments(&self, type_overrides: &[(String, String)]) -> String {
+        let mut arguments = String::new();
+        if let Some(sample_size) = self.sample_size {
+            arguments.push_str(&format!(", sample_size = {}", sample_size));
+        }
+
+        let types = type_overrides
+            .iter()
+            .chain(self.types.iter().filter(|(column, _)| {
+                !type_overrides
+                    .iter()
+                    .any(|(overridden, _)| overridden == column)
+            }))
+            .map(|(column, data_type)| format!("'{}': '{}'", quote(column), quote(data_type)))
+            .collect::<Vec<_>>();
+        if !types.is_empty() {
+            arguments.push_str(&format!(", types = {{{}}}", types.join(", ")));
+        }
+
+        if !self.null_strings.is_empty() {
+            arguments.push_str(&format!(", nullstr = {}", list(&self.null_strings)));
+        }
+        if let Some(delimiter) = &self.delimiter {
+            arguments.push_str(&format!(", delim = '{}'", quote(delimiter)));
+        }
+        if let Some(header) = self.header {
+            arguments.push_str(&format!(", header = {}", header));
+        }
+        arguments
+    }
+
+    // Encoding of the file, as given or detected
+    pub(crate) fn encoding_of(&self, file_path: &str) -> Result<String, Box<dyn Error>> {
+        match &self.encoding {
+            Some(encoding) => Ok(charset::normalize(encoding)),
+            None => charset::detect_file(file_path),
+        }
+    }
+
+    // Path of a UTF-8 copy of the file to read instead, written when the encoding is not UTF-8
+    // load_key is unique to the load, so loads of the same file don't write over each other's copies
+    pub(crate) fn transcode(
+        file_path: &str,
+        encoding: &str,
+        load_key: &str,
+    ) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        if encoding == "utf-8" {
+            return Ok(None);
+        }
+        let path = transcoded_path(file_path, load_key);
+        let written = charset::transcode(
+            BufReader::new(File::open(file_path)?),
+            BufWriter::new(File::create(&path)?),
+            encoding,
+        );
+        if let Err(e) = written {
+            let _ = fs::remove_file(&path);
+            return Err(format!("Can't read CSV '{}': {}", file_path, e).into());
+        }
+        Ok(Some(path))
+    }
+}
+
+// Temporary UTF-8 copy of a CSV file, named after the load and a hash of the file's path
+pub(crate) fn transcoded_path(file_path: &str, load_key: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    file_path.hash(&mut hasher);
+    std::env::temp_dir().join(format!("{}_{:x}.csv", load_key, hasher.finish()))
+}
+
+impl ParquetReaderOptions {
+    pub(crate) fn arguments(&self) -> String {
+        let mut arguments = String::new();
+        if self.hive_partitioning {
+            arguments.push_str(", hive_partitioning = true");
+        }
+        if self.union_by_name {
+            arguments.push_str(", union_by_name = true");
+        }
+        arguments
+    }
+}
+
+impl SpatialReaderOptions {
+    // extra_open_options are passed on with open_options, e.g. the ENCODING detected for a Shapefile
+    pub(crate) fn arguments(&self, extra_open_options: &[String]) -> String {
+        let mut arguments = String::new();
+        let open_options = self
+            .open_options
+            .iter()
+            .chain(extra_open_options)
+            .cloned()
+            .collect::<Vec<_>>();
+        if !open_options.is_empty() {
+            arguments.push_str(&format!(", open_options := {}", list(&open_options)));
+        }
+        if let Some(filter) = &self.spatial_filter {
+            arguments.push_str(&format!(
+                ", spatial_filter := ST_AsWKB(ST_GeomFromText('{}'))",
+                quote(filter)
+            ));
+        }
+        arguments
+    }
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+fn list(values: &[String]) -> String {
+    format!(
+        "[{}]",
+        values
+            .iter()
+            .map(|value| format!("'{}'", quote(value)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
@@ -0,0 +1,204 @@
+use super::dictionary::DataDictionary;
+use super::evolution::SchemaEvolutionReport;
+use super::naming::NameMapping;
+use super::points::PointColumns;
+use super::query_log::QueryLogEntry;
+use super::resources::ResourceUsage;
+use super::sink::SinkReport;
+use serde::{Deserialize, Serialize};
+
+// A single transformation applied to the data during a load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineageEntry {
+    pub stage: String,
+    pub column: Option<String>,
+    pub description: String,
+}
+
+impl LineageEntry {
+    pub fn new(stage: &str, column: Option<&str>, description: &str) -> Self {
+        Self {
+            stage: stage.to_string(),
+            column: column.map(str::to_string),
+            description: description.to_string(),
+        }
+    }
+}
+
+// Result of validating a single geometry column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeometryValidationReport {
+    pub column: String,
+    pub invalid_count: u64,
+    // Rows dropped under GeometryValidationPolicy::Skip
+    pub skipped_count: u64,
+    // Geometries fixed under GeometryValidationPolicy::Repair
+    pub repaired_count: u64,
+}
+
+// PostGIS column type chosen for a geometry column, e.g. geometry(MultiPolygon, 4326)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeometryColumnType {
+    pub column: String,
+    pub geometry_type: String,
+    pub srid: String,
+    // Single geometries were wrapped with ST_Multi to fit the column type
+    pub promoted_to_multi: bool,
+    // Some geometries have Z or M values - the others are padded with zeros to fit the column type
+    #[serde(default)]
+    pub has_z: bool,
+    #[serde(default)]
+    pub has_m: bool,
+}
+
+impl GeometryColumnType {
+    pub fn postgis_type(&self) -> String {
+        format!(
+            "geometry({}{}, {})",
+            self.geometry_type,
+            self.dimensions(),
+            self.srid
+        )
+    }
+
+    // Suffix of the type for the coordinate dimensions beyond X and Y, e.g. 'Z' for PolygonZ
+    pub fn dimensions(&self) -> &'static str {
+        match (self.has_z, self.has_m) {
+            (true, true) => "ZM",
+            (true, false) => "Z",
+            (false, true) => "M",
+            (false, false) => "",
+        }
+    }
+}
+
+// Summary of a completed load
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadResult {
+    pub table_name: String,
+    pub geometry_columns: Vec<String>,
+    pub geometry_column_types: Vec<GeometryColumnType>,
+    pub lineage: Vec<LineageEntry>,
+    pub geometry_validation: Vec<GeometryValidationReport>,
+    // Set when the load replaced an existing table
+    pub drift: Option<DriftReport>,
+    // Every statement run against DuckDB and Postgres, in order
+    pub query_log: Vec<QueryLogEntry>,
+    // Spatial summary of each geometry column as transferred, in the target CRS
+    pub geometry_statistics: Vec<GeometryStatistics>,
+    // Set when LoadOptions::write_layer_extents is enabled
+    pub layer_extents: Vec<LayerExtent>,
+    // Names of the styles stored when LoadOptions::write_layer_styles is enabled
+    pub layer_styles: Vec<String>,
+    // Set when LoadOptions::data_dictionary is enabled
+    pub data_dictionary: Option<DataDictionary>,
+    // Retries of Postgres steps under LoadOptions::retry_policy
+    pub retries: u32,
+    // Set when LoadOptions::transfer_verification is enabled
+    pub verification: Option<VerificationReport>,
+    // Set when an append reconciled the table's columns under LoadOptions::schema_evolution
+    pub schema_evolution: Option<SchemaEvolutionReport>,
+    // Set for PostGIS and custom sinks
+    pub sink_report: Option<SinkReport>,
+    // Set when LoadOptions::resource_sample_interval is enabled
+    pub resource_usage: Option<ResourceUsage>,
+    // Coordinate columns found by LoadOptions::detect_point_columns, whether or not they were confirmed
+    pub detected_point_columns: Option<PointColumns>,
+    // One per LoadOptions::replica_connections, in order
+    pub replicas: Vec<ReplicaResult>,
+    // Table and column names changed by LoadOptions::name_normalization, the table first
+    pub normalized_names: Vec<NameMapping>,
+    // (file path, encoding) of CSV and Shapefile sources as they were read, e.g. 'windows-1252'
+    pub source_encodings: Vec<(String, String)>,
+    // BLAKE3 fingerprint of the source under LoadOptions::skip_unchanged
+    pub fingerprint: Option<String>,
+    // The fingerprint matched the table's latest load, so nothing was read or written
+    pub skipped_unchanged: bool,
+}
+
+// Outcome of applying a load to one of LoadOptions::replica_connections
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicaResult {
+    // Connection string with any password redacted
+    pub connection: String,
+    pub retries: u32,
+    pub verification: Option<VerificationReport>,
+    // Set when the load failed on this replica, leaving its previous table in place
+    pub error: Option<String>,
+}
+
+// Bounding box, geometry types and vertex counts of a geometry column, computed in DuckDB before the transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeometryStatistics {
+    pub column: String,
+    pub srid: String,
+    // None when the column holds no non-empty geometries
+    pub extent: Option<Extent>,
+    // (geometry type, count) pairs, most common first
+    pub type_counts: Vec<(String, u64)>,
+    pub null_count: u64,
+    pub empty_count: u64,
+    pub total_vertices: u64,
+    pub max_vertices: u64,
+}
+
+// Extent and feature count of a loaded geometry column, as recorded in the layer_extents table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerExtent {
+    pub column: String,
+    pub srid: String,
+    // None when the column holds no geometries
+    pub extent: Option<Extent>,
+    pub feature_count: u64,
+}
+
+// Bounding box of a geometry column in the target CRS
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Extent {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Extent {
+    pub fn area(&self) -> f64 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+
+    pub(crate) fn to_wkt(self) -> String {
+        format!(
+            "POLYGON(({0} {1}, {2} {1}, {2} {3}, {0} {3}, {0} {1}))",
+            self.min_x, self.min_y, self.max_x, self.max_y
+        )
+    }
+}
+
+// Comparison of the rows sent to Postgres with the rows that arrived in the staging table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub source_rows: u64,
+    pub target_rows: u64,
+    pub sampled_rows: u64,
+    // Sampled rows with no identical row in Postgres
+    pub sample_mismatches: u64,
+}
+
+impl VerificationReport {
+    pub fn passed(&self) -> bool {
+        self.source_rows == self.target_rows && self.sample_mismatches == 0
+    }
+}
+
+// Comparison of a replace-mode load against the version of the table it replaced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub previous_row_count: u64,
+    pub row_count: u64,
+    pub previous_extent: Option<Extent>,
+    pub extent: Option<Extent>,
+    // True when any threshold was exceeded
+    pub suspicious: bool,
+    pub reasons: Vec<String>,
+}
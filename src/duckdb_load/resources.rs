@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Linux reports CPU time in clock ticks of 1/100 s on all mainstream architectures
+const CLOCK_TICKS_PER_SECOND: f64 = 100.0;
+
+// Memory and CPU use of the loader process while a load ran
+// CPU percentages are of one core, so a load using four cores fully reports 400
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub peak_rss_bytes: u64,
+    pub average_rss_bytes: u64,
+    pub peak_cpu_percent: f64,
+    pub average_cpu_percent: f64,
+    pub samples: u32,
+}
+
+// Samples the process from a background thread until finished or dropped
+pub(crate) struct ResourceSampler {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Option<ResourceUsage>>>,
+}
+
+impl ResourceSampler {
+    pub(crate) fn start(interval: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let first_cpu = cpu_seconds()?;
+            let mut usage = ResourceUsage::default();
+            let mut total_rss = 0u64;
+            let mut last = (started, first_cpu);
+            loop {
+                // Wake often enough to notice the load finishing without waiting out a long interval
+                let wake = Instant::now() + interval;
+                while Instant::now() < wake && !stopped.load(Ordering::Relaxed) {
+                    thread::sleep(interval.min(Duration::from_millis(20)));
+                }
+
+                let now = Instant::now();
+                let (Some(rss), Some(cpu)) = (rss_bytes(), cpu_seconds()) else {
+                    break;
+                };
+                let elapsed = now.duration_since(last.0).as_secs_f64();
+                if elapsed > 0.0 {
+                    let cpu_percent = (cpu - last.1) / elapsed * 100.0;
+                    usage.peak_cpu_percent = usage.peak_cpu_percent.max(cpu_percent);
+                }
+                usage.peak_rss_bytes = usage.peak_rss_bytes.max(rss);
+                total_rss += rss;
+                usage.samples += 1;
+                last = (now, cpu);
+
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            if usage.samples == 0 {
+                return None;
+            }
+            usage.average_rss_bytes = total_rss / usage.samples as u64;
+            let elapsed = last.0.duration_since(started).as_secs_f64();
+            if elapsed > 0.0 {
+                usage.average_cpu_percent = (last.1 - first_cpu) / elapsed * 100.0;
+            }
+            Some(usage)
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    // None when the platform has no /proc to read
+    pub(crate) fn finish(mut self) -> Option<ResourceUsage> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.take()?.join().ok().flatten()
+    }
+}
+
+// A failed load stops the thread without waiting for it
+impl Drop for ResourceSampler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn rss_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+// User and system CPU time of the process so far
+fn cpu_seconds() -> Option<f64> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so fields are counted from after its closing parenthesis
+    let fields = stat[stat.rfind(')')? + 1..]
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let user: f64 = fields.get(11)?.parse().ok()?;
+    let system: f64 = fields.get(12)?.parse().ok()?;
+    Some((user + system) / CLOCK_TICKS_PER_SECOND)
+}
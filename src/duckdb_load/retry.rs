@@ -0,0 +1,84 @@
+use super::LoadCancelledError;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+// Steps that talk to Postgres and can be retried after a dropped connection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RetryStage {
+    // Attaching the PostGIS database
+    Attach,
+    // Copying the transformed data into Postgres - the database is re-attached under a new alias before each retry
+    // Appends to an existing table are never retried, as one whose commit was lost in transit would insert twice
+    Transfer,
+}
+
+// How often and how patiently transient Postgres failures are retried
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Attempts per stage including the first - 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    // Each wait is this many times the previous one, up to max_backoff
+    pub backoff_multiplier: f64,
+    pub max_backoff: Duration,
+    pub stages: Vec<RetryStage>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(500),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(30),
+            stages: vec![RetryStage::Attach, RetryStage::Transfer],
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Runs an operation, retrying it if the stage is retryable - the operation is passed the attempt number from 1
+    // Returns the result with the number of retries it took
+    pub(crate) fn run<T>(
+        &self,
+        stage: RetryStage,
+        mut operation: impl FnMut(u32) -> Result<T, Box<dyn Error>>,
+    ) -> Result<(T, u32), Box<dyn Error>> {
+        let max_attempts = if self.stages.contains(&stage) {
+            self.max_attempts.max(1)
+        } else {
+            1
+        };
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match operation(attempt) {
+                Ok(value) => return Ok((value, attempt - 1)),
+                // A cancelled load is never retried
+                Err(e) if e.is::<LoadCancelledError>() => return Err(e),
+                Err(e) if attempt < max_attempts => {
+                    println!(
+                        "{:?} failed on attempt {} of {}, retrying in {:?}: {}",
+                        stage, attempt, max_attempts, backoff, e
+                    );
+                    thread::sleep(backoff);
+                    backoff = Duration::try_from_secs_f64(
+                        backoff.as_secs_f64() * self.backoff_multiplier,
+                    )
+                    .unwrap_or(self.max_backoff)
+                    .min(self.max_backoff);
+                    attempt += 1;
+                }
+                Err(e) if max_attempts > 1 => {
+                    return Err(
+                        format!("{:?} failed after {} attempts: {}", stage, attempt, e).into(),
+                    )
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
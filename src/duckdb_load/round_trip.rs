@@ -0,0 +1,108 @@
+use super::options::LoadOptions;
+use super::staging;
+use serde::{Deserialize, Serialize};
+
+// Options for checking how faithfully a file survives a load into PostGIS and an export back out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoundTripOptions {
+    // Geometries are compared after snapping to a grid of this size and numbers may differ by this much
+    pub tolerance: f64,
+    // Column identifying a row in both copies - None pairs rows by their order
+    pub key_column: Option<String>,
+    pub load_options: LoadOptions,
+}
+
+impl Default for RoundTripOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-9,
+            key_column: None,
+            load_options: LoadOptions::default(),
+        }
+    }
+}
+
+// Number of paired rows whose values differ in a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMismatch {
+    pub column: String,
+    pub mismatched_rows: u64,
+}
+
+// Differences between a source file, reprojected to the target CRS, and the same data exported back from PostGIS
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoundTripReport {
+    pub source_rows: u64,
+    pub round_trip_rows: u64,
+    // Source columns that did not come back
+    pub missing_columns: Vec<String>,
+    pub geometry_mismatches: Vec<ColumnMismatch>,
+    pub attribute_mismatches: Vec<ColumnMismatch>,
+}
+
+impl RoundTripReport {
+    pub fn is_lossless(&self) -> bool {
+        self.source_rows == self.round_trip_rows
+            && self.missing_columns.is_empty()
+            && self.geometry_mismatches.is_empty()
+            && self.attribute_mismatches.is_empty()
+    }
+}
+
+// Condition matching paired values that are equal within the tolerance, treating two NULLs as equal
+pub(crate) fn geometry_matches(source: &str, round_trip: &str, tolerance: f64) -> String {
+    let equal = if tolerance > 0.0 {
+        format!(
+            "ST_Equals(ST_ReducePrecision({}, {}), ST_ReducePrecision({}, {}))",
+            source, tolerance, round_trip, tolerance
+        )
+    } else {
+        format!("ST_Equals({}, {})", source, round_trip)
+    };
+    format!(
+        "coalesce({}, {} IS NULL AND {} IS NULL)",
+        equal, source, round_trip
+    )
+}
+
+pub(crate) fn attribute_matches(
+    source: &str,
+    round_trip: &str,
+    numeric: bool,
+    tolerance: f64,
+) -> String {
+    if numeric {
+        format!(
+            "coalesce(abs({} - {}) <= {}, {} IS NULL AND {} IS NULL)",
+            source, round_trip, tolerance, source, round_trip
+        )
+    } else {
+        format!(
+            "{}::VARCHAR IS NOT DISTINCT FROM {}::VARCHAR",
+            source, round_trip
+        )
+    }
+}
+
+// The table exported for comparison, in a file named uniquely so concurrent round trips of a table don't share it
+// The file is removed when this is dropped, however the round trip ends
+pub(crate) struct ExportedFile {
+    pub(crate) path: String,
+}
+
+impl ExportedFile {
+    pub(crate) fn new() -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("{}_round_trip.parquet", staging::new_table_name()))
+            .to_string_lossy()
+            .to_string();
+        Self { path }
+    }
+}
+
+impl Drop for ExportedFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
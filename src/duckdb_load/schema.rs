@@ -0,0 +1,46 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+
+// Version of the JSON form of the config and report types, bumped whenever a change would stop older
+// documents from reading back the same way - added fields with defaults do not need a bump
+pub const SCHEMA_VERSION: u32 = 1;
+
+// JSON with a top-level schema_version member, for exchanging config and reports with other processes
+// Implemented for every serializable type, e.g. LoadOptions, LoadResult and RoundTripReport
+pub trait VersionedJson: Serialize + DeserializeOwned {
+    fn to_versioned_json(&self) -> Result<String, Box<dyn Error>> {
+        let mut document = match serde_json::to_value(self)? {
+            Value::Object(members) => members,
+            _ => return Err("Only structs can be written as versioned JSON".into()),
+        };
+        document.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        Ok(serde_json::to_string_pretty(&document)?)
+    }
+
+    fn from_versioned_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let mut document = match serde_json::from_str(json)? {
+            Value::Object(members) => members,
+            _ => return Err("Versioned JSON must be an object".into()),
+        };
+        match document
+            .remove("schema_version")
+            .as_ref()
+            .and_then(Value::as_u64)
+        {
+            Some(version) if version == SCHEMA_VERSION as u64 => {}
+            Some(version) => {
+                return Err(format!(
+                    "Unsupported schema version {}, expected {}",
+                    version, SCHEMA_VERSION
+                )
+                .into())
+            }
+            None => return Err("Missing schema_version".into()),
+        }
+        Ok(serde_json::from_value(Value::Object(document))?)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> VersionedJson for T {}
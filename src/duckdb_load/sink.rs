@@ -0,0 +1,501 @@
+use super::catalog::identifier;
+use super::evolution::SchemaEvolutionReport;
+use super::geometry::GeometryEncoding;
+use super::options::LoadOptions;
+use super::query_log::LoggedConnection;
+use super::report::{Extent, GeometryColumnType, VerificationReport};
+use super::staging;
+use super::{geometry_extent, require_spatial};
+use duckdb::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// Destination the transformed data is written to
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Sink {
+    // Replace the table in the attached PostGIS database
+    #[default]
+    PostGis,
+    Parquet(ParquetSink),
+    // Write a GeoJSON, newline-delimited GeoJSON or CSV file for downloads
+    Export(ExportSink),
+    // Create the table in a file-backed DuckDB database
+    DuckDb(DuckDbSink),
+    // A destination outside the crate - set in code, so it is never serialized
+    #[serde(skip)]
+    Custom(CustomSink),
+}
+
+impl Sink {
+    // DuckDB and Parquet sinks skip the WKB round trip and read the transformed geometries directly
+    pub(crate) fn geometry_encoding(&self) -> GeometryEncoding {
+        match self {
+            Sink::Parquet(_) | Sink::DuckDb(_) => GeometryEncoding::Native,
+            Sink::PostGis | Sink::Export(_) | Sink::Custom(_) => GeometryEncoding::Wkb,
+        }
+    }
+
+    // Writes a table of attribute and geometry columns, held as the encoding says, to a file sink
+    pub(crate) fn write_file(
+        &self,
+        conn: &LoggedConnection,
+        source_table: &str,
+        table_name: &str,
+        column_types: &[GeometryColumnType],
+        encoding: GeometryEncoding,
+    ) -> Result<(), Box<dyn Error>> {
+        let source = SinkSource {
+            table: source_table,
+            column_types,
+            encoding,
+        };
+        match self {
+            Sink::PostGis | Sink::Custom(_) => Err("Not a file sink".into()),
+            Sink::Parquet(sink) => sink.write(conn, &source, table_name),
+            Sink::Export(sink) => sink.write(conn, &source, table_name),
+            Sink::DuckDb(sink) => sink.write(conn, &source, table_name),
+        }
+    }
+}
+
+// Writes the transformed table to a destination, e.g. PostGisSink
+// Implement it for destinations the crate doesn't cover and load with Sink::Custom
+pub trait SinkWriter: Send + Sync {
+    // source_table holds the attribute columns as they are and each geometry column as WKB in a BLOB
+    // column named {column}_wkb, in the target CRS
+    fn write(
+        &self,
+        conn: &Connection,
+        source_table: &str,
+        context: &SinkContext,
+    ) -> Result<SinkReport, Box<dyn Error>>;
+
+    // Shown in Debug output of the options
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+}
+
+// What a SinkWriter is told about the load
+pub struct SinkContext<'a> {
+    // Target table name, after any name normalization
+    pub table_name: &'a str,
+    // One per geometry column of the source table, with the geometry type and SRID it was resolved to
+    pub column_types: &'a [GeometryColumnType],
+    pub options: &'a LoadOptions,
+}
+
+// Outcome of a SinkWriter::write, kept as LoadResult::sink_report
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SinkReport {
+    pub rows_written: u64,
+    // Added to LoadResult::retries
+    pub retries: u32,
+    // Copied to LoadResult::verification
+    pub verification: Option<VerificationReport>,
+    // Copied to LoadResult::schema_evolution
+    pub schema_evolution: Option<SchemaEvolutionReport>,
+    // Names of the tables built under LoadOptions::companion_tables, e.g. 'roads_centroid'
+    pub companion_tables: Vec<String>,
+}
+
+// A SinkWriter shared between clones of the options
+#[derive(Clone)]
+pub struct CustomSink(pub Arc<dyn SinkWriter>);
+
+impl CustomSink {
+    pub fn new(writer: impl SinkWriter + 'static) -> Self {
+        Self(Arc::new(writer))
+    }
+}
+
+impl fmt::Debug for CustomSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CustomSink").field(&self.0.name()).finish()
+    }
+}
+
+// Two custom sinks are equal when they share the same writer
+impl PartialEq for CustomSink {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Table a file sink reads from
+struct SinkSource<'a> {
+    table: &'a str,
+    column_types: &'a [GeometryColumnType],
+    encoding: GeometryEncoding,
+}
+
+// Compression codec used for Parquet column chunks
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ParquetCompression {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    #[default]
+    Zstd,
+}
+
+impl ParquetCompression {
+    fn codec(&self) -> &'static str {
+        match self {
+            ParquetCompression::Uncompressed => "uncompressed",
+            ParquetCompression::Snappy => "snappy",
+            ParquetCompression::Gzip => "gzip",
+            ParquetCompression::Zstd => "zstd",
+        }
+    }
+}
+
+// Writes the data to a Parquet file with GeoParquet metadata describing the geometry columns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParquetSink {
+    // Local path or s3:// URL - '{table}' is replaced with the table name so directory loads write one file each
+    pub path: String,
+    pub compression: ParquetCompression,
+    // Rows per row group - None keeps the DuckDB default
+    pub row_group_size: Option<u64>,
+}
+
+impl ParquetSink {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            compression: ParquetCompression::default(),
+            row_group_size: None,
+        }
+    }
+
+    fn write(
+        &self,
+        conn: &LoggedConnection,
+        source: &SinkSource,
+        table_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // S3 credentials are picked up from the environment or AWS config
+        if self.path.starts_with("s3://") {
+            conn.execute("INSTALL httpfs;", [])?;
+            conn.execute("LOAD httpfs;", [])?;
+            conn.execute("INSTALL aws;", [])?;
+            conn.execute("LOAD aws;", [])?;
+            conn.execute(
+                "CREATE SECRET IF NOT EXISTS gridwalk_s3 (TYPE S3, PROVIDER CREDENTIAL_CHAIN);",
+                [],
+            )?;
+        }
+
+        // GeoParquet stores geometries as WKB
+        let select = output_select(source, |_, geometry| format!("ST_AsWKB({})", geometry));
+
+        let mut geo_columns = Vec::new();
+        for column_type in source.column_types {
+            geo_columns.push((
+                column_type.clone(),
+                geometry_extent(conn, source.table, &column_type.column, source.encoding)?,
+            ));
+        }
+        let mut projjson = HashMap::new();
+        for (column_type, _) in &geo_columns {
+            if column_type.srid != "4326" && !projjson.contains_key(&column_type.srid) {
+                projjson.insert(
+                    column_type.srid.clone(),
+                    crs_projjson(conn, &column_type.srid)?,
+                );
+            }
+        }
+        let geo_metadata = geoparquet_metadata(&geo_columns, &projjson);
+
+        let output_path = self.path.replace("{table}", table_name);
+        conn.execute(
+            &format!(
+                "COPY (SELECT {} FROM {}) TO '{}' ({});",
+                select,
+                source.table,
+                output_path,
+                self.copy_options(geo_metadata.as_deref())
+            ),
+            [],
+        )?;
+
+        println!(
+            "Table {} written to {} with geometry columns: {:?}",
+            table_name,
+            output_path,
+            source
+                .column_types
+                .iter()
+                .map(|column_type| &column_type.column)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    // Options for the COPY statement, including the GeoParquet 'geo' key-value metadata
+    fn copy_options(&self, geo_metadata: Option<&str>) -> String {
+        let mut options = vec![
+            "FORMAT PARQUET".to_string(),
+            format!("COMPRESSION {}", self.compression.codec()),
+        ];
+        if let Some(row_group_size) = self.row_group_size {
+            options.push(format!("ROW_GROUP_SIZE {}", row_group_size));
+        }
+        if let Some(geo_metadata) = geo_metadata {
+            options.push(format!(
+                "KV_METADATA {{geo: '{}'}}",
+                geo_metadata.replace('\'', "''")
+            ));
+        }
+        options.join(", ")
+    }
+}
+
+// File formats a load can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    GeoJson,
+    // One feature per line (RFC 8142)
+    GeoJsonSeq,
+    // Geometries are written as WKT
+    Csv,
+}
+
+// Writes the data to a file through DuckDB's COPY - GeoJSON formats use the spatial extension's GDAL writer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportSink {
+    // '{table}' is replaced with the table name so directory loads write one file each
+    pub path: String,
+    pub format: ExportFormat,
+}
+
+impl ExportSink {
+    pub fn new(path: &str, format: ExportFormat) -> Self {
+        Self {
+            path: path.to_string(),
+            format,
+        }
+    }
+
+    fn write(
+        &self,
+        conn: &LoggedConnection,
+        source: &SinkSource,
+        table_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // GDAL writes a single geometry field, so any further geometry columns are exported as WKT
+        let writes_geometry = self.format != ExportFormat::Csv;
+        if writes_geometry {
+            require_spatial(&format!("Writing {:?}", self.format))?;
+        }
+        let select = output_select(source, |i, geometry| {
+            if i == 0 && writes_geometry {
+                geometry
+            } else {
+                format!("ST_AsText({})", geometry)
+            }
+        });
+        let srid = source
+            .column_types
+            .first()
+            .map_or("4326", |column_type| column_type.srid.as_str());
+
+        let output_path = self.path.replace("{table}", table_name);
+        conn.execute(
+            &format!(
+                "COPY (SELECT {} FROM {}) TO '{}' ({});",
+                select,
+                source.table,
+                output_path,
+                self.copy_options(srid)
+            ),
+            [],
+        )?;
+
+        println!(
+            "Table {} exported to {} as {:?}",
+            table_name, output_path, self.format
+        );
+        Ok(())
+    }
+
+    fn copy_options(&self, srid: &str) -> String {
+        match self.format {
+            ExportFormat::GeoJson => {
+                format!("FORMAT GDAL, DRIVER 'GeoJSON', SRS 'EPSG:{}'", srid)
+            }
+            ExportFormat::GeoJsonSeq => {
+                format!("FORMAT GDAL, DRIVER 'GeoJSONSeq', SRS 'EPSG:{}'", srid)
+            }
+            ExportFormat::Csv => "FORMAT CSV, HEADER".to_string(),
+        }
+    }
+}
+
+// Stages the data in a DuckDB database file for analysts, created if it doesn't exist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuckDbSink {
+    pub path: String,
+}
+
+impl DuckDbSink {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+
+    fn write(
+        &self,
+        conn: &LoggedConnection,
+        source: &SinkSource,
+        table_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // The database stays attached so later loads in the same context reuse it
+        let database = self.database_alias();
+        conn.execute(
+            &format!("ATTACH IF NOT EXISTS '{}' AS {};", self.path, database),
+            [],
+        )?;
+
+        // Geometries are stored natively so analysts can query them with the spatial extension
+        let select = output_select(source, |_, geometry| geometry);
+        conn.execute(
+            &format!(
+                "CREATE OR REPLACE TABLE {}.{} AS SELECT {} FROM {};",
+                database, table_name, select, source.table
+            ),
+            [],
+        )?;
+
+        println!(
+            "Table {} created in DuckDB database {}",
+            table_name, self.path
+        );
+        Ok(())
+    }
+
+    // Loads writing to the same file share one attachment, named after a hash of its path
+    fn database_alias(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        format!("duckdb_sink_{:x}", hasher.finish())
+    }
+}
+
+// Select list for writing the source table outside PostGIS
+// Each geometry column is read as a geometry, promoted to its multi type when required, and passed through
+// encode before being written under the original column name
+fn output_select(source: &SinkSource, encode: impl Fn(usize, String) -> String) -> String {
+    if source.column_types.is_empty() {
+        return "*".to_string();
+    }
+    let stored_columns = source
+        .column_types
+        .iter()
+        .map(|column_type| identifier(&source.encoding.stored_column(&column_type.column)))
+        .collect::<Vec<_>>();
+    let geometries = source
+        .column_types
+        .iter()
+        .enumerate()
+        .map(|(i, column_type)| {
+            let geometry = source.encoding.geometry(&column_type.column);
+            let geometry = if column_type.promoted_to_multi {
+                format!("ST_Multi({})", geometry)
+            } else {
+                geometry
+            };
+            format!(
+                "{} AS {}",
+                encode(i, geometry),
+                identifier(&column_type.column)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "* EXCLUDE ({}), {}",
+        stored_columns.join(", "),
+        geometries.join(", ")
+    )
+}
+
+// GeoParquet 1.0.0 'geo' metadata for WKB encoded geometry columns
+// The first column is the primary one, matching the first geometry column in the source
+// PROJJSON of an EPSG code as PROJ describes it - DuckDB has no function returning it, so it is read back
+// through GDAL from a one-row GeoPackage written with the CRS
+fn crs_projjson(conn: &LoggedConnection, srid: &str) -> Result<Value, Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!("{}.gpkg", staging::new_table_name()));
+    let path = path.to_string_lossy();
+    let projjson = conn
+        .execute(
+            &format!(
+                "COPY (SELECT ST_Point(0, 0) AS geom) TO '{}' (FORMAT GDAL, DRIVER 'GPKG', SRS 'EPSG:{}');",
+                path, srid
+            ),
+            [],
+        )
+        .and_then(|_| {
+            conn.query_row(
+                &format!(
+                    "SELECT layers[1].geometry_fields[1].crs.projjson FROM st_read_meta('{}');",
+                    path
+                ),
+                [],
+                |row| row.get::<_, Option<String>>(0),
+            )
+        });
+    let _ = fs::remove_file(&*path);
+    let projjson = projjson?.ok_or_else(|| format!("PROJ has no definition of EPSG:{}", srid))?;
+    Ok(serde_json::from_str(&projjson)?)
+}
+
+// projjson holds the PROJJSON of each SRID other than 4326
+fn geoparquet_metadata(
+    columns: &[(GeometryColumnType, Option<Extent>)],
+    projjson: &HashMap<String, Value>,
+) -> Option<String> {
+    let (primary, _) = columns.first()?;
+
+    let mut column_metadata = Map::new();
+    for (column_type, extent) in columns {
+        // A column holding mixed types is written as 'Geometry', which GeoParquet expresses as an empty list
+        // GeoParquet has no M types, so M values only show in the WKB
+        let geometry_types = if column_type.geometry_type == "Geometry" {
+            Vec::new()
+        } else if column_type.has_z {
+            vec![format!("{} Z", column_type.geometry_type)]
+        } else {
+            vec![column_type.geometry_type.clone()]
+        };
+        let mut metadata = json!({
+            "encoding": "WKB",
+            "geometry_types": geometry_types,
+        });
+
+        // A missing crs means OGC:CRS84, which is what EPSG:4326 holds once transformed with always_xy
+        if let Some(crs) = projjson.get(&column_type.srid) {
+            metadata["crs"] = crs.clone();
+        }
+        if let Some(extent) = extent {
+            metadata["bbox"] = json!([extent.min_x, extent.min_y, extent.max_x, extent.max_y]);
+        }
+        column_metadata.insert(column_type.column.clone(), metadata);
+    }
+
+    let metadata = json!({
+        "version": "1.0.0",
+        "primary_column": primary.column,
+        "columns": Value::Object(column_metadata),
+    });
+    Some(metadata.to_string())
+}
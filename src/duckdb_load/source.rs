@@ -0,0 +1,155 @@
+use super::options::LoadOptions;
+use super::FileType;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+// How sure a detector is that a file is in its format
+// Magic bytes are High, structural checks such as GeoJSON's members Medium, and loose text heuristics Low
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+// A file format added by downstream code, e.g. OSM PBF or GPX, registered with SourceRegistry::register
+pub trait SourceHandler: Send + Sync {
+    // Reported as the file type of loads and inspections, e.g. 'Gpx'
+    fn name(&self) -> String;
+
+    // Given at most the first 64 KiB of the file - None when it is not in this format
+    fn detect(&self, prefix: &[u8]) -> Option<Confidence>;
+
+    // SELECT reading the file, which becomes the data table of the load
+    fn ingest_sql(&self, path: &str, options: &LoadOptions) -> Result<String, Box<dyn Error>>;
+
+    // Statements run before the query, e.g. INSTALL and LOAD of the extension that reads the format
+    fn setup_sql(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // File name endings without the leading dot, e.g. 'osm.pbf', used when no detector recognises the
+    // content and to pick files out of a directory
+    fn file_extensions(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    // EPSG code of the geometries the query returns when the format fixes one, e.g. '4326' for GPX
+    // LoadOptions::source_crs takes precedence
+    fn crs(&self, _path: &str) -> Option<String> {
+        None
+    }
+}
+
+// Formats consulted alongside the built-in ones - the most confident detector wins, and registered
+// handlers win ties so they can take over a built-in format
+#[derive(Clone, Default)]
+pub struct SourceRegistry {
+    handlers: Vec<Arc<dyn SourceHandler>>,
+}
+
+impl SourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, handler: impl SourceHandler + 'static) -> &mut Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.handlers.iter().map(|handler| handler.name()).collect()
+    }
+
+    // Registered handler or built-in type that is most confident about the start of a file
+    pub fn detect(&self, prefix: &[u8]) -> Option<DetectedSource> {
+        // Earlier registrations win ties between handlers
+        let registered = self
+            .handlers
+            .iter()
+            .rev()
+            .filter_map(|handler| Some((handler.detect(prefix)?, handler)))
+            .max_by_key(|(confidence, _)| *confidence);
+        let built_in = FileType::BUILT_IN
+            .iter()
+            .filter_map(|file_type| Some((file_type.detect(prefix)?, *file_type)))
+            .max_by_key(|(confidence, _)| *confidence);
+
+        match (registered, built_in) {
+            (Some((registered_confidence, handler)), Some((confidence, _)))
+                if registered_confidence >= confidence =>
+            {
+                Some(DetectedSource::Registered(handler.clone()))
+            }
+            (Some((_, handler)), None) => Some(DetectedSource::Registered(handler.clone())),
+            (_, Some((_, file_type))) => Some(DetectedSource::BuiltIn(file_type)),
+            (None, None) => None,
+        }
+    }
+
+    // Registered handler claiming the file's extension
+    pub(crate) fn handler_for_extension(&self, file_path: &str) -> Option<Arc<dyn SourceHandler>> {
+        let file_path = file_path.to_lowercase();
+        self.handlers
+            .iter()
+            .find(|handler| {
+                handler
+                    .file_extensions()
+                    .iter()
+                    .any(|extension| file_path.ends_with(&format!(".{}", extension.to_lowercase())))
+            })
+            .cloned()
+    }
+}
+
+impl fmt::Debug for SourceRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+// Format a file was detected as
+#[derive(Clone)]
+pub enum DetectedSource {
+    BuiltIn(FileType),
+    Registered(Arc<dyn SourceHandler>),
+}
+
+impl DetectedSource {
+    pub fn name(&self) -> String {
+        match self {
+            DetectedSource::BuiltIn(file_type) => format!("{:?}", file_type),
+            DetectedSource::Registered(handler) => handler.name(),
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self {
+            DetectedSource::BuiltIn(file_type) => *file_type,
+            DetectedSource::Registered(_) => FileType::Registered,
+        }
+    }
+
+    pub(crate) fn handler(&self) -> Option<Arc<dyn SourceHandler>> {
+        match self {
+            DetectedSource::BuiltIn(_) => None,
+            DetectedSource::Registered(handler) => Some(handler.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for DetectedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectedSource::BuiltIn(file_type) => {
+                f.debug_tuple("BuiltIn").field(file_type).finish()
+            }
+            DetectedSource::Registered(handler) => {
+                f.debug_tuple("Registered").field(&handler.name()).finish()
+            }
+        }
+    }
+}
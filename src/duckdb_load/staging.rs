@@ -0,0 +1,84 @@
+use super::query_log::LoggedConnection;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Postgres schema holding tables mid-load, kept apart from published tables
+pub(crate) const STAGING_SCHEMA: &str = "gridwalk_staging";
+
+static NEXT_STAGING_ID: AtomicU64 = AtomicU64::new(0);
+
+// Name for a new staging table, unique across loads and processes and well within Postgres's 63 byte limit
+pub(crate) fn new_table_name() -> String {
+    let mut hasher = DefaultHasher::new();
+    std::process::id().hash(&mut hasher);
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos())
+        .hash(&mut hasher);
+    NEXT_STAGING_ID
+        .fetch_add(1, Ordering::Relaxed)
+        .hash(&mut hasher);
+    format!("gridwalk_{:016x}", hasher.finish())
+}
+
+// Each staging table is commented with its creation time in seconds since the epoch,
+// as Postgres does not record when a table was created
+pub(crate) fn creation_comment(staging_table: &str) -> String {
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    format!(
+        "COMMENT ON TABLE {}.{} IS '{}';",
+        STAGING_SCHEMA, staging_table, created
+    )
+}
+
+// Drops staging tables left behind by crashed runs once they are older than max_age, returning their names
+// Tables without a creation comment were not made by the loader and are left alone
+pub(crate) fn drop_stale_tables(
+    conn: &LoggedConnection,
+    database: &str,
+    max_age: Duration,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let query = format!(
+        "SELECT c.relname::text FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = '{}' AND c.relkind = 'r'
+        AND obj_description(c.oid, 'pg_class') ~ '^[0-9]+$'
+        AND to_timestamp(obj_description(c.oid, 'pg_class')::bigint) < now() - make_interval(secs => {})",
+        STAGING_SCHEMA,
+        max_age.as_secs()
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM postgres_query('{}', '{}');",
+        database,
+        query.replace('\'', "''")
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut stale_tables = Vec::new();
+    while let Some(row) = rows.next()? {
+        stale_tables.push(row.get::<_, String>(0)?);
+    }
+
+    if !stale_tables.is_empty() {
+        let drops = stale_tables
+            .iter()
+            .map(|table| format!("DROP TABLE IF EXISTS {}.\"{}\";", STAGING_SCHEMA, table))
+            .collect::<Vec<_>>();
+        conn.execute(
+            &format!(
+                "CALL postgres_execute('{}', '{}');",
+                database,
+                drops.join("\n").replace('\'', "''")
+            ),
+            [],
+        )?;
+        println!(
+            "Dropped stale staging tables from {}: {:?}",
+            STAGING_SCHEMA, stale_tables
+        );
+    }
+    Ok(stale_tables)
+}
@@ -0,0 +1,176 @@
+use super::query_log::LoggedConnection;
+use super::report::GeometryColumnType;
+use super::FileType;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// A QGIS layer style found with the source data
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LayerStyle {
+    pub(crate) name: String,
+    pub(crate) qml: Option<String>,
+    pub(crate) sld: Option<String>,
+    pub(crate) use_as_default: bool,
+    pub(crate) description: Option<String>,
+}
+
+// Styles accompanying a file - .qml and .sld sidecars sharing its name, and for GeoPackages the rows of
+// its layer_styles table that belong to the layer being read
+pub(crate) fn find_styles(
+    conn: &LoggedConnection,
+    file_path: &str,
+    file_type: &FileType,
+    layer: Option<&str>,
+) -> Result<Vec<LayerStyle>, Box<dyn Error>> {
+    let mut styles = Vec::new();
+    if *file_type == FileType::Geopackage {
+        styles.extend(geopackage_styles(conn, file_path, layer)?);
+    }
+
+    let path = Path::new(file_path);
+    let read = |extension: &str| fs::read_to_string(path.with_extension(extension)).ok();
+    let (qml, sld) = (read("qml"), read("sld"));
+    if qml.is_some() || sld.is_some() {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        styles.push(LayerStyle {
+            name,
+            qml,
+            sld,
+            // QGIS applies a sidecar when opening the file, unless the GeoPackage names a default style
+            use_as_default: styles.iter().all(|style| !style.use_as_default),
+            description: None,
+        });
+    }
+    Ok(styles)
+}
+
+fn geopackage_styles(
+    conn: &LoggedConnection,
+    file_path: &str,
+    layer: Option<&str>,
+) -> Result<Vec<LayerStyle>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT layer.name FROM (SELECT unnest(layers) AS layer FROM st_read_meta('{}'));",
+        file_path
+    ))?;
+    let layers = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !layers.iter().any(|name| name == "layer_styles") {
+        return Ok(Vec::new());
+    }
+    // Without a layer option the first feature layer is the one loaded
+    let Some(layer) = layer.or_else(|| {
+        layers
+            .iter()
+            .map(String::as_str)
+            .find(|name| *name != "layer_styles")
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT stylename, styleqml, stylesld, coalesce(useasdefault, false), description
+        FROM ST_Read('{}', layer := 'layer_styles')
+        WHERE f_table_name = ?
+        ORDER BY useasdefault DESC, stylename;",
+        file_path
+    ))?;
+    let styles = stmt
+        .query_map([layer], |row| {
+            Ok(LayerStyle {
+                name: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                qml: row.get(1)?,
+                sld: row.get(2)?,
+                use_as_default: row.get(3)?,
+                description: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(styles)
+}
+
+// Postgres statements storing the styles against a loaded table in QGIS's layer_styles table, replacing
+// any earlier styles of the same name
+pub(crate) fn layer_styles_statements(
+    table_name: &str,
+    schema: Option<&str>,
+    column_types: &[GeometryColumnType],
+    styles: &[LayerStyle],
+) -> String {
+    let literal = |value: Option<&str>| match value {
+        Some(value) => format!("'{}'", value.replace('\'', "''")),
+        None => "NULL".to_string(),
+    };
+    let table_literal = literal(Some(table_name));
+    let schema_literal = schema.map_or("current_schema()".to_string(), |schema| {
+        literal(Some(schema))
+    });
+    let geometry = column_types.first();
+    let geometry_column = literal(geometry.map(|column_type| column_type.column.as_str()));
+    let geometry_type =
+        literal(geometry.and_then(|column_type| qgis_geometry_type(&column_type.geometry_type)));
+
+    // Same definition as the table QGIS creates when saving a style to the database
+    let mut statements = vec!["CREATE TABLE IF NOT EXISTS layer_styles (
+        id serial PRIMARY KEY,
+        f_table_catalog varchar,
+        f_table_schema varchar,
+        f_table_name varchar,
+        f_geometry_column varchar,
+        stylename text,
+        styleqml xml,
+        stylesld xml,
+        useasdefault boolean,
+        description text,
+        owner varchar(63) DEFAULT CURRENT_USER,
+        ui xml,
+        update_time timestamp DEFAULT CURRENT_TIMESTAMP,
+        type varchar
+    );"
+    .to_string()];
+    if styles.iter().any(|style| style.use_as_default) {
+        statements.push(format!(
+            "UPDATE layer_styles SET useasdefault = false
+            WHERE f_table_schema = {} AND f_table_name = {};",
+            schema_literal, table_literal
+        ));
+    }
+    for style in styles {
+        let style_name = literal(Some(&style.name));
+        statements.push(format!(
+            "DELETE FROM layer_styles
+            WHERE f_table_schema = {} AND f_table_name = {} AND stylename = {};",
+            schema_literal, table_literal, style_name
+        ));
+        statements.push(format!(
+            "INSERT INTO layer_styles (f_table_catalog, f_table_schema, f_table_name, f_geometry_column,
+                stylename, styleqml, stylesld, useasdefault, description, type)
+            VALUES (current_database(), {}, {}, {}, {}, XMLPARSE(DOCUMENT {}), XMLPARSE(DOCUMENT {}), {}, {}, {});",
+            schema_literal,
+            table_literal,
+            geometry_column,
+            style_name,
+            literal(style.qml.as_deref()),
+            literal(style.sld.as_deref()),
+            style.use_as_default,
+            literal(style.description.as_deref()),
+            geometry_type
+        ));
+    }
+    statements.join("\n")
+}
+
+// QGIS records the geometry family of a styled layer rather than its exact type
+fn qgis_geometry_type(geometry_type: &str) -> Option<&'static str> {
+    match geometry_type {
+        "Point" | "MultiPoint" => Some("Point"),
+        "LineString" | "MultiLineString" => Some("Line"),
+        "Polygon" | "MultiPolygon" => Some("Polygon"),
+        _ => None,
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// SQL the loader runs at each stage, overridable to work around environment-specific quirks
+// Placeholders are written as {name} and must be ones listed for the template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SqlTemplates {
+    // DuckDB statement reading the source data - {table}, {source} (the default reader query) and {path} (the first input file)
+    pub ingest: String,
+    // DuckDB statement copying the transformed data to PostGIS - {database} (the alias the PostGIS database is
+    // attached under), {table}, {source}
+    pub transfer: String,
+    // Postgres statements turning a WKB column into a typed geometry column - {table}, {column}, {wkb_column},
+    // {column_type}, {geometry}, with the columns given as quoted identifiers
+    pub geometry_column: String,
+    // Postgres statement run for each geometry column when create_spatial_index is set - {table}, {column}
+    // (quoted), {index}, the quoted name {name}_{column}_gist, and {name}, the table name without the
+    // LoadOptions::schema qualifier
+    pub spatial_index: String,
+    // Postgres statements run once the table is loaded, skipped when empty - {table}
+    pub post_process: String,
+}
+
+impl Default for SqlTemplates {
+    fn default() -> Self {
+        Self {
+            ingest: "CREATE TABLE {table} AS {source};".to_string(),
+            transfer: "CREATE TABLE {database}.{table} AS SELECT * FROM {source};".to_string(),
+            geometry_column: "ALTER TABLE {table} ADD COLUMN {column} {column_type};
+                UPDATE {table} SET {column} = {geometry};
+                ALTER TABLE {table} DROP COLUMN {wkb_column};"
+                .to_string(),
+            spatial_index: "CREATE INDEX IF NOT EXISTS {index} ON {table} USING GIST ({column});"
+                .to_string(),
+            post_process: String::new(),
+        }
+    }
+}
+
+// Fills the {name} placeholders in a template
+// Braces that don't wrap an identifier, such as DuckDB struct literals, are left as they are
+pub(crate) fn render(template: &str, values: &[(&str, &str)]) -> Result<String, Box<dyn Error>> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        if name_len == 0 || !after[name_len..].starts_with('}') {
+            rendered.push('{');
+            rest = after;
+            continue;
+        }
+
+        let name = &after[..name_len];
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => {
+                return Err(format!(
+                    "Unknown placeholder {{{}}} in SQL template: {}",
+                    name, template
+                )
+                .into())
+            }
+        }
+        rest = &after[name_len + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
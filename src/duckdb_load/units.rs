@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+// Units of measure that numeric columns can be normalised between
+// Conversions are only valid between units of the same dimension
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Unit {
+    Feet,
+    Yards,
+    Miles,
+    Metres,
+    Kilometres,
+    MilesPerHour,
+    KilometresPerHour,
+    MetresPerSecond,
+    SquareFeet,
+    SquareMetres,
+    Acres,
+    Hectares,
+    SquareKilometres,
+    SquareMiles,
+}
+
+#[derive(Debug, PartialEq)]
+enum Dimension {
+    Length,
+    Speed,
+    Area,
+}
+
+impl Unit {
+    // Dimension of the unit and its factor relative to the SI base unit (m, m/s, m²)
+    fn base(&self) -> (Dimension, f64) {
+        match self {
+            Unit::Feet => (Dimension::Length, 0.3048),
+            Unit::Yards => (Dimension::Length, 0.9144),
+            Unit::Miles => (Dimension::Length, 1609.344),
+            Unit::Metres => (Dimension::Length, 1.0),
+            Unit::Kilometres => (Dimension::Length, 1000.0),
+            Unit::MilesPerHour => (Dimension::Speed, 0.44704),
+            Unit::KilometresPerHour => (Dimension::Speed, 1000.0 / 3600.0),
+            Unit::MetresPerSecond => (Dimension::Speed, 1.0),
+            Unit::SquareFeet => (Dimension::Area, 0.09290304),
+            Unit::SquareMetres => (Dimension::Area, 1.0),
+            Unit::Acres => (Dimension::Area, 4046.8564224),
+            Unit::Hectares => (Dimension::Area, 10_000.0),
+            Unit::SquareKilometres => (Dimension::Area, 1_000_000.0),
+            Unit::SquareMiles => (Dimension::Area, 2_589_988.110336),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Feet => "ft",
+            Unit::Yards => "yd",
+            Unit::Miles => "mi",
+            Unit::Metres => "m",
+            Unit::Kilometres => "km",
+            Unit::MilesPerHour => "mph",
+            Unit::KilometresPerHour => "km/h",
+            Unit::MetresPerSecond => "m/s",
+            Unit::SquareFeet => "sq ft",
+            Unit::SquareMetres => "m²",
+            Unit::Acres => "ac",
+            Unit::Hectares => "ha",
+            Unit::SquareKilometres => "km²",
+            Unit::SquareMiles => "sq mi",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+// A declarative conversion of a single numeric column from one unit to another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitConversion {
+    pub column: String,
+    pub from: Unit,
+    pub to: Unit,
+}
+
+impl UnitConversion {
+    pub fn new(column: &str, from: Unit, to: Unit) -> Self {
+        Self {
+            column: column.to_string(),
+            from,
+            to,
+        }
+    }
+
+    // Multiplier applied to each value in the column
+    pub fn factor(&self) -> Result<f64, Box<dyn Error>> {
+        let (from_dimension, from_factor) = self.from.base();
+        let (to_dimension, to_factor) = self.to.base();
+        if from_dimension != to_dimension {
+            return Err(format!(
+                "Cannot convert column {} from {} to {}: units measure different quantities",
+                self.column, self.from, self.to
+            )
+            .into());
+        }
+        Ok(from_factor / to_factor)
+    }
+}
+
+// Checks whether a DuckDB column type can hold a unit conversion
+pub(crate) fn is_numeric_type(data_type: &str) -> bool {
+    let data_type = data_type.to_uppercase();
+    data_type.starts_with("DECIMAL")
+        || matches!(
+            data_type.as_str(),
+            "TINYINT"
+                | "SMALLINT"
+                | "INTEGER"
+                | "BIGINT"
+                | "HUGEINT"
+                | "UTINYINT"
+                | "USMALLINT"
+                | "UINTEGER"
+                | "UBIGINT"
+                | "UHUGEINT"
+                | "FLOAT"
+                | "DOUBLE"
+        )
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+// Stage of a load a validation issue was found in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ValidationCheck {
+    Detection,
+    Schema,
+    Geometry,
+    Crs,
+    Naming,
+    Target,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IssueSeverity {
+    // The load would run, but perhaps not as the user expects
+    Warning,
+    // The load would fail
+    Error,
+}
+
+// What a validation issue is about, for callers that react to particular issues
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IssueCode {
+    // The file type couldn't be detected or the source couldn't be read
+    UnreadableSource,
+    EmptySource,
+    // The source breaks LoadOptions::schema_contract
+    ContractViolation,
+    // A column named in LoadOptions::column_mapping is not in the source
+    UnknownColumn,
+    InvalidGeometries,
+    // A geometry column mixes types that no single PostGIS type other than Geometry holds
+    MixedGeometryTypes,
+    MissingCrs,
+    // The source CRS can't be transformed to the target CRS
+    UnsupportedTransform,
+    // A name would be changed by LoadOptions::name_normalization
+    RenamedIdentifier,
+    // A name Postgres only accepts quoted, e.g. with capitals or spaces
+    QuotedIdentifier,
+    // A name longer than the 63 bytes Postgres keeps
+    LongIdentifier,
+    // Two column names that would end up the same in Postgres
+    DuplicateColumn,
+    // The PostGIS database couldn't be reached or queried
+    TargetUnavailable,
+    // Replace mode would drop an existing table
+    TableReplaced,
+    // Append mode with source columns the table doesn't have, or table columns the source doesn't have
+    ColumnMismatch,
+    PermissionDenied,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub check: ValidationCheck,
+    pub severity: IssueSeverity,
+    pub code: IssueCode,
+    // Column the issue is about - None for issues about the file or target table
+    pub column: Option<String>,
+    pub message: String,
+}
+
+// What a load with the same options would do, and what would go wrong, found without writing anything
+// Checks that depend on an earlier one, such as geometry checks on a source that couldn't be read, are skipped
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationReport {
+    // None when the file type couldn't be detected
+    pub file_type: Option<String>,
+    pub row_count: Option<u64>,
+    // (name, DuckDB type) pairs as read from the source
+    pub columns: Vec<(String, String)>,
+    pub geometry_columns: Vec<String>,
+    // EPSG code of the source - None when there are no geometry columns or it couldn't be found
+    pub crs: Option<String>,
+    // Table the load would write, after LoadOptions::name_normalization
+    pub table_name: String,
+    // Set for the PostGIS sink when the database could be reached
+    pub table_exists: Option<bool>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Warning)
+    }
+
+    // No issue would stop the load
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        check: ValidationCheck,
+        severity: IssueSeverity,
+        code: IssueCode,
+        column: Option<&str>,
+        message: &str,
+    ) {
+        self.issues.push(ValidationIssue {
+            check,
+            severity,
+            code,
+            column: column.map(str::to_string),
+            message: message.to_string(),
+        });
+    }
+}
+
+// Postgres keeps this many bytes of an identifier and silently drops the rest
+pub(crate) const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+// Names Postgres accepts unquoted, as the loader's SQL mostly writes them
+pub(crate) fn is_plain_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_lowercase() || first == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
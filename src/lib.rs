@@ -1 +1,3 @@
 pub mod duckdb_load;
+#[cfg(feature = "python")]
+mod python;
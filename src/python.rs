@@ -0,0 +1,115 @@
+// Python bindings, built by maturin into a gridwalk module with the python feature
+// Keyword options are named after the LoadOptions fields and take their JSON form, e.g.
+// gridwalk.load("roads.gpkg", "roads", dsn, target_crs="27700", load_mode="Append")
+use crate::duckdb_load::{inspect_file, launch_process_file_with_options, LoadOptions};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde::Serialize;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+// Loads a file into a PostGIS table and returns the LoadResult as a dict
+#[pyfunction]
+#[pyo3(signature = (path, table, pg_dsn, **options))]
+fn load(
+    py: Python<'_>,
+    path: &str,
+    table: &str,
+    pg_dsn: &str,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<PyObject> {
+    if let Some(options) = options {
+        if options.contains("postgres_connection")? {
+            return Err(PyValueError::new_err(
+                "Give the connection as pg_dsn rather than postgres_connection",
+            ));
+        }
+    }
+    let mut options = load_options(py, options)?;
+    options.postgres_connection = pg_dsn.to_string();
+    let result = run_interruptible(py, &options, |options| {
+        launch_process_file_with_options(path, table, options)
+    })?;
+    to_python(py, &result)
+}
+
+// Describes a file as a load with these options would read it, and returns the FileInfo as a dict
+#[pyfunction]
+#[pyo3(signature = (path, **options))]
+fn inspect(py: Python<'_>, path: &str, options: Option<&Bound<'_, PyDict>>) -> PyResult<PyObject> {
+    let options = load_options(py, options)?;
+    let info = run_interruptible(py, &options, |options| inspect_file(path, options))?;
+    to_python(py, &info)
+}
+
+// Default options with the keyword arguments laid over them - options that can't be serialized, such as
+// custom sinks and source handlers, can't be given
+fn load_options(py: Python<'_>, options: Option<&Bound<'_, PyDict>>) -> PyResult<LoadOptions> {
+    let mut value = serde_json::to_value(LoadOptions::default()).map_err(value_error)?;
+    if let Some(options) = options {
+        let text: String = py
+            .import_bound("json")?
+            .call_method1("dumps", (options,))?
+            .extract()?;
+        let given: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&text).map_err(value_error)?;
+        let fields = value
+            .as_object_mut()
+            .ok_or_else(|| PyValueError::new_err("LoadOptions is not a JSON object"))?;
+        for (name, option) in given {
+            if !fields.contains_key(&name) {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown load option '{}'",
+                    name
+                )));
+            }
+            fields.insert(name, option);
+        }
+    }
+    serde_json::from_value(value).map_err(value_error)
+}
+
+// Runs a load on another thread so Ctrl-C in a notebook cancels it, interrupting its running query
+fn run_interruptible<T: Send>(
+    py: Python<'_>,
+    options: &LoadOptions,
+    work: impl FnOnce(&LoadOptions) -> Result<T, io::Error> + Send,
+) -> PyResult<T> {
+    thread::scope(|scope| {
+        let handle = scope.spawn(|| work(options));
+        while !handle.is_finished() {
+            py.allow_threads(|| thread::sleep(Duration::from_millis(100)));
+            if let Err(interrupt) = py.check_signals() {
+                options.cancellation.cancel();
+                let _ = handle.join();
+                return Err(interrupt);
+            }
+        }
+        match handle.join() {
+            Ok(result) => result.map_err(PyErr::from),
+            Err(_) => Err(PyRuntimeError::new_err("The load panicked")),
+        }
+    })
+}
+
+// Results go through JSON so they arrive as plain dicts, lists and scalars
+fn to_python(py: Python<'_>, value: &impl Serialize) -> PyResult<PyObject> {
+    let text = serde_json::to_string(value).map_err(value_error)?;
+    Ok(py
+        .import_bound("json")?
+        .call_method1("loads", (text,))?
+        .unbind())
+}
+
+fn value_error(e: serde_json::Error) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+#[pymodule]
+fn gridwalk(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(inspect, m)?)?;
+    Ok(())
+}
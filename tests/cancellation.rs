@@ -0,0 +1,97 @@
+// Cancelling a load interrupts the query it is running rather than waiting for its next stage
+// Run with: GRIDWALK_TEST_PG=postgres://... cargo test --test cancellation
+use duckdb::Connection;
+use duckdb_postgis::duckdb_load::{
+    launch_process_file_with_options, LoadCancelledError, LoadOptions, TransferEngine,
+};
+use postgres::{Client, NoTls};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ROWS: usize = 20_000_000;
+
+fn staging_tables(client: &mut Client) -> Vec<String> {
+    client
+        .query(
+            "SELECT table_name::text FROM information_schema.tables WHERE table_schema = 'gridwalk_staging'",
+            &[],
+        )
+        .expect("staging tables")
+        .iter()
+        .map(|row| row.get(0))
+        .collect()
+}
+
+#[test]
+fn cancelling_interrupts_the_transfer_and_drops_the_staging_table() {
+    let Ok(postgres_connection) = std::env::var("GRIDWALK_TEST_PG") else {
+        println!("Set GRIDWALK_TEST_PG to the Postgres database to load into");
+        return;
+    };
+    let path = std::env::temp_dir().join(format!(
+        "gridwalk_cancellation_{}.parquet",
+        std::process::id()
+    ));
+    Connection::open_in_memory()
+        .and_then(|conn| {
+            conn.execute_batch(&format!(
+                "COPY (SELECT range AS id, 'row ' || range AS name FROM range({})) TO '{}' (FORMAT parquet);",
+                ROWS,
+                path.display()
+            ))
+        })
+        .expect("source file");
+
+    let mut client = Client::connect(&postgres_connection, NoTls).expect("Postgres connection");
+    let existing = staging_tables(&mut client);
+    // Through COPY the staging table is committed empty before the rows are sent, so it can be seen mid-load
+    let options = LoadOptions {
+        postgres_connection,
+        transfer_engine: TransferEngine::Copy,
+        transfer_verification: None,
+        drift_thresholds: None,
+        ..Default::default()
+    };
+    let cancellation = options.cancellation.clone();
+    let load = {
+        let path = path.display().to_string();
+        thread::spawn(move || {
+            launch_process_file_with_options(&path, "gridwalk_cancellation", &options)
+        })
+    };
+
+    // Cancel once the rows are being copied into the staging table
+    let started = Instant::now();
+    let staged = loop {
+        let staged = staging_tables(&mut client)
+            .into_iter()
+            .find(|table| !existing.contains(table));
+        if let Some(staged) = staged {
+            break staged;
+        }
+        assert!(
+            !load.is_finished() && started.elapsed() < Duration::from_secs(120),
+            "the load never staged a table"
+        );
+        thread::sleep(Duration::from_millis(50));
+    };
+    let cancelled = Instant::now();
+    cancellation.cancel();
+    let error = load
+        .join()
+        .expect("load thread")
+        .expect_err("load is cancelled");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(
+        cancelled.elapsed() < Duration::from_secs(10),
+        "the load took {:?} to stop",
+        cancelled.elapsed()
+    );
+    assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+    assert!(error
+        .get_ref()
+        .is_some_and(|inner| inner.is::<LoadCancelledError>()));
+    assert!(!staging_tables(&mut client).contains(&staged));
+}
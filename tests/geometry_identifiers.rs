@@ -0,0 +1,72 @@
+// Geometry columns whose names need quoting keep their case and spaces through to the sink
+use duckdb_postgis::duckdb_load::{
+    launch_process_file_with_options, DuckDbSink, ExportFormat, ExportSink, LoadOptions, Sink,
+};
+use std::fs;
+use std::path::PathBuf;
+
+const POINTS: &str = r#"{"type": "FeatureCollection", "features": [
+{"type": "Feature", "properties": {"name": "a"}, "geometry": {"type": "Point", "coordinates": [0.5, 51.5]}},
+{"type": "Feature", "properties": {"name": "b"}, "geometry": {"type": "Point", "coordinates": [-1.5, 53.0]}}]}"#;
+
+const RENAME: &str = r#"SELECT name, geom AS "Shape Geom" FROM data"#;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "gridwalk_identifiers_{}_{}",
+        name,
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).expect("scratch directory");
+    dir
+}
+
+fn source_file(dir: &PathBuf) -> String {
+    let source = dir.join("points.geojson");
+    fs::write(&source, POINTS).expect("source file");
+    source.to_string_lossy().to_string()
+}
+
+#[test]
+fn mixed_case_geometry_column_is_transformed_in_place() {
+    let dir = scratch_dir("native");
+    let options = LoadOptions {
+        sink: Sink::DuckDb(DuckDbSink::new(&dir.join("out.duckdb").to_string_lossy())),
+        sql_transform: Some(RENAME.to_string()),
+        target_crs: "3857".to_string(),
+        add_lon_lat_columns: true,
+        ..Default::default()
+    };
+    let result = launch_process_file_with_options(&source_file(&dir), "points", &options);
+    let _ = fs::remove_dir_all(&dir);
+
+    let result = result.expect("load succeeds");
+    assert_eq!(result.geometry_columns, vec!["Shape Geom".to_string()]);
+    let column_type = &result.geometry_column_types[0];
+    assert_eq!(column_type.column, "Shape Geom");
+    assert_eq!(column_type.geometry_type, "Point");
+}
+
+#[test]
+fn mixed_case_geometry_column_is_written_from_wkb() {
+    let dir = scratch_dir("wkb");
+    let output = dir.join("points.csv");
+    let options = LoadOptions {
+        sink: Sink::Export(ExportSink::new(
+            &output.to_string_lossy(),
+            ExportFormat::Csv,
+        )),
+        sql_transform: Some(RENAME.to_string()),
+        ..Default::default()
+    };
+    let result = launch_process_file_with_options(&source_file(&dir), "points", &options);
+    let written = fs::read_to_string(&output);
+    let _ = fs::remove_dir_all(&dir);
+
+    result.expect("load succeeds");
+    let written = written.expect("export file");
+    let header = written.lines().next().expect("header row");
+    assert!(header.contains("Shape Geom"), "header was {}", header);
+    assert!(!header.contains("_wkb"), "header was {}", header);
+    assert!(written.contains("POINT"), "export was {}", written);
+}
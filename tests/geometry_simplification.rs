@@ -0,0 +1,53 @@
+// Column types are resolved from the geometries as simplification and rounding leave them
+use duckdb_postgis::duckdb_load::{
+    launch_process_file_with_options, DuckDbSink, GeometrySimplification, LoadOptions, Sink,
+};
+use std::fs;
+use std::path::PathBuf;
+
+// Two unit squares joined by a neck 0.02 wide, which rounding to one decimal place closes
+const DUMBBELL: &str = r#"{"type": "FeatureCollection", "features": [{"type": "Feature", "properties": {"name": "dumbbell"},
+"geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 0.5], [2, 0.5], [2, 0], [3, 0], [3, 1], [2, 1],
+[2, 0.52], [1, 0.52], [1, 1], [0, 1], [0, 0]]]}}]}"#;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "gridwalk_simplification_{}_{}",
+        name,
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).expect("scratch directory");
+    dir
+}
+
+fn load_dumbbell(name: &str, simplification: Option<GeometrySimplification>) -> String {
+    let dir = scratch_dir(name);
+    let source = dir.join("dumbbell.geojson");
+    fs::write(&source, DUMBBELL).expect("source file");
+    let options = LoadOptions {
+        sink: Sink::DuckDb(DuckDbSink::new(&dir.join("out.duckdb").to_string_lossy())),
+        simplification,
+        ..Default::default()
+    };
+    let result = launch_process_file_with_options(&source.to_string_lossy(), "dumbbell", &options)
+        .expect("load succeeds");
+    let _ = fs::remove_dir_all(&dir);
+    result.geometry_column_types[0].geometry_type.clone()
+}
+
+#[test]
+fn unsimplified_polygon_keeps_its_type() {
+    assert_eq!(load_dumbbell("plain", None), "Polygon");
+}
+
+#[test]
+fn rounding_that_splits_a_polygon_resolves_the_split_type() {
+    let simplification = GeometrySimplification {
+        tolerance: None,
+        decimal_places: Some(1),
+    };
+    assert_eq!(
+        load_dumbbell("rounded", Some(simplification)),
+        "MultiPolygon"
+    );
+}
@@ -0,0 +1,35 @@
+// Loads with write_load_history are recorded in gridwalk_loads whether they succeed or not
+// Run with: GRIDWALK_TEST_PG=postgres://... cargo test --test load_history
+use duckdb_postgis::duckdb_load::{
+    launch_process_file_with_options, list_loads, LoadOptions, LoadStatus,
+};
+use std::fs;
+
+#[test]
+fn failed_load_is_recorded_with_its_error() {
+    let Ok(postgres_connection) = std::env::var("GRIDWALK_TEST_PG") else {
+        println!("Set GRIDWALK_TEST_PG to the Postgres database to load into");
+        return;
+    };
+    let table_name = format!("gridwalk_history_{}", std::process::id());
+    let source = std::env::temp_dir().join(format!("{}.csv", table_name));
+    fs::write(&source, "id,name\n1,a\n2,b\n").expect("source file");
+
+    let options = LoadOptions {
+        postgres_connection: postgres_connection.clone(),
+        write_load_history: true,
+        sql_transform: Some("SELECT missing_column FROM data".to_string()),
+        ..Default::default()
+    };
+    let result = launch_process_file_with_options(&source.to_string_lossy(), &table_name, &options);
+    let _ = fs::remove_file(&source);
+    assert!(result.is_err(), "the transform names a missing column");
+
+    let entries = list_loads(&postgres_connection, None, &table_name).expect("load history");
+    let entry = entries.first().expect("the failed load is recorded");
+    assert_eq!(entry.status, LoadStatus::Failed);
+    assert_eq!(entry.row_count, None);
+    assert_eq!(entry.file_type, "Csv");
+    let error = entry.error.as_deref().expect("the error is recorded");
+    assert!(error.contains("missing_column"), "error was {}", error);
+}
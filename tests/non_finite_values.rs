@@ -0,0 +1,136 @@
+// NaN and infinite values in DOUBLE columns are kept, nulled, replaced with a sentinel or fail the load
+use duckdb::Connection;
+use duckdb_postgis::duckdb_load::{
+    launch_process_file_with_options, DuckDbSink, LoadOptions, LoadResult, NonFinitePolicy, Sink,
+};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "gridwalk_non_finite_{}_{}",
+        name,
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).expect("scratch directory");
+    dir
+}
+
+// Loads readings of 1.5, NaN, inf and -inf, returning the result and the values as loaded in id order
+fn load_readings(
+    name: &str,
+    policy: NonFinitePolicy,
+) -> Result<(LoadResult, Vec<Option<f64>>), io::Error> {
+    let dir = scratch_dir(name);
+    let source = dir.join("readings.parquet");
+    let output = dir.join("out.duckdb");
+    Connection::open_in_memory()
+        .and_then(|conn| {
+            conn.execute_batch(&format!(
+                "COPY (SELECT * FROM (VALUES (1, 1.5::DOUBLE), (2, 'nan'::DOUBLE), (3, 'inf'::DOUBLE),
+                    (4, '-inf'::DOUBLE)) AS readings(id, value)) TO '{}' (FORMAT parquet);",
+                source.display()
+            ))
+        })
+        .expect("source file");
+
+    let options = LoadOptions {
+        sink: Sink::DuckDb(DuckDbSink::new(&output.to_string_lossy())),
+        non_finite_policy: policy,
+        ..Default::default()
+    };
+    let result = launch_process_file_with_options(&source.to_string_lossy(), "readings", &options)
+        .map(|result| {
+            let conn = Connection::open(&output).expect("output database");
+            let mut stmt = conn
+                .prepare("SELECT value FROM readings ORDER BY id;")
+                .expect("loaded table");
+            let values = stmt
+                .query_map([], |row| row.get::<_, Option<f64>>(0))
+                .expect("loaded values")
+                .collect::<Result<Vec<_>, _>>()
+                .expect("loaded values");
+            (result, values)
+        });
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn replacement_lineage(result: &LoadResult) -> Option<&str> {
+    result
+        .lineage
+        .iter()
+        .find(|entry| entry.stage == "non_finite_values")
+        .map(|entry| entry.description.as_str())
+}
+
+#[test]
+fn null_policy_replaces_nan_and_infinities_with_null() {
+    let (result, values) = load_readings("null", NonFinitePolicy::Null).expect("load succeeds");
+    assert_eq!(values, vec![Some(1.5), None, None, None]);
+    assert_eq!(
+        replacement_lineage(&result),
+        Some("Replaced 3 NaN or infinite values with NULL")
+    );
+}
+
+#[test]
+fn keep_policy_writes_the_values_as_they_are() {
+    let (result, values) = load_readings("keep", NonFinitePolicy::Keep).expect("load succeeds");
+    assert_eq!(values[0], Some(1.5));
+    assert!(values[1].is_some_and(f64::is_nan));
+    assert_eq!(values[2], Some(f64::INFINITY));
+    assert_eq!(values[3], Some(f64::NEG_INFINITY));
+    assert_eq!(replacement_lineage(&result), None);
+}
+
+#[test]
+fn sentinel_policy_replaces_values_with_the_sentinel() {
+    let (result, values) =
+        load_readings("sentinel", NonFinitePolicy::Sentinel(-9999.0)).expect("load succeeds");
+    assert_eq!(
+        values,
+        vec![Some(1.5), Some(-9999.0), Some(-9999.0), Some(-9999.0)]
+    );
+    assert_eq!(
+        replacement_lineage(&result),
+        Some("Replaced 3 NaN or infinite values with -9999")
+    );
+}
+
+// Rust writes 1e300 out in full, which SQL would read as an out of range DECIMAL rather than a DOUBLE
+#[test]
+fn large_sentinel_is_written_as_a_double() {
+    let (_, values) =
+        load_readings("large", NonFinitePolicy::Sentinel(1e300)).expect("load succeeds");
+    assert_eq!(
+        values,
+        vec![Some(1.5), Some(1e300), Some(1e300), Some(1e300)]
+    );
+}
+
+#[test]
+fn non_finite_sentinel_is_rejected() {
+    let error = load_readings("nan_sentinel", NonFinitePolicy::Sentinel(f64::NAN))
+        .expect_err("the sentinel is rejected");
+    assert!(
+        error
+            .to_string()
+            .contains("The sentinel for NaN and infinite values must be finite, not NaN"),
+        "error was {}",
+        error
+    );
+}
+
+#[test]
+fn fail_policy_fails_the_load() {
+    let error = load_readings("fail", NonFinitePolicy::Fail).expect_err("the load fails");
+    assert!(
+        error
+            .to_string()
+            .contains("Column value contains 3 NaN or infinite values"),
+        "error was {}",
+        error
+    );
+}
@@ -0,0 +1,127 @@
+// Planning how an append reconciles the source's columns with the existing table
+use duckdb_postgis::duckdb_load::{
+    IncompatibleChangePolicy, IncompatibleSchemaError, SchemaChangeKind, SchemaEvolution,
+    SchemaEvolutionReport,
+};
+
+fn columns(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, data_type)| (name.to_string(), data_type.to_string()))
+        .collect()
+}
+
+fn kinds(report: &SchemaEvolutionReport) -> Vec<(&str, SchemaChangeKind)> {
+    report
+        .changes
+        .iter()
+        .map(|change| (change.column.as_str(), change.kind))
+        .collect()
+}
+
+#[test]
+fn new_columns_are_added_and_narrow_columns_widened() {
+    let target = columns(&[
+        ("id", "integer"),
+        ("name", "character varying(20)"),
+        ("area", "real"),
+        ("notes", "text"),
+    ]);
+    let source = columns(&[
+        ("id", "bigint"),
+        ("name", "text"),
+        ("area", "real"),
+        ("notes", "integer"),
+        ("survey_date", "date"),
+    ]);
+    let report = SchemaEvolution::default()
+        .plan(&source, &target)
+        .expect("every change is compatible");
+    assert_eq!(
+        kinds(&report),
+        vec![
+            ("id", SchemaChangeKind::Widened),
+            ("name", SchemaChangeKind::Widened),
+            ("notes", SchemaChangeKind::Cast),
+            ("survey_date", SchemaChangeKind::Added),
+        ]
+    );
+}
+
+#[test]
+fn narrower_source_types_are_cast_without_changing_the_table() {
+    let target = columns(&[("count", "bigint"), ("value", "double precision")]);
+    let source = columns(&[("count", "smallint"), ("value", "integer")]);
+    let report = SchemaEvolution::default()
+        .plan(&source, &target)
+        .expect("compatible");
+    assert_eq!(
+        kinds(&report),
+        vec![
+            ("count", SchemaChangeKind::Cast),
+            ("value", SchemaChangeKind::Cast),
+        ]
+    );
+}
+
+#[test]
+fn columns_missing_from_the_source_are_reported() {
+    let target = columns(&[("id", "integer"), ("legacy_code", "text")]);
+    let source = columns(&[("id", "integer")]);
+    let report = SchemaEvolution::default()
+        .plan(&source, &target)
+        .expect("compatible");
+    assert_eq!(
+        kinds(&report),
+        vec![("legacy_code", SchemaChangeKind::Missing)]
+    );
+}
+
+#[test]
+fn incompatible_changes_fail_or_are_skipped() {
+    let target = columns(&[("opened", "date"), ("id", "integer")]);
+    let source = columns(&[("opened", "text"), ("id", "integer")]);
+
+    let error = SchemaEvolution::default()
+        .plan(&source, &target)
+        .expect_err("text can't go into a date column");
+    let error = error
+        .downcast_ref::<IncompatibleSchemaError>()
+        .expect("an IncompatibleSchemaError");
+    assert_eq!(
+        kinds(&error.report),
+        vec![("opened", SchemaChangeKind::Incompatible)]
+    );
+
+    let evolution = SchemaEvolution {
+        on_incompatible: IncompatibleChangePolicy::SkipColumn,
+        ..Default::default()
+    };
+    let report = evolution.plan(&source, &target).expect("skipped");
+    assert_eq!(report.incompatible().count(), 1);
+}
+
+#[test]
+fn widening_can_be_turned_off() {
+    let target = columns(&[("id", "integer")]);
+    let source = columns(&[("id", "bigint"), ("extra", "text")]);
+    let evolution = SchemaEvolution {
+        widen_types: false,
+        on_incompatible: IncompatibleChangePolicy::SkipColumn,
+        ..Default::default()
+    };
+    let report = evolution.plan(&source, &target).expect("skipped");
+    assert_eq!(
+        kinds(&report),
+        vec![
+            ("id", SchemaChangeKind::Incompatible),
+            ("extra", SchemaChangeKind::Added),
+        ]
+    );
+
+    let evolution = SchemaEvolution {
+        add_columns: false,
+        ..Default::default()
+    };
+    assert!(evolution.plan(&source, &target).is_err());
+}
@@ -0,0 +1,149 @@
+// Feeds sniff_bytes truncated, corrupted and random input - it must return a type or None, and never panic
+use duckdb_postgis::duckdb_load::{sniff_bytes, FileType};
+
+const PREFIX_BYTES: usize = 64 * 1024;
+
+// Small deterministic generator so failures can be replayed
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    // Bytes drawn from the characters JSON and CSV detection look for
+    fn text_soup(&mut self, len: usize) -> Vec<u8> {
+        const ALPHABET: &[u8] = b"{}[]\":,\\ \t\r\n\xef\xbb\xbfabtypeFeatureCollection0123456789-.";
+        (0..len)
+            .map(|_| ALPHABET[self.below(ALPHABET.len())])
+            .collect()
+    }
+}
+
+fn samples() -> Vec<(Vec<u8>, FileType)> {
+    let mut duckdb = vec![0u8; 8];
+    duckdb.extend_from_slice(b"DUCK");
+    duckdb.extend_from_slice(&[0; 20]);
+    vec![
+        (b"PK\x03\x04\x14\x00\x06\x00".to_vec(), FileType::Excel),
+        (b"SQLite format 3\0\x10\x00".to_vec(), FileType::Geopackage),
+        (vec![0, 0, 39, 10, 0, 0, 0, 0], FileType::Shapefile),
+        (b"PAR1\x15\x04\x15".to_vec(), FileType::Parquet),
+        (duckdb, FileType::DuckDb),
+        (b"ARROW1\0\0\xff\xff".to_vec(), FileType::Arrow),
+        (
+            b"\0\0\0\x0e\x0a\x09OSMHeader\x18\x7c".to_vec(),
+            FileType::OsmPbf,
+        ),
+        (
+            b"<?xml version=\"1.0\"?>\n<gpx version=\"1.1\"><wpt lat=\"1\" lon=\"2\"/></gpx>"
+                .to_vec(),
+            FileType::Gpx,
+        ),
+        (
+            br#"{"type": "FeatureCollection", "features": []}"#.to_vec(),
+            FileType::Geojson,
+        ),
+        (b"name,x,y\na,1,2\nb,3,4\n".to_vec(), FileType::Csv),
+    ]
+}
+
+#[test]
+fn empty_input_is_unknown() {
+    assert_eq!(sniff_bytes(&[]), None);
+}
+
+#[test]
+fn complete_samples_are_detected() {
+    for (bytes, file_type) in samples() {
+        assert_eq!(sniff_bytes(&bytes), Some(file_type), "{:?}", bytes);
+    }
+}
+
+#[test]
+fn truncated_samples_do_not_panic() {
+    for (bytes, _) in samples() {
+        for len in 0..=bytes.len() {
+            sniff_bytes(&bytes[..len]);
+        }
+    }
+}
+
+#[test]
+fn corrupted_samples_do_not_panic() {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    for (bytes, _) in samples() {
+        for _ in 0..2_000 {
+            let mut corrupted = bytes.clone();
+            for _ in 0..=rng.below(4) {
+                let i = rng.below(corrupted.len());
+                corrupted[i] = rng.next() as u8;
+            }
+            corrupted.truncate(rng.below(corrupted.len() + 1));
+            sniff_bytes(&corrupted);
+        }
+    }
+}
+
+#[test]
+fn random_input_does_not_panic() {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20_000 {
+        let len = rng.below(64);
+        sniff_bytes(&rng.bytes(len));
+        sniff_bytes(&rng.text_soup(len * 4));
+    }
+}
+
+// Text detection drops a partial last line once the input fills the detection prefix
+#[test]
+fn input_around_the_prefix_size_does_not_panic() {
+    let mut rng = XorShift(0xdead_beef_cafe_f00d);
+    for len in [
+        PREFIX_BYTES - 1,
+        PREFIX_BYTES,
+        PREFIX_BYTES + 1,
+        2 * PREFIX_BYTES,
+    ] {
+        sniff_bytes(&rng.bytes(len));
+        sniff_bytes(&rng.text_soup(len));
+        sniff_bytes(&vec![b'{'; len]);
+        sniff_bytes(&vec![b'\\'; len]);
+        sniff_bytes(&"a,b\n".repeat(len / 4).into_bytes());
+    }
+}
+
+#[test]
+fn deeply_nested_json_does_not_panic() {
+    let mut nested = br#"{"crs": "#.to_vec();
+    nested.extend(vec![b'['; PREFIX_BYTES]);
+    assert_eq!(sniff_bytes(&nested), None);
+}
+
+// Legacy encodings are detected as CSV and converted when the file is read
+#[test]
+fn csv_in_legacy_encodings_is_detected() {
+    // 'Café' and '€' in Windows-1252, and 'Zürich' in Latin-1
+    let windows_1252 = b"name,price\nCaf\xe9,\x805\nTea,3\n";
+    let latin_1 = b"city,population\nZ\xfcrich,421878\nBern,134794\n";
+    let mut utf_16 = vec![0xFF, 0xFE];
+    for unit in "name,x\nStraße,1\n".encode_utf16() {
+        utf_16.extend(unit.to_le_bytes());
+    }
+    for bytes in [&windows_1252[..], &latin_1[..], &utf_16[..]] {
+        assert_eq!(sniff_bytes(bytes), Some(FileType::Csv), "{:?}", bytes);
+    }
+    // Control characters still mark binary data
+    assert_eq!(sniff_bytes(b"a,b\x01\nc,d\x02\n"), None);
+}
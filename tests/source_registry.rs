@@ -0,0 +1,136 @@
+// Built-in detectors on their own, and registered handlers competing with them
+use duckdb_postgis::duckdb_load::{
+    Confidence, DetectedSource, FileType, LoadOptions, SourceHandler, SourceRegistry,
+};
+use std::error::Error;
+
+const GPX: &[u8] = br#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test"><trk><trkseg><trkpt lat="51.5" lon="-0.1"/></trkseg></trk></gpx>"#;
+
+const TOPOJSON: &[u8] = br#"{"type": "Topology", "objects": {}, "arcs": []}"#;
+
+struct TopoJson;
+
+impl SourceHandler for TopoJson {
+    fn name(&self) -> String {
+        "TopoJson".to_string()
+    }
+
+    fn detect(&self, prefix: &[u8]) -> Option<Confidence> {
+        let text = std::str::from_utf8(prefix).ok()?;
+        text.contains(r#""Topology""#).then_some(Confidence::Medium)
+    }
+
+    fn ingest_sql(&self, path: &str, _options: &LoadOptions) -> Result<String, Box<dyn Error>> {
+        Ok(format!("SELECT * FROM ST_Read('{}')", path))
+    }
+}
+
+// Claims anything with a comma, as a handler for a CSV dialect might
+struct CsvDialect(&'static str, Confidence);
+
+impl SourceHandler for CsvDialect {
+    fn name(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn detect(&self, prefix: &[u8]) -> Option<Confidence> {
+        prefix.contains(&b',').then_some(self.1)
+    }
+
+    fn ingest_sql(&self, path: &str, _options: &LoadOptions) -> Result<String, Box<dyn Error>> {
+        Ok(format!("SELECT * FROM read_csv('{}')", path))
+    }
+}
+
+fn name(detected: Option<DetectedSource>) -> Option<String> {
+    detected.map(|detected| detected.name())
+}
+
+#[test]
+fn built_in_detectors_only_match_their_own_format() {
+    let samples: [(&[u8], FileType, Confidence); 6] = [
+        (b"PAR1\x15\x04\x15", FileType::Parquet, Confidence::High),
+        (b"ARROW1\0\0\xff\xff", FileType::Arrow, Confidence::High),
+        (
+            b"\0\0\0\x0e\x0a\x09OSMHeader\x18\x7c",
+            FileType::OsmPbf,
+            Confidence::High,
+        ),
+        (GPX, FileType::Gpx, Confidence::Medium),
+        (
+            br#"{"type": "Feature", "geometry": null}"#,
+            FileType::Geojson,
+            Confidence::Medium,
+        ),
+        (b"name,x,y\na,1,2\n", FileType::Csv, Confidence::Low),
+    ];
+    for (bytes, file_type, confidence) in samples {
+        for other in FileType::BUILT_IN {
+            let expected = (other == file_type).then_some(confidence);
+            assert_eq!(other.detect(bytes), expected, "{:?} on {:?}", other, bytes);
+        }
+    }
+}
+
+#[test]
+fn json_that_is_not_geojson_is_not_csv() {
+    let json = br#"{"a": 1, "b": 2}
+{"a": 3, "b": 4}"#;
+    assert_eq!(FileType::Geojson.detect(json), None);
+    assert_eq!(FileType::Csv.detect(json), None);
+}
+
+#[test]
+fn gpx_is_detected_after_comments_and_not_from_other_xml() {
+    let commented =
+        b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<!-- exported -->\n<gpx\n version=\"1.1\">";
+    assert_eq!(FileType::Gpx.detect(commented), Some(Confidence::Medium));
+    assert_eq!(FileType::Gpx.detect(b"<?xml version=\"1.0\"?><kml>"), None);
+    assert_eq!(FileType::Gpx.detect(b"<gpxdata>"), None);
+}
+
+#[test]
+fn registered_detection_has_no_built_in_detector() {
+    assert_eq!(FileType::Registered.detect(TOPOJSON), None);
+}
+
+#[test]
+fn registered_handlers_detect_new_formats() {
+    let mut registry = SourceRegistry::new();
+    assert_eq!(name(registry.detect(TOPOJSON)), None);
+    registry.register(TopoJson);
+    let detected = registry.detect(TOPOJSON).expect("TopoJSON is detected");
+    assert_eq!(detected.name(), "TopoJson");
+    assert_eq!(detected.file_type(), FileType::Registered);
+    assert_eq!(
+        name(registry.detect(b"PAR1\x15\x04\x15")),
+        Some("Parquet".to_string())
+    );
+}
+
+#[test]
+fn registered_handlers_win_ties_with_built_in_types() {
+    let csv = b"name,x,y\na,1,2\n";
+    let mut registry = SourceRegistry::new();
+    registry.register(CsvDialect("dialect", Confidence::Low));
+    assert_eq!(name(registry.detect(csv)), Some("dialect".to_string()));
+
+    // A less confident handler does not take over magic bytes
+    assert_eq!(
+        name(registry.detect(b"PAR1,\x04\x15")),
+        Some("Parquet".to_string())
+    );
+}
+
+#[test]
+fn the_most_confident_handler_wins_and_earlier_registrations_win_ties() {
+    let csv = b"name,x,y\na,1,2\n";
+    let mut registry = SourceRegistry::new();
+    registry
+        .register(CsvDialect("low", Confidence::Low))
+        .register(CsvDialect("first", Confidence::Medium))
+        .register(CsvDialect("second", Confidence::Medium));
+    assert_eq!(name(registry.detect(csv)), Some("first".to_string()));
+    assert_eq!(registry.names(), vec!["low", "first", "second"]);
+}